@@ -3,6 +3,30 @@
 use crate::direction_bitboards;
 use crate::fixed_vecor::FixedVector;
 
+// Bitboards of every bit in a file or rank
+// Files and ranks are numbered the same as the columns and rows below, so file 0 is the h file and rank 0 is the eighth rank
+pub const FILE_MASKS: [u64; 8] = [
+    0x0101010101010101,
+    0x0202020202020202,
+    0x0404040404040404,
+    0x0808080808080808,
+    0x1010101010101010,
+    0x2020202020202020,
+    0x4040404040404040,
+    0x8080808080808080,
+];
+
+pub const RANK_MASKS: [u64; 8] = [
+    0x00000000000000FF,
+    0x000000000000FF00,
+    0x0000000000FF0000,
+    0x00000000FF000000,
+    0x000000FF00000000,
+    0x0000FF0000000000,
+    0x00FF000000000000,
+    0xFF00000000000000,
+];
+
 // Chesboard indices (corresponds to bits in the bitboards)
 //
 //      C7 C6 C5 C4 C3 C2 C1 C0
@@ -16,6 +40,29 @@ use crate::fixed_vecor::FixedVector;
 // R6 | 55 54 53 52 51 50 49 48
 // R7 | 63 62 61 60 59 58 57 56
 
+// Square name of every bit, laid out the same as the diagram above (bit 0 is h8 and bit 63 is a1)
+// This is the one place the coordinate convention is written down, conversions between bits and squares go through it
+const SQUARE_NAMES: [&str; 64] = [
+    "h8", "g8", "f8", "e8", "d8", "c8", "b8", "a8",
+    "h7", "g7", "f7", "e7", "d7", "c7", "b7", "a7",
+    "h6", "g6", "f6", "e6", "d6", "c6", "b6", "a6",
+    "h5", "g5", "f5", "e5", "d5", "c5", "b5", "a5",
+    "h4", "g4", "f4", "e4", "d4", "c4", "b4", "a4",
+    "h3", "g3", "f3", "e3", "d3", "c3", "b3", "a3",
+    "h2", "g2", "f2", "e2", "d2", "c2", "b2", "a2",
+    "h1", "g1", "f1", "e1", "d1", "c1", "b1", "a1",
+];
+
+// Returns the algebraic name of a bit, e.g. 59 is e1
+pub fn square_name(bit: u8) -> &'static str {
+    SQUARE_NAMES[bit as usize]
+}
+
+// Returns the bit for an algebraic square name, the opposite of square_name
+pub fn square_bit(name: &str) -> Option<u8> {
+    SQUARE_NAMES.iter().position(|&square_name| square_name == name).map(|bit| bit as u8)
+}
+
 // Shifts a direction bitboard so that it is alligned with a piece at $piece_coordinates
 // This needs to be done to obtain a move bitboard for any piece
 pub fn shift_direction_bitboard(piece_bit: u8, piece_coordinates: (i8, i8), direction_bitboard: &direction_bitboards::DirectionBitboard) -> u64 {
@@ -139,6 +186,29 @@ pub fn get_piece_coordinates(piece_bit: u8) -> (i8, i8) {
     (piece_column as i8, piece_row as i8)
 }
 
+// Returns a bitboard of the bits between two bits, not including either of them
+// Returns None if the bits aren't on the same row, column, or diagonal
+pub fn bits_between(bit_a: u8, bit_b: u8) -> Option<u64> {
+    let (column_a, row_a) = get_piece_coordinates(bit_a);
+    let (column_b, row_b) = get_piece_coordinates(bit_b);
+
+    let (dx, dy) = (column_b - column_a, row_b - row_a);
+
+    if bit_a == bit_b || !(dx == 0 || dy == 0 || dx.abs() == dy.abs()) {
+        return None;
+    }
+
+    // Step from bit a towards bit b
+    let mut output: u64 = 0;
+    let mut coordinates = (column_a + dx.signum(), row_a + dy.signum());
+    while coordinates != (column_b, row_b) {
+        output |= 1 << get_piece_bit(coordinates);
+        coordinates = (coordinates.0 + dx.signum(), coordinates.1 + dy.signum());
+    }
+
+    Some(output)
+}
+
 // Remove floating ends of a masked vertical move bitboard
 // Only does this in one direction (has to be called twice to remove both ends)
 //
@@ -212,35 +282,56 @@ fn remove_byte_ends(bit: i8, test_byte: u8, check_up: bool) -> (u8, Option<i8>){
 }
 
 // Returns a vector containing the bits that are on in a u64 number
+// If more than L bits are on, the vector is filled and the remaining bits are ignored
+// Use bits_on_checked if this shouldn't happen
 pub fn bits_on<const L: usize>(num: u64, placeholder_num: u8) -> FixedVector<u8, L> {
     let mut bits_on_vector = FixedVector::new(placeholder_num);
 
+    // Take the lowest bit that is on and turn it off, until no bits are left or the vector is full
     let mut num = num;
+    while bits_on_vector.len() < L {
+        match pop_lowest_bit(&mut num) {
+            Some(bit) => bits_on_vector.push(bit),
+            None => break,
+        }
+    }
 
-    let mut bits_counted = 0;
-    while bits_counted < 64 && bits_on_vector.len() < L {
-        let trailing_zeros = num.trailing_zeros() as u8;
+    bits_on_vector
+}
 
-        if num == 0 {
-            break;
-        }
+// Same as bits_on, but returns an error containing the number of bits that are on if they don't fit in the vector
+pub fn bits_on_checked<const L: usize>(num: u64, placeholder_num: u8) -> Result<FixedVector<u8, L>, u32> {
+    let bit_count = count_bits(num);
 
-        // Count trailing zeros to avoid having to iterate over every bit
-        if trailing_zeros > 0 {
-            bits_counted += trailing_zeros;
-            num >>= trailing_zeros;
-        } else {
-            
-            // Add bit 0 (which will be on if there are no trailing zeros)
-            // to output vector
-            bits_on_vector.push(bits_counted);
-            num >>= 1;
+    if bit_count as usize > L {
+        return Err(bit_count);
+    }
 
-            bits_counted += 1;
-        }
+    Ok(bits_on(num, placeholder_num))
+}
+
+// Returns the number of bits that are on in a u64 number
+// Use this instead of bits_on when only the count is needed
+pub fn count_bits(num: u64) -> u32 {
+    num.count_ones()
+}
+
+// Returns the lowest bit that is on in a u64 number
+pub fn lowest_bit(num: u64) -> Option<u8> {
+    if num == 0 {
+        return None;
     }
 
-    bits_on_vector
+    Some(num.trailing_zeros() as u8)
+}
+
+// Returns the lowest bit that is on in a u64 number and turns it off
+// Can be called in a loop to iterate over every bit that is on, without the capacity limit of bits_on
+pub fn pop_lowest_bit(num: &mut u64) -> Option<u8> {
+    let bit = lowest_bit(*num)?;
+    *num &= *num - 1;
+
+    Some(bit)
 }
 
 
@@ -261,6 +352,7 @@ where
 
 
 // Instead of doing a bitwise shift on an entire 64 bit number only shift each byte
+// Bits shifted past the end of a byte are dropped, so moves near the a and h files never wrap to the other side of the board
 // Sign of shift variable indicates direction
 // + (right)
 // - (left)
@@ -269,10 +361,11 @@ pub fn shift_bytes(num: u64, shift: i8) -> u64 {
     for i in 0..8 {
         let current_byte = isolate_byte(&num, i);
 
+        // Shifting a byte by 8 or more clears it
         let new_byte = if shift < 0 {
-            current_byte << shift.abs()
+            current_byte.checked_shl(shift.unsigned_abs() as u32).unwrap_or(0)
         } else {
-            current_byte >> shift
+            current_byte.checked_shr(shift as u32).unwrap_or(0)
         };
 
         output |= (new_byte as u64) << (i * 8)
@@ -285,11 +378,52 @@ pub fn shift_bytes(num: u64, shift: i8) -> u64 {
 // Sign of shift variable indicates direction
 // + (right)
 // - (left)
+// Shifts of 64 or more give 0
 pub fn shift_u64(num_to_shift: u64, shift: i8) -> u64 {
     if shift < 0 {
-        return num_to_shift << shift.abs();
+        return num_to_shift.checked_shl(shift.unsigned_abs() as u32).unwrap_or(0);
+    }
+    num_to_shift.checked_shr(shift as u32).unwrap_or(0)
+}
+
+
+// Returns the file (column) of a bit
+pub fn file_of(bit: u8) -> u8 {
+    bit % 8
+}
+
+// Returns the rank (row) of a bit
+pub fn rank_of(bit: u8) -> u8 {
+    bit / 8
+}
+
+// Returns the number of king moves between two bits (0 to 7)
+pub fn chebyshev_distance(a: u8, b: u8) -> u8 {
+    let (a_column, a_row) = get_piece_coordinates(a);
+    let (b_column, b_row) = get_piece_coordinates(b);
+    (a_column - b_column).unsigned_abs().max((a_row - b_row).unsigned_abs())
+}
+
+// Returns the number of rook moves of one bit between two bits, the column distance plus the row distance (0 to 14)
+pub fn manhattan_distance(a: u8, b: u8) -> u8 {
+    let (a_column, a_row) = get_piece_coordinates(a);
+    let (b_column, b_row) = get_piece_coordinates(b);
+    (a_column - b_column).unsigned_abs() + (a_row - b_row).unsigned_abs()
+}
+
+// Returns a bitboard with every bit in the files either side of $file on
+pub fn adjacent_files_mask(file: u8) -> u64 {
+    let mut mask = 0;
+
+    if file > 0 {
+        mask |= FILE_MASKS[file as usize - 1];
+    }
+
+    if file < 7 {
+        mask |= FILE_MASKS[file as usize + 1];
     }
-    num_to_shift >> shift
+
+    mask
 }
 
 
@@ -326,6 +460,14 @@ mod tests {
         assert_eq!(get_piece_bit(get_piece_coordinates(32)), 32);
     }
 
+    #[test]
+    fn test_bits_between() {
+        assert_eq!(bits_between(59, 3), Some(1 << 51 | 1 << 43 | 1 << 35 | 1 << 27 | 1 << 19 | 1 << 11));
+        assert_eq!(bits_between(38, 59), Some(1 << 45 | 1 << 52));
+        assert_eq!(bits_between(59, 58), Some(0));
+        assert_eq!(bits_between(59, 42), None);
+    }
+
     #[test]
     fn test_shift_bytes() {
         let expected_right_shift: u64 = 0b1100000001100000001100000001100000001100000001100000001100000001 ^ DIAGONAL_RIGHT.bitboard;
@@ -333,6 +475,22 @@ mod tests {
 
         assert_eq!(shift_bytes(DIAGONAL_RIGHT.bitboard, 1), expected_right_shift);
         assert_eq!(shift_bytes(DIAGONAL_RIGHT.bitboard, -2), expected_left_shift);
+
+        // Shifts of a byte or more clear every byte
+        assert_eq!(shift_bytes(u64::MAX, 7), 0x0101010101010101);
+        assert_eq!(shift_bytes(u64::MAX, 8), 0);
+        assert_eq!(shift_bytes(u64::MAX, -8), 0);
+        assert_eq!(shift_bytes(u64::MAX, i8::MIN), 0);
+    }
+
+    #[test]
+    fn test_shift_u64() {
+        assert_eq!(shift_u64(u64::MAX, 63), 1);
+        assert_eq!(shift_u64(1, -63), 1 << 63);
+        assert_eq!(shift_u64(u64::MAX, 64), 0);
+        assert_eq!(shift_u64(u64::MAX, -64), 0);
+        assert_eq!(shift_u64(u64::MAX, i8::MAX), 0);
+        assert_eq!(shift_u64(u64::MAX, i8::MIN), 0);
     }
 
     #[test]
@@ -371,6 +529,33 @@ mod tests {
         assert_eq!((remove_byte_ends(4, test_byte, true)), (expected_u, Some(6)));
     }
 
+    #[test]
+    fn test_adjacent_files_mask() {
+        assert_eq!(adjacent_files_mask(0), 0x0202020202020202);
+        assert_eq!(adjacent_files_mask(7), 0x4040404040404040);
+        assert_eq!(adjacent_files_mask(3), 0x1414141414141414);
+        assert_eq!(adjacent_files_mask(3), FILE_MASKS[2] | FILE_MASKS[4]);
+    }
+
+    #[test]
+    fn test_file_and_rank_masks() {
+        assert_eq!(FILE_MASKS[0] & FILE_MASKS[7], 0);
+        assert_eq!(RANK_MASKS[0] & RANK_MASKS[7], 0);
+        assert_eq!(FILE_MASKS.iter().fold(0, |all, mask| all | mask), u64::MAX);
+        assert_eq!(RANK_MASKS.iter().fold(0, |all, mask| all | mask), u64::MAX);
+
+        for i in 0..8 {
+            assert_eq!(count_bits(FILE_MASKS[i]), 8);
+            assert_eq!(count_bits(RANK_MASKS[i]), 8);
+        }
+
+        for bit in 0..64 {
+            let (column, row) = get_piece_coordinates(bit);
+            assert_eq!((file_of(bit) as i8, rank_of(bit) as i8), (column, row));
+            assert!(bit_on(FILE_MASKS[file_of(bit) as usize], bit) && bit_on(RANK_MASKS[rank_of(bit) as usize], bit));
+        }
+    }
+
     #[test]
     fn test_bit_on() {
         assert_eq!(bit_on(129, 7), true);
@@ -417,8 +602,53 @@ mod tests {
 
         let num: u64 = 0b1000000000000000000000000000000000000000000000000000000000000000;
         assert_eq!(bits_on(131071, 255).internal_array, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 255, 255]);
-        assert_eq!(bits_on(num, 21).internal_array, [63, 21])
+        assert_eq!(bits_on(num, 21).internal_array, [63, 21]);
+
+        // No bits on gives an empty vector of placeholders
+        let result: FixedVector<u8, 4> = bits_on(0, 255);
+        assert_eq!(result.len(), 0);
+        assert_eq!(result.internal_array, [255; 4]);
+
+        // Every bit on, and more bits on than the vector can hold
+        let result: FixedVector<u8, 64> = bits_on(u64::MAX, 255);
+        assert_eq!(result.len(), 64);
+        assert_eq!(result.internal_array, std::array::from_fn(|i| i as u8));
+
+        let result: FixedVector<u8, 3> = bits_on(u64::MAX, 255);
+        assert_eq!(result.internal_array, [0, 1, 2]);
+    }
+
+    #[test]
+    fn test_bits_on_checked() {
+        let result: Result<FixedVector<u8, 16>, u32> = bits_on_checked(u64::MAX, 255);
+        assert_eq!(result, Err(64));
+
+        let result: Result<FixedVector<u8, 16>, u32> = bits_on_checked(20, 255);
+        assert_eq!(result, Ok(bits_on(20, 255)));
 
+        let result: Result<FixedVector<u8, 2>, u32> = bits_on_checked(20, 255);
+        assert_eq!(result.map(|v| v.len()), Ok(2));
+    }
+
+    #[test]
+    fn test_count_and_pop_bits() {
+        for num in [0, 20, 2164, 131071, u64::MAX, 1 << 63] {
+            let bits_on_vec: FixedVector<u8, 64> = bits_on(num, 255);
+            assert_eq!(count_bits(num) as usize, bits_on_vec.len());
+
+            // Popping the lowest bit should give the same bits in the same order as bits_on
+            let mut popped_bits = Vec::new();
+            let mut remaining = num;
+            while let Some(bit) = pop_lowest_bit(&mut remaining) {
+                popped_bits.push(bit);
+            }
+
+            assert_eq!(remaining, 0);
+            assert_eq!(popped_bits, bits_on_vec.internal_array[0..bits_on_vec.len()]);
+        }
+
+        assert_eq!(lowest_bit(0), None);
+        assert_eq!(lowest_bit(2164), Some(2));
     }
 
     #[test]
@@ -460,4 +690,86 @@ mod tests {
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn test_shift_direction_bitboard_edges() {
+
+        // Knights on the corners, and the a and h files
+        let knight_moves = [
+            (63, 1 << 46 | 1 << 53),                        // a1
+            (56, 1 << 41 | 1 << 50),                        // h1
+            (7, 1 << 22 | 1 << 13),                         // a8
+            (0, 1 << 17 | 1 << 10),                         // h8
+            (39, 1 << 22 | 1 << 54 | 1 << 29 | 1 << 45),    // a4
+            (24, 1 << 9 | 1 << 41 | 1 << 18 | 1 << 34),     // h5
+        ];
+
+        for (piece_bit, expected) in knight_moves {
+            let piece_coordinates = get_piece_coordinates(piece_bit);
+            let result = shift_direction_bitboard(piece_bit, piece_coordinates, &direction_bitboards::KNIGHT_MOVES);
+            assert_eq!(result, expected, "knight on bit {}", piece_bit);
+        }
+
+        // Kings on the a and h files
+        let king_moves = [
+            (63, 1 << 62 | 1 << 55 | 1 << 54),                              // a1
+            (32, 1 << 24 | 1 << 25 | 1 << 33 | 1 << 40 | 1 << 41),          // h4
+        ];
+
+        for (piece_bit, expected) in king_moves {
+            let piece_coordinates = get_piece_coordinates(piece_bit);
+            let result = shift_direction_bitboard(piece_bit, piece_coordinates, &direction_bitboards::KING_MOVES);
+            assert_eq!(result, expected, "king on bit {}", piece_bit);
+        }
+    }
+
+    #[test]
+    fn test_distances() {
+
+        // Adjacent bits, including diagonally
+        assert_eq!(chebyshev_distance(59, 51), 1);
+        assert_eq!(chebyshev_distance(59, 50), 1);
+        assert_eq!(manhattan_distance(59, 50), 2);
+
+        // Opposite corners, and the same bit
+        assert_eq!(chebyshev_distance(0, 63), 7);
+        assert_eq!(manhattan_distance(0, 63), 14);
+        assert_eq!(chebyshev_distance(7, 56), 7);
+        assert_eq!(manhattan_distance(7, 56), 14);
+        assert_eq!((chebyshev_distance(27, 27), manhattan_distance(27, 27)), (0, 0));
+
+        // A knight move, and the h file to the a file on the same row doesn't wrap around
+        assert_eq!((chebyshev_distance(57, 42), manhattan_distance(57, 42)), (2, 3));
+        assert_eq!((chebyshev_distance(24, 31), manhattan_distance(24, 31)), (7, 7));
+
+        for (a, b) in [(0, 63), (12, 45), (24, 31)] {
+            assert_eq!(chebyshev_distance(a, b), chebyshev_distance(b, a));
+            assert_eq!(manhattan_distance(a, b), manhattan_distance(b, a));
+        }
+    }
+
+    #[test]
+    fn test_square_names() {
+
+        // Every bit has a different name, which converts back to the same bit
+        let names: std::collections::HashSet<_> = (0..64).map(square_name).collect();
+        assert_eq!(names.len(), 64);
+        for bit in 0..64 {
+            assert_eq!(square_bit(square_name(bit)), Some(bit));
+        }
+
+        // Starting king squares, and the corners
+        assert_eq!(square_name(59), "e1");
+        assert_eq!(square_name(3), "e8");
+        assert_eq!(square_name(0), "h8");
+        assert_eq!(square_name(63), "a1");
+
+        // Names match the file and rank masks
+        for bit in 0..64 {
+            let name = square_name(bit).as_bytes();
+            assert_ne!(FILE_MASKS[(b'h' - name[0]) as usize] & RANK_MASKS[(b'8' - name[1]) as usize] & 1 << bit, 0);
+        }
+
+        assert_eq!(square_bit("i1"), None);
+        assert_eq!(square_bit("e"), None);
+    }
 }
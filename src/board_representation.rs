@@ -13,6 +13,9 @@
 
 use crate::bitboard_manipulation;
 use crate::pieces;
+use crate::castling;
+use crate::generic_math;
+use crate::pesto;
 
 
 // Board is defined as the white team being at the bottom of the board, and the black team at the top (at starting position)
@@ -24,9 +27,9 @@ pub const STARTING_BLACK_BOARD: [u64; 3] = [65332, 110, 153];
 
 // Material value of a team at the start of the game
 // Should lign up with material values provided in pieces.rs
-pub const TEAM_MATERIAL_VALUE: i8 = 39;
+pub const TEAM_MATERIAL_VALUE: i16 = 39;
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Board {
 
     // Each team has an array containing 3 bitboards, the bit represents a pieces position and is common for all 3 bitboards
@@ -43,26 +46,32 @@ pub struct Board {
     pub en_passant_target_bit: Option<u8>,
     pub castling_availability: CastlingAvailability,
 
-    pub white_material: i8,
-    pub black_material: i8,
+    pub white_material: i16,
+    pub black_material: i16,
+
+    // Piece square table totals, updated incrementally like the material values
+    pub white_table_score: pesto::TableScore,
+    pub black_table_score: pesto::TableScore,
 
     pub halfmove_clock: i16, // Number of half moves since capture or pawn advance
     pub fullmove_number: i16, // Incremented after blacks turn
 }
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
 pub enum PieceColor {
     White,
     Black,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+// The column of the rook each side can castle with, or None if castling to that side isn't available
+// Storing the rook column rather than a flag allows Chess960 castling
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub struct CastlingAvailability {
-    pub w_ks: bool,
-    pub w_qs: bool,
+    pub w_ks: Option<u8>,
+    pub w_qs: Option<u8>,
     
-    pub b_ks: bool,
-    pub b_qs: bool,
+    pub b_ks: Option<u8>,
+    pub b_qs: Option<u8>,
 }
 
 // Boards from the perspective of the team whos turn it is to move
@@ -78,7 +87,7 @@ impl Board {
 
     // Create new board with the starting position
     pub fn new() -> Self {
-        Board {
+        let mut board = Board {
             white_board: STARTING_WHITE_BOARD,
             black_board: STARTING_BLACK_BOARD,
             white_king_bit: 59,
@@ -88,9 +97,15 @@ impl Board {
             castling_availability: CastlingAvailability::new(true),
             white_material: TEAM_MATERIAL_VALUE,
             black_material: TEAM_MATERIAL_VALUE,
+            white_table_score: pesto::TableScore::default(),
+            black_table_score: pesto::TableScore::default(),
             halfmove_clock: 0,
             fullmove_number: 1,
-        }
+        };
+
+        board.white_table_score = pesto::recount_table_score(&board, PieceColor::White);
+        board.black_table_score = pesto::recount_table_score(&board, PieceColor::Black);
+        board
     }
 
     // Create new empty board
@@ -105,23 +120,259 @@ impl Board {
             castling_availability: CastlingAvailability::new(false),
             white_material: 0,
             black_material: 0,
+            white_table_score: pesto::TableScore::default(),
+            black_table_score: pesto::TableScore::default(),
             halfmove_clock: 0,
             fullmove_number: 1,
         }
     }
+
+    // Create a board from a list of (bit, color, piece_id), with the given team to move
+    // Castling and en-passant aren't available
+    // If two pieces are given for the same bit, the last one is used
+    pub fn from_squares(squares: &[(u8, PieceColor, usize)], side: PieceColor) -> Board {
+        let mut board = Board::empty();
+        board.piece_to_move = side;
+
+        for &(bit, color, piece_id) in squares {
+            board.set_piece(bit, color, piece_id);
+        }
+
+        board
+    }
+
+    // Returns the piece on every bit as (color, piece_id), or None for empty bits
+    // The array is indexed by bit, so index 0 is h8, 7 is a8, 56 is h1, and 63 is a1 (see the diagram at the top of the file)
+    pub fn to_array(&self) -> [Option<(PieceColor, usize)>; 64] {
+        let mut squares = [None; 64];
+
+        for (bit, square) in squares.iter_mut().enumerate() {
+            let white_piece_id = read_piece_id(&self.white_board, bit as u8);
+            let black_piece_id = read_piece_id(&self.black_board, bit as u8);
+
+            if white_piece_id != 0 {
+                *square = Some((PieceColor::White, white_piece_id));
+            } else if black_piece_id != 0 {
+                *square = Some((PieceColor::Black, black_piece_id));
+            }
+        }
+
+        squares
+    }
+
+    // Create a board from an array in the to_array format, with the given team to move
+    // Castling and en-passant aren't available, the same as from_squares
+    pub fn from_array(squares: &[Option<(PieceColor, usize)>; 64], side: PieceColor) -> Board {
+        let pieces: Vec<(u8, PieceColor, usize)> = squares.iter()
+            .enumerate()
+            .filter_map(|(bit, square)| square.map(|(color, piece_id)| (bit as u8, color, piece_id)))
+            .collect();
+
+        Board::from_squares(&pieces, side)
+    }
+
+    // Puts a piece on a bit, replacing any piece already there
+    // Material and king bits are kept up to date
+    pub fn set_piece(&mut self, bit: u8, color: PieceColor, piece_id: usize) {
+        self.clear_square(bit);
+
+        let (half_board, material, table_score, king_bit) = match color {
+            PieceColor::Black => (&mut self.black_board, &mut self.black_material, &mut self.black_table_score, &mut self.black_king_bit),
+            PieceColor::White => (&mut self.white_board, &mut self.white_material, &mut self.white_table_score, &mut self.white_king_bit),
+        };
+
+        insert_piece(bit, piece_id, half_board);
+        *material += piece_value(piece_id, color);
+        *table_score += pesto::piece_table_score(piece_id, bit, color);
+
+        if piece_id == pieces::KING_ID {
+            *king_bit = bit;
+        }
+    }
+
+    // Returns the material difference between the teams, positive when white is ahead
+    pub fn material_balance(&self) -> i16 {
+        self.white_material - self.black_material
+    }
+
+    // Returns a bitboard of every occupied bit
+    pub fn occupied(&self) -> u64 {
+        self.white_occupancy() | self.black_occupancy()
+    }
+
+    // Returns a bitboard of the bits occupied by white pieces
+    pub fn white_occupancy(&self) -> u64 {
+        team_occupancy(&self.white_board)
+    }
+
+    // Returns a bitboard of the bits occupied by black pieces
+    pub fn black_occupancy(&self) -> u64 {
+        team_occupancy(&self.black_board)
+    }
+
+    // Returns 1.0 for the midgame and 0.0 for the endgame, based on the total material of both teams
+    pub fn game_phase(&self) -> f32 {
+        let total_material = self.white_material as f32 + self.black_material as f32;
+        generic_math::f32_scale_clamped(total_material, 0.0, 2.0 * TEAM_MATERIAL_VALUE as f32)
+    }
+
+    // Returns the number of half moves played since the start of the game, from the fullmove number
+    // White to move on move N is ply 2 * (N - 1), and black to move is one more
+    pub fn ply(&self) -> u32 {
+        let white_ply = (self.fullmove_number.max(1) as u32 - 1) * 2;
+
+        match self.piece_to_move {
+            PieceColor::Black => white_ply + 1,
+            PieceColor::White => white_ply,
+        }
+    }
+
+    // Returns the board with the halfmove clock and fullmove number set
+    pub fn with_clocks(mut self, halfmove_clock: i16, fullmove_number: i16) -> Board {
+        self.halfmove_clock = halfmove_clock;
+        self.fullmove_number = fullmove_number;
+        self
+    }
+
+    // Returns the board flipped vertically with the colours of every piece swapped
+    // The mirrored position is the same position from the other teams perspective
+    pub fn mirror(&self) -> Board {
+
+        // Flipping vertically swaps row r with row 7 - r, which is the same as reversing the bytes
+        let flip_bitboards = |half_board: &[u64; 3]| half_board.map(u64::swap_bytes);
+        let flip_bit = |bit: u8| bit ^ 56;
+
+        let piece_to_move = self.piece_to_move.opposite();
+
+        Board {
+            white_board: flip_bitboards(&self.black_board),
+            black_board: flip_bitboards(&self.white_board),
+            white_king_bit: flip_bit(self.black_king_bit),
+            black_king_bit: flip_bit(self.white_king_bit),
+            piece_to_move,
+            en_passant_target_bit: self.en_passant_target_bit.map(flip_bit),
+            castling_availability: CastlingAvailability {
+                w_ks: self.castling_availability.b_ks,
+                w_qs: self.castling_availability.b_qs,
+                b_ks: self.castling_availability.w_ks,
+                b_qs: self.castling_availability.w_qs,
+            },
+            white_material: self.black_material,
+            black_material: self.white_material,
+            white_table_score: self.black_table_score,
+            black_table_score: self.white_table_score,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+        }
+    }
+
+    // Removes any piece on a bit, subtracting it's material value and table score
+    // The king bit is left unchanged if a king is removed
+    pub fn clear_square(&mut self, bit: u8) {
+        let black_piece_id = read_piece_id(&self.black_board, bit);
+        let white_piece_id = read_piece_id(&self.white_board, bit);
+
+        self.black_material -= pieces::BLACK_PIECE_INFORMATION[black_piece_id].piece_value;
+        self.white_material -= pieces::WHITE_PIECE_INFORMATION[white_piece_id].piece_value;
+
+        self.black_table_score -= pesto::piece_table_score(black_piece_id, bit, PieceColor::Black);
+        self.white_table_score -= pesto::piece_table_score(white_piece_id, bit, PieceColor::White);
+
+        remove_piece(bit, &mut self.black_board);
+        remove_piece(bit, &mut self.white_board);
+    }
+}
+
+// Only the fields which make up a position for repetitions are hashed, the move clocks are left out
+// This means boards which hash equally can still be unequal with PartialEq, which compares the clocks as well
+// The material, table scores, and king bits all come from the piece positions, so they don't need hashing either
+impl std::hash::Hash for Board {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.white_board.hash(state);
+        self.black_board.hash(state);
+        self.piece_to_move.hash(state);
+        self.en_passant_target_bit.hash(state);
+        self.castling_availability.hash(state);
+    }
+}
+
+// Returns true if two boards are the same position for repetitions, comparing the same fields that are hashed
+// Unlike PartialEq the move clocks aren't compared
+pub fn positions_equal(a: &Board, b: &Board) -> bool {
+    a.white_board == b.white_board
+        && a.black_board == b.black_board
+        && a.piece_to_move == b.piece_to_move
+        && a.en_passant_target_bit == b.en_passant_target_bit
+        && a.castling_availability == b.castling_availability
+}
+
+impl PieceColor {
+
+    // Returns the other team's color
+    pub fn opposite(self) -> PieceColor {
+        match self {
+            PieceColor::Black => PieceColor::White,
+            PieceColor::White => PieceColor::Black,
+        }
+    }
+
+    // Both colors, white first
+    pub fn all() -> [PieceColor; 2] {
+        [PieceColor::White, PieceColor::Black]
+    }
 }
 
 impl CastlingAvailability {
+    // Castling is either available to every side with the standard rook columns, or none
     fn new(common_state: bool) -> Self {
+        let (kingside, queenside) = if common_state {
+            (Some(castling::STANDARD_KINGSIDE_ROOK_COLUMN), Some(castling::STANDARD_QUEENSIDE_ROOK_COLUMN))
+        } else {
+            (None, None)
+        };
+
+        CastlingAvailability {
+            w_ks: kingside,
+            w_qs: queenside,
+            b_ks: kingside,
+            b_qs: queenside
+        }
+    }
+
+    // Returns which sides castling is available to as bits, w_ks is bit 0, w_qs bit 1, b_ks bit 2, and b_qs bit 3
+    // Only availability is stored, not the rook columns
+    pub fn to_bits(&self) -> u8 {
+        [self.w_ks, self.w_qs, self.b_ks, self.b_qs].iter()
+            .enumerate()
+            .fold(0, |bits, (i, rook_column)| bits | (rook_column.is_some() as u8) << i)
+    }
+
+    // Creates castling availability from bits in the to_bits format
+    // Castling uses the standard rook columns
+    pub fn from_bits(bits: u8) -> Self {
+        let standard = CastlingAvailability::new(true);
+        let available = |i: u8, rook_column: Option<u8>| if bitboard_manipulation::bit_on(bits, i) {
+            rook_column
+        } else {
+            None
+        };
+
         CastlingAvailability {
-            w_ks: common_state,
-            w_qs: common_state,
-            b_ks: common_state,
-            b_qs: common_state
+            w_ks: available(0, standard.w_ks),
+            w_qs: available(1, standard.w_qs),
+            b_ks: available(2, standard.b_ks),
+            b_qs: available(3, standard.b_qs),
         }
     }
 }
 
+// No castling is available by default
+impl Default for CastlingAvailability {
+    fn default() -> Self {
+        CastlingAvailability::new(false)
+    }
+}
+
 impl<'a> PerspectiveBoards<'a> {
 
     // Generate perspective boards for piece to move
@@ -134,7 +385,7 @@ impl<'a> PerspectiveBoards<'a> {
                     enemy_board: &board.white_board,
                     friendly_starting_board: &STARTING_BLACK_BOARD,
                     friendly_piece_information: pieces::BLACK_PIECE_INFORMATION,
-                    enemy_team_color: PieceColor::White,
+                    enemy_team_color: from_persecpective.opposite(),
                 }
             }
 
@@ -144,7 +395,7 @@ impl<'a> PerspectiveBoards<'a> {
                     enemy_board: &board.black_board,
                     friendly_starting_board: &STARTING_WHITE_BOARD,
                     friendly_piece_information: pieces::WHITE_PIECE_INFORMATION,
-                    enemy_team_color: PieceColor::Black,
+                    enemy_team_color: from_persecpective.opposite(),
                 }
             }
         } 
@@ -153,13 +404,15 @@ impl<'a> PerspectiveBoards<'a> {
     // Generates friendly and enemy bitboards
     // These bitboards contain no information about the type of piece, just the positions
     pub fn gen_bitboards(&self) -> (u64, u64) {
-        let friendly_bitboard = self.friendly_board[0] | self.friendly_board[1] | self.friendly_board [2];
-        let enemy_bitboard = self.enemy_board[0] | self.enemy_board[1] | self.enemy_board [2];
-
-        (friendly_bitboard, enemy_bitboard)
+        (team_occupancy(self.friendly_board), team_occupancy(self.enemy_board))
     }
 }
 
+// Returns a bitboard of the bits occupied by any piece on a team board
+pub fn team_occupancy(team_board: &[u64; 3]) -> u64 {
+    team_board[0] | team_board[1] | team_board[2]
+}
+
 // Reads a piece id from a team board given a bit
 // See board_representation.rs for information about how the team boards work
 pub fn read_piece_id(team_board: &[u64; 3], piece_bit: u8) -> usize {
@@ -174,6 +427,45 @@ pub fn read_piece_id(team_board: &[u64; 3], piece_bit: u8) -> usize {
     output
 }
 
+// Returns the material value of a piece, from the piece information of the team it belongs to
+pub fn piece_value(piece_id: usize, color: PieceColor) -> i16 {
+    match color {
+        PieceColor::Black => pieces::BLACK_PIECE_INFORMATION[piece_id].piece_value,
+        PieceColor::White => pieces::WHITE_PIECE_INFORMATION[piece_id].piece_value,
+    }
+}
+
+// Sums the material value of every piece on a team board
+// Used to check the material values which are updated incrementally
+pub fn recount_material(board: &Board, color: PieceColor) -> i16 {
+    let (team_board, piece_information) = match color {
+        PieceColor::Black => (&board.black_board, &pieces::BLACK_PIECE_INFORMATION),
+        PieceColor::White => (&board.white_board, &pieces::WHITE_PIECE_INFORMATION),
+    };
+
+    let mut material = 0;
+    for bit in 0..64 {
+        material += piece_information[read_piece_id(team_board, bit)].piece_value;
+    }
+
+    material
+}
+
+// Returns the color and material value of the piece on a bit, or (None, 0) if the bit is empty
+pub fn piece_value_at(board: &Board, bit: u8) -> (Option<PieceColor>, i16) {
+    let white_piece_id = read_piece_id(&board.white_board, bit);
+    if white_piece_id != 0 {
+        return (Some(PieceColor::White), pieces::WHITE_PIECE_INFORMATION[white_piece_id].piece_value);
+    }
+
+    let black_piece_id = read_piece_id(&board.black_board, bit);
+    if black_piece_id != 0 {
+        return (Some(PieceColor::Black), pieces::BLACK_PIECE_INFORMATION[black_piece_id].piece_value);
+    }
+
+    (None, 0)
+}
+
 // Insert piece in white or black team board
 pub fn insert_piece(piece_bit: u8, piece_id: usize, half_board: &mut [u64; 3]) {
     for i in 0..3 {
@@ -201,6 +493,93 @@ mod tests {
         assert_eq!(read_piece_id(&[0, 1 << 7, 1 << 7], 7), 6)
     }
 
+    #[test]
+    fn test_board_hash() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        let hash = |board: &Board| {
+            let mut hasher = DefaultHasher::new();
+            board.hash(&mut hasher);
+            hasher.finish()
+        };
+
+        // Boards differing only in the halfmove clock are the same position, but aren't equal
+        let board = Board::new();
+        let mut clock_board = Board::new();
+        clock_board.halfmove_clock = 10;
+
+        assert_eq!(hash(&board), hash(&clock_board));
+        assert_ne!(board, clock_board);
+
+        // Different positions hash differently
+        assert_ne!(hash(&board), hash(&board.apply_uci_move("e2e4").unwrap()));
+
+        let boards: std::collections::HashSet<Board> = [board, clock_board].into_iter().collect();
+        assert_eq!(boards.len(), 2);
+    }
+
+    #[test]
+    fn test_positions_equal() {
+        let board = Board::new();
+        let clock_board = Board::new().with_clocks(7, 12);
+        assert!(positions_equal(&board, &clock_board));
+        assert_ne!(board, clock_board);
+
+        // Knights out and back is the same position with different clocks
+        let knight_shuffle = Board::from_moves(&["g1f3", "g8f6", "f3g1", "f6g8"]).unwrap();
+        assert!(positions_equal(&board, &knight_shuffle));
+        assert_ne!(board, knight_shuffle);
+
+        // The team to move, castling rights, and en-passant target are all part of the position
+        assert!(!positions_equal(&board, &Board { piece_to_move: PieceColor::Black, ..Board::new() }));
+        assert!(!positions_equal(&board, &fen::read_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w Kkq - 0 1")));
+        assert!(!positions_equal(&board, &Board { en_passant_target_bit: Some(43), ..Board::new() }));
+    }
+
+    #[test]
+    fn test_ply() {
+        let board = Board::new();
+        assert_eq!(board.ply(), 0);
+
+        let board = board.apply_uci_move("e2e4").unwrap();
+        assert_eq!(board.ply(), 1);
+
+        let board = board.apply_uci_move("e7e5").unwrap().apply_uci_move("g1f3").unwrap();
+        assert_eq!(board.ply(), 3);
+        assert_eq!(board, fen::read_fen("rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"));
+
+        assert_eq!(fen::read_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 40").ply(), 78);
+
+        let board = Board::new().with_clocks(12, 30);
+        assert_eq!((board.halfmove_clock, board.fullmove_number), (12, 30));
+        assert_eq!(board.ply(), 58);
+        assert_eq!(board.with_clocks(0, 0).ply(), 0);
+    }
+
+    #[test]
+    fn test_occupancy() {
+        let board = Board::new();
+        assert_eq!(board.occupied().count_ones(), 32);
+        assert_eq!(board.occupied(), board.white_occupancy() | board.black_occupancy());
+        assert_eq!(board.white_occupancy() & board.black_occupancy(), 0);
+
+        // White pieces are on rows 6 and 7, black pieces on rows 0 and 1
+        assert_eq!(board.white_occupancy(), 0xFFFF << 48);
+        assert_eq!(board.black_occupancy(), 0xFFFF);
+
+        let perspective_boards = PerspectiveBoards::gen(&board, PieceColor::Black);
+        assert_eq!(perspective_boards.gen_bitboards(), (board.black_occupancy(), board.white_occupancy()));
+    }
+
+    #[test]
+    fn test_piece_color_opposite() {
+        for color in PieceColor::all() {
+            assert_ne!(color.opposite(), color);
+            assert_eq!(color.opposite().opposite(), color);
+        }
+    }
+
     #[test]
     fn test_remove_piece() {
         let mut half_board = [3, 0, 3];
@@ -209,6 +588,125 @@ mod tests {
         assert_eq!(half_board, [1, 0, 1]);
     }
 
+    #[test]
+    fn test_recount_material() {
+        let board = Board::new();
+        assert_eq!(recount_material(&board, PieceColor::White), TEAM_MATERIAL_VALUE);
+
+        // Capture then promote
+        let board = fen::read_fen("r3k3/1P6/8/8/8/8/8/4K3 w - - 0 1").apply_uci_move("b7a8q").unwrap();
+        assert_eq!(recount_material(&board, PieceColor::White), board.white_material);
+        assert_eq!(recount_material(&board, PieceColor::Black), board.black_material);
+        assert_eq!(board.white_material, 9);
+        assert_eq!(board.black_material, 0);
+
+        // Three promoted queens, and an extreme position with more material than fits in an i8
+        let board = fen::read_fen("rnbqkbnr/8/8/8/8/8/8/QQQQK2R w K - 0 1");
+        assert_eq!(board.white_material, 3 * 9 + 9 + 5);
+        assert_eq!(board.material_balance(), 3 * 9 + 9 + 5 - 31);
+
+        let board = fen::read_fen("QQQQQQQQ/QQQQQQQQ/8/8/8/8/8/k1K5 w - - 0 1");
+        assert_eq!(board.white_material, 16 * 9);
+        assert_eq!(board.material_balance(), 144);
+        assert_eq!(recount_material(&board, PieceColor::White), 144);
+    }
+
+    #[test]
+    fn test_set_piece() {
+        let mut board = Board::new();
+
+        // Setting then clearing a queen should leave the board unchanged
+        board.set_piece(35, PieceColor::White, pieces::QUEEN_ID);
+        assert_eq!(board.white_material, TEAM_MATERIAL_VALUE + 9);
+
+        board.clear_square(35);
+        assert_eq!(board, Board::new());
+
+        // Replacing a piece removes it's material
+        board.set_piece(8, PieceColor::White, pieces::KING_ID);
+        assert_eq!(board.black_material, TEAM_MATERIAL_VALUE - 1);
+        assert_eq!(board.white_king_bit, 8);
+        assert_eq!(read_piece_id(&board.black_board, 8), 0);
+    }
+
+    #[test]
+    fn test_material_balance_and_game_phase() {
+        let board = Board::new();
+        assert_eq!(board.material_balance(), 0);
+        assert!((board.game_phase() - 1.0).abs() < 0.001);
+
+        let board = fen::read_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+        assert!(board.game_phase().abs() < 0.001);
+
+        // The phase doesn't depend on the team to move
+        let white_board = fen::read_fen("4k3/8/8/8/8/8/8/QQ2K3 w - - 0 1");
+        let black_board = fen::read_fen("4k3/8/8/8/8/8/8/QQ2K3 b - - 0 1");
+        assert_eq!(white_board.material_balance(), 18);
+        assert_eq!(white_board.game_phase(), black_board.game_phase());
+    }
+
+    #[test]
+    fn test_mirror() {
+        let board = fen::read_fen("r3k2r/pp3ppp/8/3pP3/8/8/PPP2PPP/R3K2R w Kq 27 0 1");
+        let expected = fen::read_fen("r3k2r/ppp2ppp/8/8/3Pp3/8/PP3PPP/R3K2R b Qk 35 0 1");
+
+        assert_eq!(board.mirror(), expected);
+        assert_eq!(board.mirror().mirror(), board);
+        assert_eq!(Board::new().mirror(), Board::new().mirror().mirror().mirror());
+    }
+
+    #[test]
+    fn test_piece_value_at() {
+        let board = Board::new();
+
+        assert_eq!(piece_value_at(&board, 60), (Some(PieceColor::White), 9));
+        assert_eq!(piece_value_at(&board, 1), (Some(PieceColor::Black), 3));
+        assert_eq!(piece_value_at(&board, 35), (None, 0));
+    }
+
+    #[test]
+    fn test_from_squares() {
+        let board = Board::from_squares(&[
+            (59, PieceColor::White, pieces::KING_ID),
+            (3, PieceColor::Black, pieces::KING_ID),
+            (35, PieceColor::White, pieces::PAWN_ID),
+        ], PieceColor::Black);
+
+        assert_eq!(board, fen::read_fen("4k3/8/8/8/4P3/8/8/4K3 b - - 0 1"));
+
+        // The last piece on a bit is kept
+        let board = Board::from_squares(&[
+            (35, PieceColor::White, pieces::PAWN_ID),
+            (35, PieceColor::Black, pieces::QUEEN_ID),
+        ], PieceColor::White);
+
+        assert_eq!(board, fen::read_fen("8/8/8/8/4q3/8/8/8 w - - 0 1"));
+    }
+
+    #[test]
+    fn test_castling_bits() {
+        for bits in 0..16 {
+            assert_eq!(CastlingAvailability::from_bits(bits).to_bits(), bits);
+        }
+
+        assert_eq!(CastlingAvailability::from_bits(0b1111), Board::new().castling_availability);
+        assert_eq!(CastlingAvailability::default().to_bits(), 0);
+    }
+
+    #[test]
+    fn test_to_array() {
+        let board = fen::read_fen("r3k2r/pp3ppp/8/3pP3/8/8/PPP2PPP/R3K2R b - - 0 1");
+        let squares = board.to_array();
+
+        // Index 7 is a8, and index 56 is h1
+        assert_eq!(squares[7], Some((PieceColor::Black, pieces::ROOK_ID)));
+        assert_eq!(squares[56], Some((PieceColor::White, pieces::ROOK_ID)));
+        assert_eq!(squares[59], Some((PieceColor::White, pieces::KING_ID)));
+        assert_eq!(squares[35], None);
+
+        assert_eq!(Board::from_array(&squares, PieceColor::Black), board);
+    }
+
     #[test]
     fn test_insert_piece() {
         let mut half_board = [0, 3, 0];
@@ -222,9 +720,60 @@ mod tests {
 // Module for converting to and from fen notation
 pub mod fen {
     use crate::pieces::{BLACK_PIECE_TYPES, WHITE_PIECE_TYPES, KING_ID};
-    use core::str::Chars;
+    use crate::castling;
     use super::*;
 
+    #[derive(Debug, PartialEq)]
+    pub enum FenError {
+        InvalidCastling,        // The castling field contains characters other than KQkq or file letters
+        InconsistentCastling,   // A castling flag is set but the king or rook isn't on it's starting bit
+        MissingKing(PieceColor), // A team has no king
+    }
+
+    // Create a board from a fen string, validating the fields read_fen is lenient with
+    pub fn try_read_fen(fen_string: &str) -> Result<Board, FenError> {
+        let board = read_fen(fen_string);
+
+        let castling_field = fen_string.split_whitespace().nth(2).unwrap_or("-");
+        if castling_field != "-" && !castling_field.chars().all(|c| "KQkq".contains(c) || ('A'..='H').contains(&c) || ('a'..='h').contains(&c)) {
+            return Err(FenError::InvalidCastling);
+        }
+
+        let castling_sides = [
+            (board.castling_availability.w_ks, PieceColor::White, true),
+            (board.castling_availability.w_qs, PieceColor::White, false),
+            (board.castling_availability.b_ks, PieceColor::Black, true),
+            (board.castling_availability.b_qs, PieceColor::Black, false),
+        ];
+
+        // The king and rook have to be on the bits castling starts from for castling to be available
+        for (rook_column, king_color, kingside) in castling_sides {
+            if rook_column.is_none() {
+                continue;
+            }
+
+            let team_board = match king_color {
+                PieceColor::Black => &board.black_board,
+                PieceColor::White => &board.white_board,
+            };
+
+            match castling::get_castling_bits(&board, king_color, kingside) {
+                Some(castling_bits) if read_piece_id(team_board, castling_bits.king_initial_bit) == KING_ID
+                    && read_piece_id(team_board, castling_bits.rook_initial_bit) == pieces::ROOK_ID => (),
+                _ => return Err(FenError::InconsistentCastling),
+            }
+        }
+
+        // read_fen leaves the king bit at 0 if a team has no king
+        for color in PieceColor::all() {
+            if crate::check_validation::get_king_bit(&board, color).is_none() {
+                return Err(FenError::MissingKing(color));
+            }
+        }
+
+        Ok(board)
+    }
+
     // Create a board from a fen string
     // This implementation of fen is alsmost completely standard
     // Except the en-passant target square field is replaced by an en-passant target bit
@@ -235,100 +784,169 @@ pub mod fen {
         let mut row = 0;
         let mut board = Board::empty();
 
-        let mut space_counter = 0;
+        // Fen fields are seperated by spaces
+        // Missing fields are left at the Board::empty() defaults
+        let mut fields = fen_string.split_whitespace();
 
-        let mut last_character_space = false;
-        for (i, c) in fen_string.chars().enumerate() {
+        // The first field is the board layout
+        for c in fields.next().unwrap_or("").chars() {
 
             // The '/' seperator is useless to this implementation
             if c == '/' {
                 continue;
             }
 
-            if c == ' ' {
-                space_counter += 1;
-                last_character_space = true;
-                continue;
+            set_king_bits(bit, c, &mut board);
+
+            // If the the character is a number, skip that many squares in the bitbaord
+            let skip_numer: u8 = char_to_num(c).unwrap_or(1);
+        
+            // Insert black/white piece into their respective board arrays
+            // and add pieces material value to appropriate variables
+            if let Some(piece_id) = find_key_in_array(c, BLACK_PIECE_TYPES) {
+                insert_piece(bit, piece_id, &mut board.black_board);
+                board.black_material += pieces::BLACK_PIECE_INFORMATION[piece_id].piece_value;
+                board.black_table_score += pesto::piece_table_score(piece_id, bit, PieceColor::Black);
+            } else if let Some(piece_id) = find_key_in_array(c, WHITE_PIECE_TYPES) {
+                insert_piece(bit, piece_id, &mut board.white_board);
+                board.white_material += pieces::WHITE_PIECE_INFORMATION[piece_id].piece_value;
+                board.white_table_score += pesto::piece_table_score(piece_id, bit, PieceColor::White);
             }
 
-            // If a space hasn't appeared in the FEN string then we're looking at board layout information
-            if space_counter == 0 {
-
-                set_king_bits(bit, c, &mut board);
-
-                // If the the character is a number, skip that many squares in the bitbaord
-                let skip_numer: u8 = char_to_num(c).unwrap_or(1);
-            
-                // Insert black/white piece into their respective board arrays
-                // and add pieces material value to appropriate variables
-                if let Some(piece_id) = find_key_in_array(c, BLACK_PIECE_TYPES) {
-                    insert_piece(bit, piece_id, &mut board.black_board);
-                    board.black_material += pieces::BLACK_PIECE_INFORMATION[piece_id].piece_value;
-                } else if let Some(piece_id) = find_key_in_array(c, WHITE_PIECE_TYPES) {
-                    insert_piece(bit, piece_id, &mut board.white_board);
-                    board.white_material += pieces::WHITE_PIECE_INFORMATION[piece_id].piece_value;
+            // For traversing bitboard
+            for _ in 0..skip_numer {
+                if bit % 8 == 0 {
+                    row += 1;
+                    bit = row * 8 + 7;
+                } else {
+                    bit -= 1
                 }
+            }
+        }
 
-                // For traversing bitboard
-                for _ in 0..skip_numer {
-                    if bit % 8 == 0 {
-                        row += 1;
-                        bit = row * 8 + 7;
-                    } else {
-                        bit -= 1
-                    }
-                }
-            } 
-            
-            // If there is one space then we're looking at the team to move
-            else if space_counter == 1 {
-
-                // Set piece to move
-                if c == 'w' {
-                   board.piece_to_move = PieceColor::White
+        // The second field is the team to move
+        if let Some(piece_to_move) = fields.next() {
+            if piece_to_move == "w" {
+                board.piece_to_move = PieceColor::White
+            } else {
+                board.piece_to_move = PieceColor::Black
+            }
+        }
+
+        // The third field is the castling availability
+        // Both KQkq and file letters (Shredder / X-FEN notation for Chess960) are supported
+        for c in fields.next().unwrap_or("").chars() {
+            set_castling_availability(c, &mut board);
+        }
+
+        // The fourth field is the en passant target bit
+        // Not really FEN notation because something like E5 would normally be here
+        // Instead we use a bit e.g. 27 = E5
+        // Bit 0 is a valid bit, so no en-passant is written as - rather than 0
+        board.en_passant_target_bit = match fields.next() {
+            None | Some("-") => None,
+            Some(en_passant_target_bit) => en_passant_target_bit.parse().ok(),
+        };
+
+        // The fifth and sixth fields are the half and fullmove clocks
+        if let Some(Ok(halfmove_clock)) = fields.next().map(str::parse) {
+            board.halfmove_clock = halfmove_clock;
+        }
+
+        if let Some(Ok(fullmove_number)) = fields.next().map(str::parse) {
+            board.fullmove_number = fullmove_number;
+        }
+
+        board
+    }
+
+    // Create a fen string from a board, in the same format read_fen uses
+    // Castling with the standard rook columns is written as KQkq, otherwise the rooks file letter is used
+    pub fn write_fen(board: &Board) -> String {
+        let mut fen = String::new();
+
+        // Board layout, rows are written from the top of the board (row 0)
+        for row in 0..8 {
+            let mut empty_squares = 0;
+
+            for bit in (row * 8..row * 8 + 8).rev() {
+                let white_piece_id = read_piece_id(&board.white_board, bit);
+                let black_piece_id = read_piece_id(&board.black_board, bit);
+
+                let piece_char = if white_piece_id != 0 {
+                    WHITE_PIECE_TYPES[white_piece_id]
+                } else if black_piece_id != 0 {
+                    BLACK_PIECE_TYPES[black_piece_id]
                 } else {
-                    board.piece_to_move = PieceColor::Black
+                    empty_squares += 1;
+                    continue;
+                };
+
+                if empty_squares != 0 {
+                    fen.push_str(&empty_squares.to_string());
+                    empty_squares = 0;
                 }
+                fen.push(piece_char);
             }
-            
-            // If there are two spaces then we're looking at the castling availability
-            else if space_counter == 2 {
-                match c {
-                    'K' => board.castling_availability.w_ks = true,
-                    'Q' => board.castling_availability.w_qs = true,
-                    'k' => board.castling_availability.b_ks = true,
-                    'q' => board.castling_availability.b_qs = true,
-                    _ => ()
-                }
+
+            if empty_squares != 0 {
+                fen.push_str(&empty_squares.to_string());
             }
 
-            // If there are three spaces then we're looking at the en passant target bit
-            // Not really FEN notation because something like E5 would normally be here
-            // Instead we use a bit e.g. 27 = E5
-            else if space_counter == 3 && last_character_space {
-                if char_to_num(c) != None {
-                    board.en_passant_target_bit = Some(collect_nums(fen_string.chars(), i) as u8);
-                }
-                last_character_space = false;
-            } 
-            
-            // If there are four or five spaces then we're looking at the half and fullmove clocks
-            else if last_character_space {
-
-                // Half and fullmove clocks can have multiple digit numbers, so use collect_nums()
-                let num = collect_nums(fen_string.chars(), i) as i16;
-
-                match space_counter {
-                    4 => board.halfmove_clock = num, 
-                    5 => board.fullmove_number = num,
-                    _ => ()
-                }
-                last_character_space = false
+            if row != 7 {
+                fen.push('/');
             }
+        }
 
+        // Team to move
+        let piece_to_move = match board.piece_to_move {
+            PieceColor::Black => 'b',
+            PieceColor::White => 'w',
+        };
+
+        // Castling availability
+        let castling_sides = [
+            (board.castling_availability.w_ks, 'K', castling::STANDARD_KINGSIDE_ROOK_COLUMN),
+            (board.castling_availability.w_qs, 'Q', castling::STANDARD_QUEENSIDE_ROOK_COLUMN),
+            (board.castling_availability.b_ks, 'k', castling::STANDARD_KINGSIDE_ROOK_COLUMN),
+            (board.castling_availability.b_qs, 'q', castling::STANDARD_QUEENSIDE_ROOK_COLUMN),
+        ];
+
+        let mut castling_field = String::new();
+        for (rook_column, standard_char, standard_rook_column) in castling_sides {
+            match rook_column {
+                Some(column) if column == standard_rook_column => castling_field.push(standard_char),
+                Some(column) => {
+                    let file = (b'a' + 7 - column) as char;
+                    castling_field.push(if standard_char.is_ascii_uppercase() { file.to_ascii_uppercase() } else { file });
+                },
+                None => (),
+            }
         }
 
-        board
+        if castling_field.is_empty() {
+            castling_field.push('-');
+        }
+
+        // En-passant target bit, then the move clocks
+        let en_passant_field = board.en_passant_target_bit.map_or("-".to_string(), |bit| bit.to_string());
+
+        format!("{} {} {} {} {} {}", fen, piece_to_move, castling_field, en_passant_field, board.halfmove_clock, board.fullmove_number)
+    }
+
+    // Boards are displayed as their fen string
+    impl std::fmt::Display for Board {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "{}", write_fen(self))
+        }
+    }
+
+    impl std::str::FromStr for Board {
+        type Err = FenError;
+
+        fn from_str(fen_string: &str) -> Result<Board, FenError> {
+            try_read_fen(fen_string)
+        }
     }
 
     // Sets king bits in the board from the current fen character
@@ -342,6 +960,49 @@ pub mod fen {
         }
     }
 
+    // Sets castling availability from a character in the castling field
+    // KQkq use the outermost rook on that side of the king, file letters give the rooks file
+    fn set_castling_availability(castling_char: char, board: &mut Board) {
+        let king_color = if castling_char.is_ascii_uppercase() {
+            PieceColor::White
+        } else {
+            PieceColor::Black
+        };
+
+        let (team_board, king_bit) = match king_color {
+            PieceColor::Black => (&board.black_board, board.black_king_bit),
+            PieceColor::White => (&board.white_board, board.white_king_bit),
+        };
+
+        // Assume the king is on it's standard column if it isn't on the back row
+        let row = castling::back_row(king_color);
+        let king_column = if king_bit / 8 == row {
+            king_bit % 8
+        } else {
+            castling::STANDARD_KING_COLUMN
+        };
+
+        let is_rook = |column: &u8| read_piece_id(team_board, row * 8 + column) == pieces::ROOK_ID;
+
+        let (kingside, rook_column) = match castling_char.to_ascii_lowercase() {
+            'k' => (true, (0..king_column).find(is_rook).unwrap_or(castling::STANDARD_KINGSIDE_ROOK_COLUMN)),
+            'q' => (false, (king_column + 1..8).rev().find(is_rook).unwrap_or(castling::STANDARD_QUEENSIDE_ROOK_COLUMN)),
+            file @ 'a'..='h' => {
+                let column = 7 - (file as u8 - b'a');
+                (column < king_column, column)
+            },
+            _ => return,
+        };
+
+        let castling_availability = &mut board.castling_availability;
+        match (king_color, kingside) {
+            (PieceColor::Black, true) => castling_availability.b_ks = Some(rook_column),
+            (PieceColor::Black, false) => castling_availability.b_qs = Some(rook_column),
+            (PieceColor::White, true) => castling_availability.w_ks = Some(rook_column),
+            (PieceColor::White, false) => castling_availability.w_qs = Some(rook_column),
+        }
+    }
+
     // Converts character to number
     fn char_to_num(c: char) -> Option<u8> {
         let c_num = c as u8;
@@ -352,23 +1013,6 @@ pub mod fen {
         None
     }
 
-    // Collect nums in character iterator to a number
-    // start_index is the index to start in the iterator
-    // E.g. collect_nums(Chars(['1', '2', '3', '4']), 0) -> 1234
-    // E.g. collect_nums(Chars(['1', '2', '3', '4', ' ', '1']), 0) -> 1234
-    fn collect_nums(characters: Chars<'_>, start_index: usize) -> u32 {
-        let mut output = 0;
-        for c in characters.skip(start_index) {
-            if let Some(num) = char_to_num(c) {
-                output = output * 10 + num as u32
-            } else {
-                break;
-            }
-        }
-        output
-    }
-
-
     // Returns index of a key in an array
     fn find_key_in_array<T: Copy + PartialOrd, const COUNT: usize>(key: T, arr: [T; COUNT]) -> Option<usize> {
         arr.iter().position(|&s| s == key)
@@ -378,12 +1022,6 @@ pub mod fen {
     mod tests {
         use super::*;
 
-        #[test]
-        fn test_collect_nums() {
-            assert_eq!(collect_nums("1234".chars(), 0), 1234);
-            assert_eq!(collect_nums("1234 1".chars(), 1), 234);
-        }
-
         #[test]
         fn test_read_fen() {
 
@@ -391,11 +1029,18 @@ pub mod fen {
             let result = read_fen("k7/8/8/8/8/8/8/8 w HAha 31 5 20");
 
             let mut expected = Board::empty();
+            expected.castling_availability = CastlingAvailability {
+                w_ks: Some(0),
+                w_qs: Some(7),
+                b_ks: Some(0),
+                b_qs: Some(7),
+            };
             expected.black_board = [0, 1 << 7, 1 << 7];
             expected.halfmove_clock = 5;
             expected.fullmove_number = 20;
             expected.black_king_bit = 7;
             expected.en_passant_target_bit = Some(31);
+            expected.black_table_score = pesto::recount_table_score(&expected, PieceColor::Black);
 
             assert_eq!(result, expected);
 
@@ -405,10 +1050,10 @@ pub mod fen {
 
             let mut expected = Board::empty();
             expected.castling_availability = CastlingAvailability {
-                w_ks: true,
-                w_qs: false,
-                b_ks: false,
-                b_qs: true,
+                w_ks: Some(0),
+                w_qs: None,
+                b_ks: None,
+                b_qs: Some(7),
             };
 
             expected.white_board = [1 << 29 | 1 << 42, 1 << 29, 0];
@@ -416,9 +1061,213 @@ pub mod fen {
             expected.piece_to_move = PieceColor::Black;
             expected.white_material = 4;
             expected.black_material = 1;
+            expected.white_table_score = pesto::recount_table_score(&expected, PieceColor::White);
+            expected.black_table_score = pesto::recount_table_score(&expected, PieceColor::Black);
 
             assert_eq!(result, expected);
+
+            // Test multiple digit en passant target bits and move clocks
+            let result = read_fen("8/8/8/8/8/8/8/8 w - 35 50 100");
+
+            let mut expected = Board::empty();
+            expected.en_passant_target_bit = Some(35);
+            expected.halfmove_clock = 50;
+            expected.fullmove_number = 100;
+
+            assert_eq!(result, expected);
+
+            // Test extra whitespace between fields
+            assert_eq!(read_fen("8/8/8/8/8/8/8/8  w  -  -  50  100"), read_fen("8/8/8/8/8/8/8/8 w - - 50 100"));
+        }
+
+        #[test]
+        fn test_write_fen() {
+            let fens = [
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                "r1bqkb1r/pppp1ppp/2n2n2/4p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R w KQkq - 4 4",
+                "rn1qkbnr/p1ppp1pp/bp6/8/5pP1/2N5/PPPPPP1P/R1BQKBNR b Kq 33 0 12",
+                "1k6/8/8/8/8/8/8/1RK3R1 w GB - 0 1",
+                "8/8/8/8/8/8/8/8 w - - 50 100",
+            ];
+
+            for fen in fens {
+                assert_eq!(write_fen(&read_fen(fen)), fen);
+            }
+        }
+
+        #[test]
+        fn test_en_passant_field() {
+
+            // - is no en-passant, and bit 0 is a real en-passant target bit
+            let board = read_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+            assert_eq!(board.en_passant_target_bit, None);
+            assert_eq!(write_fen(&board).split_whitespace().nth(3), Some("-"));
+
+            let board = read_fen("8/8/8/8/8/8/8/8 w - 0 0 1");
+            assert_eq!(board.en_passant_target_bit, Some(0));
+            assert_eq!(write_fen(&board).split_whitespace().nth(3), Some("0"));
+        }
+
+        #[test]
+        fn test_display_from_str() {
+            let board = Board::new();
+            assert_eq!(format!("{board}").parse::<Board>(), Ok(board));
+
+            let board = read_fen("r1bqkb1r/pppp1ppp/2n2n2/4p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R w KQkq - 4 4");
+            assert_eq!(board.to_string().parse::<Board>(), Ok(board));
+
+            assert_eq!("r3k2r/8/8/8/8/8/8/4K3 w kx - 0 1".parse::<Board>(), Err(FenError::InvalidCastling));
+        }
+
+        #[test]
+        fn test_try_read_fen() {
+            assert_eq!(try_read_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"), Ok(Board::new()));
+            assert_eq!(try_read_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1"), Ok(read_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1")));
+
+            // Castling flags without the rooks / king
+            assert_eq!(try_read_fen("8/8/8/8/8/8/8/4K3 w KQ - 0 1"), Err(FenError::InconsistentCastling));
+            assert_eq!(try_read_fen("4k2r/8/8/8/8/8/8/4K3 w q - 0 1"), Err(FenError::InconsistentCastling));
+
+            // Garbage castling field
+            assert_eq!(try_read_fen("r3k2r/8/8/8/8/8/8/4K3 w kx - 0 1"), Err(FenError::InvalidCastling));
+
+            // Missing kings, which read_fen still accepts
+            assert_eq!(try_read_fen("8/8/8/8/8/8/8/4K3 w - - 0 1"), Err(FenError::MissingKing(PieceColor::Black)));
+            assert_eq!(try_read_fen("7k/8/8/8/8/8/8/R7 w - - 0 1"), Err(FenError::MissingKing(PieceColor::White)));
+            assert_eq!(read_fen("8/8/8/8/8/8/8/4K3 w - - 0 1").white_king_bit, 59);
         }
 
     }
 } 
+
+pub mod debugging {
+    use super::*;
+    use crate::uci::bit_to_square;
+
+    const PIECE_NAMES: [&str; 7] = ["", "pawn", "knight", "bishop", "rook", "queen", "king"];
+
+    // Describes what changed between two boards, one change per line, for finding out what a move did
+    // Pieces which left one bit and appeared on another are shown as moves, any other pieces are shown as removed or added
+    // Returns an empty string if the boards are equal
+    pub fn diff(before: &Board, after: &Board) -> String {
+        let (before_squares, after_squares) = (before.to_array(), after.to_array());
+
+        let mut removed: Vec<(PieceColor, usize, u8)> = Vec::new();
+        let mut added: Vec<(PieceColor, usize, u8)> = Vec::new();
+        for bit in 0..64 {
+            if before_squares[bit as usize] == after_squares[bit as usize] {
+                continue;
+            }
+
+            if let Some((color, piece_id)) = before_squares[bit as usize] {
+                removed.push((color, piece_id, bit));
+            }
+            if let Some((color, piece_id)) = after_squares[bit as usize] {
+                added.push((color, piece_id, bit));
+            }
+        }
+
+        let mut lines: Vec<String> = Vec::new();
+
+        // Pair up removed and added pieces of the same type into moves
+        removed.retain(|&(color, piece_id, initial_bit)| {
+            match added.iter().position(|&(added_color, added_piece_id, _)| (added_color, added_piece_id) == (color, piece_id)) {
+                Some(i) => {
+                    let (_, _, final_bit) = added.remove(i);
+                    lines.push(format!("{:?} {} moved {} -> {}", color, PIECE_NAMES[piece_id], bit_to_square(initial_bit), bit_to_square(final_bit)));
+                    false
+                },
+                None => true,
+            }
+        });
+
+        for (color, piece_id, bit) in removed {
+            lines.push(format!("{:?} {} removed from {}", color, PIECE_NAMES[piece_id], bit_to_square(bit)));
+        }
+        for (color, piece_id, bit) in added {
+            lines.push(format!("{:?} {} added on {}", color, PIECE_NAMES[piece_id], bit_to_square(bit)));
+        }
+
+        if before.piece_to_move != after.piece_to_move {
+            lines.push(format!("Piece to move: {:?} -> {:?}", before.piece_to_move, after.piece_to_move));
+        }
+
+        let (before_castling, after_castling) = (&before.castling_availability, &after.castling_availability);
+        // Castling availability holds the column of each castling rook, so only the file is shown
+        let file = |column: u8| (b'a' + 7 - column) as char;
+        for (name, before_rook_column, after_rook_column) in [
+            ("White kingside", before_castling.w_ks, after_castling.w_ks),
+            ("White queenside", before_castling.w_qs, after_castling.w_qs),
+            ("Black kingside", before_castling.b_ks, after_castling.b_ks),
+            ("Black queenside", before_castling.b_qs, after_castling.b_qs),
+        ] {
+            match (before_rook_column, after_rook_column) {
+                (Some(_), None) => lines.push(format!("{} castling lost", name)),
+                (None, Some(_)) => lines.push(format!("{} castling gained", name)),
+                (Some(before_column), Some(after_column)) if before_column != after_column => {
+                    lines.push(format!("{} castling rook file {} -> {}", name, file(before_column), file(after_column)));
+                },
+                _ => (),
+            }
+        }
+
+        if before.en_passant_target_bit != after.en_passant_target_bit {
+            let square = |bit: Option<u8>| bit.map_or("-".to_string(), bit_to_square);
+            lines.push(format!("En-passant target: {} -> {}", square(before.en_passant_target_bit), square(after.en_passant_target_bit)));
+        }
+
+        if before.halfmove_clock != after.halfmove_clock {
+            lines.push(format!("Halfmove clock: {} -> {}", before.halfmove_clock, after.halfmove_clock));
+        }
+        if before.fullmove_number != after.fullmove_number {
+            lines.push(format!("Fullmove number: {} -> {}", before.fullmove_number, after.fullmove_number));
+        }
+
+        lines.join("\n")
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::board_representation::fen::read_fen;
+
+        #[test]
+        fn test_diff() {
+
+            // Knight takes the pawn on e5
+            let board = read_fen("r1bqkb1r/pppp1ppp/2n2n2/4p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R w KQkq - 4 4");
+            assert_eq!(diff(&board, &board.apply_uci_move("f3e5").unwrap()), [
+                "White knight moved f3 -> e5",
+                "Black pawn removed from e5",
+                "Piece to move: White -> Black",
+                "Halfmove clock: 4 -> 0",
+            ].join("\n"));
+
+            // En-passant removes the black pawn on d5, not a piece on the bit moved to
+            let board = read_fen("4k3/8/8/3pP3/8/8/8/4K3 w - 28 0 1");
+            assert_eq!(diff(&board, &board.apply_uci_move("e5d6").unwrap()), [
+                "White pawn moved e5 -> d6",
+                "Black pawn removed from d5",
+                "Piece to move: White -> Black",
+                "En-passant target: d5 -> -",
+            ].join("\n"));
+
+            // Castling moves both pieces, and loses both castling rights
+            let board = read_fen("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1");
+            assert_eq!(diff(&board, &board.apply_uci_move("e1g1").unwrap()), [
+                "White rook moved h1 -> f1",
+                "White king moved e1 -> g1",
+                "Piece to move: White -> Black",
+                "White kingside castling lost",
+                "White queenside castling lost",
+                "Halfmove clock: 0 -> 1",
+            ].join("\n"));
+
+            // Chess960 castling with a different rook shows the files of the rooks
+            let (before, after) = (read_fen("4k3/8/8/8/8/8/8/4K1RR w H - 0 1"), read_fen("4k3/8/8/8/8/8/8/4K1RR w G - 0 1"));
+            assert_eq!(diff(&before, &after), "White kingside castling rook file h -> g");
+
+            assert_eq!(diff(&board, &board), "");
+        }
+    }
+}
@@ -0,0 +1,60 @@
+// Opening book for playing known moves early in the game instead of searching
+
+use std::collections::HashMap;
+
+use crate::board_representation::Board;
+use crate::board_representation::fen::read_fen;
+use crate::legal_moves;
+use crate::uci::{self, MoveError};
+
+// Book moves keyed by the position hash of the board they are played from
+// Positions can have several book moves, they are tried in the order they were added
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct OpeningBook {
+    pub entries: HashMap<u64, Vec<(u8, u8, Option<usize>)>>,
+}
+
+impl OpeningBook {
+
+    // Creates a book from (fen, uci move) pairs, e.g. ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "e2e4")
+    pub fn from_pairs(pairs: &[(&str, &str)]) -> Result<Self, MoveError> {
+        let mut book = OpeningBook::default();
+
+        for (fen, uci_move) in pairs {
+            let book_move = uci::parse_uci_move(uci_move)?;
+            book.entries.entry(read_fen(fen).position_hash()).or_default().push(book_move);
+        }
+
+        Ok(book)
+    }
+}
+
+// Returns a book move for the board, if there is one
+// Book moves are checked to be legal, so a bad entry or hash collision can't return an illegal move
+pub fn probe_book(board: &Board, book: &OpeningBook) -> Option<(u8, u8, Option<usize>)> {
+    let book_moves = book.entries.get(&board.position_hash())?;
+    let legal_moves = legal_moves::legal_moves(board);
+
+    book_moves.iter()
+        .find(|book_move| legal_moves.contains(book_move))
+        .copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_book() {
+        let book = OpeningBook::from_pairs(&[
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "e2e5"),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "d2d4"),
+        ]).unwrap();
+
+        // The illegal first entry is skipped
+        assert_eq!(probe_book(&Board::new(), &book), Some((52, 36, None)));
+        assert_eq!(probe_book(&Board::new().apply_uci_move("e2e4").unwrap(), &book), None);
+
+        assert_eq!(OpeningBook::from_pairs(&[("8/8/8/8/8/8/8/8 w - - 0 1", "e9e4")]), Err(MoveError::InvalidNotation));
+    }
+}
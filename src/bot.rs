@@ -1,31 +1,86 @@
 // For main chess bot algorithm
 // Quiescence Search
 
-use std::time::{Duration, Instant};
+use std::time::Duration;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// std::time::Instant panics in the browser, so web_time is used instead when compiling for wasm
+#[cfg(not(feature = "wasm"))]
+use std::time::Instant;
+#[cfg(feature = "wasm")]
+use web_time::Instant;
 
 use crate::board_representation;
 use crate::board_representation::{Board, PerspectiveBoards};
 use crate::move_generation;
+use crate::en_passant;
 use crate::bitboard_manipulation;
 use crate::fixed_vecor::FixedVector;
-use crate::turn;
+use crate::turn::{self, TurnMove};
 use crate::check_validation;
-use crate::bot_eval::eval;
+use crate::legal_moves;
+use crate::see;
+use crate::pieces;
+use crate::generic_math;
+use crate::bot_eval::{eval_weighted, EvalWeights, PieceValues};
+use crate::book::{self, OpeningBook};
+use crate::packed_move::Move;
 
 // Non capture weight for move ordering
 // Use value of -10 so non captures are searched last
-const NON_CAPTURE_WEIGHT: i8 = -10;
+const NON_CAPTURE_WEIGHT: i16 = -10;
 
 // Checkmate weight for minimax
 // Use 5.0 because typical max value from eval fn is 1.0
-const CHECKMATE_WEIGHT: f32 = 5.0;
+pub const CHECKMATE_WEIGHT: f32 = 5.0;
+
+// Root moves scoring within this of the best move are treated as equal when tie breaking
+const TIE_BREAK_EPSILON: f32 = 0.001;
+
+pub const QUIESCENCE_SEARCH_MAX_DEPTH: u8 = 3;
+
+// Absolute limit on the ply searched from the root, including quiescence search
+// Nodes at this ply are evaluated statically whatever the depth limit, so the recursion can't run away
+// This is well below u8::MAX, so depth arithmetic can't overflow
+const MAX_SEARCH_PLY: u8 = 64;
+
+// Margins for futility pruning, indexed by the remaining depth (depth_limit - current_depth)
+// Quiet moves are skipped if the static eval of the node plus this margin can't improve on the best move found so far
+const FUTILITY_MARGINS: [f32; 3] = [0.0, 0.1, 0.2];
+
+// Early draw exit settings
+// In a level position where both teams together have no more than DRAW_EXIT_MAX_MATERIAL (e.g. a rook each),
+// the search stops once the scores of this many depths in a row are all within DRAW_EXIT_MARGIN of each other
+// The scores aren't centred on 0 (the square tables shift them), so a draw is detected by the score settling instead
+const DRAW_EXIT_ITERATIONS: usize = 3;
+const DRAW_EXIT_MARGIN: f32 = 0.03;
+const DRAW_EXIT_MAX_MATERIAL: i16 = 10;
 
-const QUIESCENCE_SEARCH_MAX_DEPTH: u8 = 3;
+// Number of entries in the eval cache, each entry is 16 bytes
+const EVAL_CACHE_SIZE: usize = 1 << 16;
 const FIXED_VECTOR_PLACEHOLDER_VALUE: u8 = 255;
 
 // Max values for fixed vectors
 const MAX_MOVE_BITBOARD_BITS_ON: usize = 28;
-const MAX_TEAM_MOVES: usize = 96; // Maximum valid moves for one team in a turn
+// The most legal moves known in a legal position is 218, move lists also hold king moves into check which are only rejected by take_turn
+const MAX_TEAM_MOVES: usize = 256;
+
+const PROMOTION_OPTIONS: [Option<usize>; 4] = [
+    Some(pieces::PROMOTION_PIECE_IDS[0]),
+    Some(pieces::PROMOTION_PIECE_IDS[1]),
+    Some(pieces::PROMOTION_PIECE_IDS[2]),
+    Some(pieces::PROMOTION_PIECE_IDS[3]),
+];
+
+// A move in the format (initial_bit, final_bit, promotion_piece_id), the same as legal_moves
+pub type BestMove = (u8, u8, Option<usize>);
+
+// Why no best move could be generated
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SearchError {
+    NoLegalMoves, // The position is checkmate or stalemate
+}
 
 // Move information for move ordering vector
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -33,8 +88,9 @@ struct MoveInformation {
     initial_bit: u8,
     final_bit: u8,
 
-    move_score: i8,
-    ep_bits: (Option<u8>, Option<u8>)
+    move_score: i16,
+    ep_bits: (Option<u8>, Option<u8>),
+    promotion_piece_id: Option<usize>,
 }
 
 impl MoveInformation {
@@ -42,36 +98,430 @@ impl MoveInformation {
         MoveInformation {
             initial_bit: 0,
             final_bit: 0,
-            move_score: i8::MIN,
-            ep_bits: (None, None)
+            move_score: i16::MIN,
+            ep_bits: (None, None),
+            promotion_piece_id: None,
         }
     }
+
+    // Castles and en-passants aren't flagged, the packed move is only compared with generated moves
+    fn packed(&self) -> Move {
+        Move::new(self.initial_bit, self.final_bit, self.promotion_piece_id)
+    }
+
+    fn turn_move(&self) -> TurnMove {
+        TurnMove::new(self.initial_bit, self.final_bit, self.ep_bits, self.promotion_piece_id)
+    }
+}
+
+// Moves generated for a position, sorted by move score
+type MoveList = FixedVector<MoveInformation, MAX_TEAM_MOVES>;
+
+// Settings for the search
+#[derive(Debug, PartialEq, Clone)]
+pub struct SearchConfig {
+    pub max_duration: Duration, // The search stops once it has been running for this long
+    pub eval_weights: EvalWeights,
+
+    // When false the board is evaluated directly at the depth limit, instead of starting a quiescence search
+    // Faster, but weaker because captures at the depth limit aren't followed through
+    pub quiescence: bool,
+
+    // Number of plies the quiescence search carries on past the depth limit
+    pub quiescence_depth: u8,
+
+    // Book moves are played without searching
+    // Shared so cloning the config doesn't copy the whole book
+    pub opening_book: Option<Arc<OpeningBook>>,
+
+    // When set, the best move is picked pseudo randomly from the root moves with equal scores
+    // The same seed and position always give the same move, so the search stays reproducible
+    pub tie_break_seed: Option<u64>,
+
+    // Skip quiet moves near the depth limit which can't improve on the best move found so far
+    // Faster, but moves are pruned based on the eval instead of being searched, so it's off by default for exact analysis
+    pub futility_pruning: bool,
+
+    // Remember the static eval of positions reached at the end of the quiescence search, so positions reached by different move orders are only evaluated once
+    // The cached values are exactly what eval_weighted returns, so this never changes the result
+    // The piece square tables are updated incrementally, so this only pays off when the more expensive eval terms (mobility, hanging) are used
+    pub eval_cache: bool,
+
+    // Stop the search early in simplified endgames when several depths in a row score as a draw
+    // Saves time in dead drawn positions, but a win found deeper than the last depth searched would be missed
+    pub early_draw_exit: bool,
+
+    // Called with the search statistics each time a depth of the iterative deepening finishes
+    pub progress: Option<ProgressCallback>,
+
+    // When set, quiescence search skips captures with a static exchange evaluation below this (0 skips every losing capture)
+    // Captures which give check are still searched, because the exchange ignores checks
+    pub quiescence_see_threshold: Option<i16>,
+
+    // Quiescence nodes only cut off on the stand pat eval (the eval without making a capture) if it beats the parent by this much
+    // A larger margin searches more captures in positions which already look good enough
+    pub stand_pat_margin: f32,
+
+    // Subtracted from the score of draws for the team searching, so a positive contempt avoids draws and a negative one seeks them
+    // Only stalemates and root moves which lead straight to a rule draw are scored as draws
+    pub contempt: f32,
+
+    // Order captures by static exchange evaluation instead of the captured piece value minus the capturing piece value
+    // Defended pieces are then searched after winning captures, which costs an exchange evaluation for every capture
+    pub see_move_ordering: bool,
+
+    // When set the search can be stopped from another thread (e.g. the uci stop command), the same as if it had timed out
+    pub stop: Option<StopFlag>,
+}
+
+// Flag for stopping a running search, clones share the same flag
+// Wrapped so SearchConfig can still be compared, like ProgressCallback
+#[derive(Debug, Clone, Default)]
+pub struct StopFlag(pub Arc<AtomicBool>);
+
+impl StopFlag {
+    pub fn new() -> Self {
+        StopFlag::default()
+    }
+
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// Flags are only equal if they are the same flag
+impl PartialEq for StopFlag {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+// Callback for reporting search progress, e.g. for printing uci info lines during a long search
+// Wrapped so SearchConfig can still be cloned, compared, and debug printed
+#[derive(Clone)]
+pub struct ProgressCallback(pub Arc<dyn Fn(&SearchStats) + Send + Sync>);
+
+impl ProgressCallback {
+    pub fn new(callback: impl Fn(&SearchStats) + Send + Sync + 'static) -> Self {
+        ProgressCallback(Arc::new(callback))
+    }
+}
+
+// Callbacks are only equal if they are the same callback
+impl PartialEq for ProgressCallback {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl std::fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ProgressCallback")
+    }
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            max_duration: Duration::from_secs(1),
+            eval_weights: EvalWeights::default(),
+            quiescence: true,
+            quiescence_depth: QUIESCENCE_SEARCH_MAX_DEPTH,
+            opening_book: None,
+            tie_break_seed: None,
+            futility_pruning: false,
+            eval_cache: false,
+            early_draw_exit: false,
+            progress: None,
+            quiescence_see_threshold: None,
+            stand_pat_margin: 0.0,
+            contempt: 0.0,
+            see_move_ordering: false,
+            stop: None,
+        }
+    }
+}
+
+// Statistics collected during a search
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SearchStats {
+    pub depth_reached: u8, // Depth limit of the deepest search which finished before the timeout
+    pub nodes: u64, // Every node searched, including quiescence search nodes
+    pub quiescence_nodes: u64,
+    pub elapsed: Duration,
+    pub tt_hits: u64, // There is no transposition table yet, so this is always 0
+    pub futility_pruned: u64, // Quiet moves skipped by futility pruning
+    pub eval_cache_hits: u64,
+    pub see_pruned: u64, // Quiescence captures skipped for losing material by static exchange evaluation
+    pub best_move: Option<BestMove>, // Best move found by the deepest search which finished
+    pub max_ply: u8, // Deepest ply from the root reached by any node, including quiescence search nodes
+}
+
+// Static evals of leaf positions, indexed by the position hash
+// Entries store the full hash and the material change, because the eval also depends on the material change from the root
+struct EvalCache {
+    entries: Vec<Option<(u64, f32, f32)>>,
+}
+
+impl EvalCache {
+
+    // The cache is empty (and never stores anything) when it isn't enabled in the config
+    fn new(config: &SearchConfig) -> Self {
+        let size = if config.eval_cache { EVAL_CACHE_SIZE } else { 0 };
+        EvalCache { entries: vec![None; size] }
+    }
+
+    // Evaluates the board, using the cached value if there is one
+    fn eval(&mut self, parent_value: f32, board: &Board, config: &SearchConfig, stats: &mut SearchStats) -> f32 {
+        if self.entries.is_empty() {
+            return eval_weighted(parent_value, board, &config.eval_weights);
+        }
+
+        let hash = board.position_hash();
+        let index = (hash % self.entries.len() as u64) as usize;
+
+        match self.entries[index] {
+            Some((entry_hash, entry_parent_value, value)) if entry_hash == hash && entry_parent_value == parent_value => {
+                stats.eval_cache_hits += 1;
+                value
+            },
+            _ => {
+                let value = eval_weighted(parent_value, board, &config.eval_weights);
+                self.entries[index] = Some((hash, parent_value, value));
+                value
+            },
+        }
+    }
+}
+
+// Everything shared by the nodes of one search, which minimax passes down the tree
+struct SearchState<'a> {
+    config: &'a SearchConfig,
+
+    // For making search exit once it has been running for too long
+    start_instant: Instant,
+
+    // Node counts are added to this
+    stats: SearchStats,
+
+    // Leaf evals are stored in this, if the eval cache is enabled
+    eval_cache: EvalCache,
+
+    // Move lists reused between nodes, so they are generated in place instead of being copied around
+    // Allocated once per search with one list per ply, a node uses the list for its depth so its children don't overwrite it
+    move_buffers: Vec<MoveList>,
+}
+
+impl<'a> SearchState<'a> {
+    fn new(config: &'a SearchConfig) -> Self {
+        SearchState {
+            config,
+            start_instant: Instant::now(),
+            stats: SearchStats::default(),
+            eval_cache: EvalCache::new(config),
+            move_buffers: vec![FixedVector::new(MoveInformation::new()); MAX_SEARCH_PLY as usize],
+        }
+    }
+
+    // Evaluates a leaf, through the eval cache
+    fn eval(&mut self, parent_value: f32, board: &Board) -> f32 {
+        self.eval_cache.eval(parent_value, board, self.config, &mut self.stats)
+    }
 }
 
 // Generate best move using iterative deepening to get pv-moves
-// Returns a tuple with the initial pieces bit and the final bit it moves to
-pub fn gen_best_move(board: &Board, max_duration: Duration) -> Result<(u8, u8), ()> {
-    let start = Instant::now();
+// Returns a tuple with the initial pieces bit, the final bit it moves to, and the piece a pawn is promoted to
+pub fn gen_best_move(board: &Board, max_duration: Duration) -> Result<BestMove, SearchError> {
+    let config = SearchConfig {
+        max_duration,
+        ..Default::default()
+    };
 
-    let mut pv_move: Option<MoveInformation> = None;
-    for depth_limit in 3..100 {
-        let (_, move_information, timeout) = minimax(&board, 0, None, pv_move, true, 0, depth_limit, false, &start, &max_duration);
+    gen_best_move_with_config(board, &config)
+}
+
+// Same as gen_best_move, with the search settings provided by config
+pub fn gen_best_move_with_config(board: &Board, config: &SearchConfig) -> Result<BestMove, SearchError> {
+    gen_best_move_with_stats(board, config).0
+}
+
+// Same as gen_best_move_with_config, also returning statistics about the search
+pub fn gen_best_move_with_stats(board: &Board, config: &SearchConfig) -> (Result<BestMove, SearchError>, SearchStats) {
+    if let Some(book_move) = config.opening_book.as_ref().and_then(|opening_book| book::probe_book(board, opening_book)) {
+        return (Ok(book_move), SearchStats::default());
+    }
+
+    // Err is only returned when there is no move to make (checkmate or stalemate)
+    if !legal_moves::has_legal_move(board) {
+        return (Err(SearchError::NoLegalMoves), SearchStats::default());
+    }
+
+    // A depth 1 search without quiescence or a time limit is cheap, and makes sure a move is returned
+    // even if the first iteration of the real search times out or is stopped
+    // Its nodes aren't counted in the returned stats, which only describe the real search
+    // The real search then carries on with its start time, eval cache and move lists
+    let fallback_config = SearchConfig {
+        max_duration: Duration::MAX,
+        quiescence: false,
+        stop: None,
+        ..config.clone()
+    };
+    let mut fallback_search = SearchState::new(&fallback_config);
+    let (_, fallback_move, _) = minimax(board, 0.0, None, None, true, 0, 1, false, &mut fallback_search);
+    let mut search = SearchState { config, stats: SearchStats::default(), ..fallback_search };
+
+    let simplified = board.white_material + board.black_material <= DRAW_EXIT_MAX_MATERIAL && board.material_balance().abs() <= 1;
+    let mut scores: Vec<f32> = Vec::new();
+
+    let mut pv_move: Option<Move> = None;
+    for depth_limit in 3..MAX_SEARCH_PLY {
+        let (score, move_information, timeout) = minimax(board, 0.0, None, pv_move, true, 0, depth_limit, false, &mut search);
 
         // Everything from the search that was currently running when the timeout occured is thrown out
         // Instead use the old pv move as the best result
         if timeout {
             break;
         } else {
-            pv_move = Some(move_information);
+            pv_move = Some(move_information.packed());
+            search.stats.depth_reached = depth_limit;
+            search.stats.best_move = Some((move_information.initial_bit, move_information.final_bit, move_information.promotion_piece_id));
+
+            if let Some(progress) = &config.progress {
+                search.stats.elapsed = search.start_instant.elapsed();
+                (progress.0)(&search.stats);
+            }
+
+            scores.push(score);
+            if config.early_draw_exit && simplified && scores.len() >= DRAW_EXIT_ITERATIONS {
+                let recent_scores = &scores[scores.len() - DRAW_EXIT_ITERATIONS..];
+                let lowest = recent_scores.iter().copied().fold(f32::MAX, f32::min);
+                let highest = recent_scores.iter().copied().fold(f32::MIN, f32::max);
+
+                if highest - lowest < DRAW_EXIT_MARGIN {
+                    break;
+                }
+            }
         }
     }
 
-    // Return best move
-    if let Some(pv_move) = pv_move {
-        Ok((pv_move.initial_bit, pv_move.final_bit))
-    } else {
-        Err(())
+    search.stats.elapsed = search.start_instant.elapsed();
+
+    // Return best move, or the fallback move if no iteration finished
+    let best_move = pv_move.unwrap_or(fallback_move.packed());
+    (Ok(best_move.to_tuple()), search.stats)
+}
+
+// A root move and the score it was given by the search
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SearchResult {
+    pub initial_bit: u8,
+    pub final_bit: u8,
+    pub promotion_piece_id: Option<usize>,
+    pub score: f32,
+
+    // The search doesn't know about draws by the rules, so these tell an equal score apart from a rule draw
+    // Repetitions need the history of the game, so they are only found by game_state::gen_best_moves_in_game
+    pub draw_reason: Option<DrawReason>, // Set when the root position is already a rule draw
+    pub reaches_draw: bool, // The move leads straight to a rule draw (only the position after the move is checked)
+}
+
+// Why a position is a draw by the rules
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DrawReason {
+    Repetition,
+    FiftyMove,
+    InsufficientMaterial,
+}
+
+// Generates the best n moves, sorted from best to worst (MultiPV)
+// Every root move is searched without pruning against the other root moves, so each gets an exact score
+// Like gen_best_move, results from a search that times out are thrown out in favour of the last finished depth
+pub fn gen_best_moves(board: &Board, n: usize, config: &SearchConfig) -> Vec<SearchResult> {
+    let mut search = SearchState::new(config);
+
+    let perspective_boards = PerspectiveBoards::gen(board, board.piece_to_move);
+    let moves = order_moves(board, None, &perspective_boards, false, &config.eval_weights.piece_values, config.see_move_ordering);
+    let potential_checking_pieces = check_validation::get_potential_checking_pieces(board, board.piece_to_move);
+
+    let draw_reason = board.draw_reason();
+
+    let mut best_moves: Vec<SearchResult> = Vec::new();
+    'deepening: for depth_limit in 3..MAX_SEARCH_PLY {
+        let mut depth_results: Vec<SearchResult> = Vec::new();
+
+        for i in 0..moves.len() {
+            let move_information = moves.internal_array[i];
+            let piece_id = board_representation::read_piece_id(perspective_boards.friendly_board, move_information.initial_bit);
+
+            let turn_data = turn::take_turn(
+                board,
+                piece_id,
+                move_information.turn_move(),
+                false,
+                potential_checking_pieces.clone()
+            );
+
+            if let Ok((new_board, _)) = turn_data {
+                let capture_value = material_gain(board, &perspective_boards, piece_id, &move_information, &config.eval_weights.piece_values);
+                let (score, _, timeout) = minimax(&new_board, capture_value, None, None, false, 1, depth_limit, false, &mut search);
+
+                if timeout {
+                    break 'deepening;
+                }
+
+                let reaches_draw = new_board.draw_reason().is_some() && !new_board.is_checkmate();
+                let score = if reaches_draw { score - config.contempt } else { score };
+
+                depth_results.push(SearchResult {
+                    initial_bit: move_information.initial_bit,
+                    final_bit: move_information.final_bit,
+                    promotion_piece_id: move_information.promotion_piece_id,
+                    score,
+                    draw_reason,
+                    reaches_draw,
+                });
+            }
+        }
+
+        // Stable sort so equal scores keep the move ordering
+        depth_results.sort_by(|a, b| b.score.total_cmp(&a.score));
+        best_moves = depth_results;
     }
+
+    best_moves.truncate(n);
+    best_moves
+}
+
+// Searches to a fixed depth with no time limit, returning the score for the team to move
+// Unlike gen_best_move the material balance already on the board is counted, so scores of different positions can be compared
+pub fn position_score(board: &Board, depth: u8, config: &SearchConfig) -> f32 {
+    let config = SearchConfig {
+        max_duration: Duration::MAX,
+        ..config.clone()
+    };
+
+    let material_balance = config.eval_weights.piece_values.material_balance(board, board.piece_to_move);
+
+    let (score, _, _) = minimax(board, material_balance, None, None, true, 0, depth, false, &mut SearchState::new(&config));
+    score
+}
+
+// Searches to a fixed depth with no time limit, for tracking search performance
+// Returns the number of nodes searched (including quiescence search nodes) and the best move
+pub fn bench(board: &Board, depth: u8) -> (u64, (u8, u8)) {
+    let config = SearchConfig {
+        max_duration: Duration::MAX,
+        ..Default::default()
+    };
+
+    let mut search = SearchState::new(&config);
+    let (_, move_information, _) = minimax(board, 0.0, None, None, true, 0, depth, false, &mut search);
+
+    (search.stats.nodes, (move_information.initial_bit, move_information.final_bit))
 }
 
 // Generates best move using minimax algorithm
@@ -80,46 +530,56 @@ pub fn gen_best_move(board: &Board, max_duration: Duration) -> Result<(u8, u8),
 fn minimax(
     board: &Board,
 
-    parent_value: i8,
+    parent_value: f32,
     parent_min_max: Option<f32>, // For pruning
 
     // Move that is searched first
     // (leftmost branch)
-    pv_move: Option<MoveInformation>,
+    pv_move: Option<Move>,
 
     is_returning_max: bool,
-    current_depth: u8, // Depth of 0 for root
+    current_depth: u8, // Depth of 0 for root, quiescence search carries on counting from the depth it starts at
 
     // Depth at which the tree stops being searched down in favor of a final quiescence search
     depth_limit: u8,
     quiescence_search: bool,
 
-    // For making search exit once it has been running for too long
-    start_instant: &Instant,
-    timeout_duration: &Duration,
+    // The config, clock, statistics, eval cache and move lists shared by every node
+    search: &mut SearchState,
 ) -> (f32, MoveInformation, bool) {
+    let config = search.config;
 
-    // Timeout
-    if start_instant.elapsed() > *timeout_duration {
+    search.stats.nodes += 1;
+    search.stats.max_ply = search.stats.max_ply.max(current_depth);
+    if quiescence_search {
+        search.stats.quiescence_nodes += 1;
+    }
+
+    // Timeout, or stopped from another thread
+    if search.start_instant.elapsed() > config.max_duration || config.stop.as_ref().is_some_and(StopFlag::is_stopped) {
         return (0.0, MoveInformation::new(), true)
     }
 
+    // Stop at the ply cap, even if the depth limit is further away
+    if current_depth >= MAX_SEARCH_PLY {
+        return (search.eval(parent_value, board), MoveInformation::new(), false);
+    }
+
     // What to do when the depth limit is reached
-    if current_depth == depth_limit {
-        if quiescence_search { // Stop quiescence search
-            return (eval(parent_value, board), MoveInformation::new(), false);
+    if current_depth >= depth_limit {
+        if quiescence_search || !config.quiescence { // Stop quiescence search
+            return (search.eval(parent_value, board), MoveInformation::new(), false);
         } else { // Start quiescence search
             return minimax(
                 board,                          // board
                 parent_value,                   // parent_value
-                None,                           // parent_min_max
+                parent_min_max,                 // parent_min_max
                 None,                           // pv_move
                 is_returning_max,               // is_returning_max
-                0,                              // current_depth
-                QUIESCENCE_SEARCH_MAX_DEPTH,    // depth_limit
+                current_depth,                  // current_depth
+                current_depth.saturating_add(config.quiescence_depth), // depth_limit
                 true,                           // quiescence_search
-                start_instant,                  // start_instant
-                timeout_duration,               // timeout_duration
+                search,                         // search
             );
         }
     }
@@ -134,52 +594,109 @@ fn minimax(
     // If no parent min or max is provided use one that will result in no pruning
     let parent_min_max = parent_min_max.unwrap_or(parent_min_max_def);
 
+    // In quiescence search the team to move doesn't have to capture, so the eval of the board as it is (stand pat) is the starting min or max
+    // Captures are only searched if the stand pat doesn't already cause a cutoff
+    if quiescence_search {
+        let stand_pat = search.eval(parent_value, board);
+        if prune(parent_min_max, stand_pat - config.stand_pat_margin * min_max_multiplier as f32, is_returning_max) {
+            return (stand_pat, MoveInformation::new(), false);
+        }
+
+        min_or_max = stand_pat;
+    }
+
     // Get initial information
     let perspective_boards = PerspectiveBoards::gen(board, board.piece_to_move);
-    let moves = order_moves(&board, pv_move, &perspective_boards);
-    let potential_checking_pieces = check_validation::get_potential_checking_pieces(&board, board.piece_to_move);
+    // Depths at or past MAX_SEARCH_PLY returned above, so there is a move list for this depth
+    let ply = current_depth as usize;
+    order_moves_into(&mut search.move_buffers[ply], board, pv_move, &perspective_boards, quiescence_search, &config.eval_weights.piece_values, config.see_move_ordering);
+    let potential_checking_pieces = check_validation::get_potential_checking_pieces(board, board.piece_to_move);
+
+    // Futility pruning is only used near the depth limit, and never when in check because every evasion has to be searched
+    let remaining_depth = (depth_limit - current_depth) as usize;
+    let futility_pruning = config.futility_pruning
+        && !quiescence_search
+        && remaining_depth < FUTILITY_MARGINS.len()
+        && !board.in_check();
+
+    // Quiet moves are estimated with the static eval of this node plus the margin, so the eval is only worked out once
+    let futility_estimate = futility_pruning.then(|| {
+        eval_weighted(parent_value, board, &config.eval_weights) + FUTILITY_MARGINS[remaining_depth] * min_max_multiplier as f32
+    });
 
-    let mut king_was_in_check = false;
     let mut children_searched = 0;
     let mut best_move: MoveInformation = MoveInformation::new();
 
-    for i in 0..moves.len() {
-        let move_information = moves.internal_array[i];
+    // Root moves and their scores, kept when tie breaking so one of the equal best can be picked
+    let tie_breaking = current_depth == 0 && !quiescence_search && config.tie_break_seed.is_some();
+    let mut root_moves: Vec<(f32, MoveInformation)> = Vec::new();
+
+    // Moves are read from the list by index, because the search of each child borrows the search state
+    for i in 0..search.move_buffers[ply].len() {
+        let move_information = search.move_buffers[ply].internal_array[i];
         let piece_id = board_representation::read_piece_id(perspective_boards.friendly_board, move_information.initial_bit);
 
-        // Make turn by moving the piece from the initial bit to the final bit
         // Only make a turn if it involves a capture when quiescence_search == true
+        if quiescence_search && !turn::is_capture(board, move_information.final_bit, move_information.ep_bits) {
+            continue;
+        }
+
+        let losing_capture = quiescence_search && config.quiescence_see_threshold.is_some_and(|threshold| {
+            see::see(board, move_information.initial_bit, move_information.final_bit) < threshold
+        });
+
+        // Quiet moves (not captures or promotions) which can't improve min_or_max even with the margin added
+        // Moves which give check are still searched, which is only known once the turn is made
+        let futile = futility_estimate.is_some_and(|estimate| !update_min_or_max(min_or_max, estimate, is_returning_max))
+            && move_information.promotion_piece_id.is_none()
+            && !turn::is_capture(board, move_information.final_bit, move_information.ep_bits);
+
+        // Make turn by moving the piece from the initial bit to the final bit
         let turn_data = turn::take_turn(
             board,
             piece_id,
-            move_information.initial_bit,
-            move_information.final_bit,
-            quiescence_search,
-            move_information.ep_bits,
+            move_information.turn_move(),
+            false,
             potential_checking_pieces.clone()
         );
 
-        if let Ok((new_board, capture_value)) = turn_data {
+        if let Ok((new_board, _)) = turn_data {
+            if losing_capture && !new_board.in_check() {
+                search.stats.see_pruned += 1;
+                continue;
+            }
+
             children_searched += 1;
 
+            let capture_value = material_gain(board, &perspective_boards, piece_id, &move_information, &config.eval_weights.piece_values);
+
             // Sign of capture value changes if the enemy is making a capture
             // (negatively influences team which the search is running in favor of)
-            let capture_value = capture_value * min_max_multiplier;
-            
-            // Sign of capture value changes if the enemy is making a capture
-            // (negatively influences team which the search is running in favor of)
-            let capture_value = capture_value * min_max_multiplier;
+            let capture_value = capture_value * min_max_multiplier as f32;
+
+            // The first move is always searched, so a node where every other move is pruned still has a score
+            if futile && children_searched > 1 && !new_board.in_check() {
+                search.stats.futility_pruned += 1;
+                continue;
+            }
+
+            // When tie breaking the window is widened, so moves within TIE_BREAK_EPSILON of the best get exact scores
+            let child_min_max = if tie_breaking {
+                min_or_max - TIE_BREAK_EPSILON * min_max_multiplier as f32
+            } else {
+                min_or_max
+            };
+
             let (branch_value, _, timeout) = minimax(
                 &new_board,                     // board
                 parent_value + capture_value,   // parent_value
-                Some(min_or_max),               // parent_min_max
+                Some(child_min_max),            // parent_min_max
                 None,                           // pv_move
                 !is_returning_max,              // is_returning_max
                 current_depth + 1,              // current_depth
                 depth_limit,                    // depth_limit
                 quiescence_search,              // quiescence_search
-                start_instant,                  // start_instant
-                timeout_duration,               // timeout_duration
+                search,                         // search
             );
 
             // Propogate timeout upwards
@@ -187,6 +704,10 @@ fn minimax(
                return (0.0, MoveInformation::new(), timeout); 
             }
 
+            if tie_breaking {
+                root_moves.push((branch_value, move_information));
+            }
+
             // Update min or max value and best move
             if update_min_or_max(min_or_max, branch_value, is_returning_max) {
                 min_or_max = branch_value;
@@ -197,24 +718,85 @@ fn minimax(
             if prune(parent_min_max, min_or_max, is_returning_max) {
                 break;
             }
-        } else if turn_data == Err(turn::TurnError::Check) {
-            king_was_in_check = true;
         }
     }
 
-    // If 0 children were searched there are no valid moves for the piece
-    // If the king is in check this makes a checkmate
-    if children_searched == 0 {
-        if quiescence_search {
-            return (eval(parent_value, board), MoveInformation::new(), false);
-        } else if king_was_in_check {
-
-            // Ignore checkmates for quiescence_search since it only evaluates capture moves
+    // If 0 children were searched there are no legal moves for the team to move
+    // This is a checkmate if the king is in check, and a stalemate otherwise
+    // Moves which would put the king in check aren't searched, so whether the king is in check has to be checked on the board
+    // Ignore checkmates and stalemates for quiescence_search since it only evaluates capture moves, the stand pat is returned instead
+    if children_searched == 0 && !quiescence_search {
+        if board.in_check() {
             return (CHECKMATE_WEIGHT * -min_max_multiplier as f32, MoveInformation::new(), false);
         }
+
+        return (stalemate_score(board, parent_value, is_returning_max, config), MoveInformation::new(), false);
+    }
+
+    if let (true, Some(seed)) = (tie_breaking, config.tie_break_seed) {
+        best_move = tie_break(&root_moves, min_or_max, min_max_multiplier, seed ^ board.position_hash()).unwrap_or(best_move);
+    }
+
+    (min_or_max, best_move, false)
+}
+
+// Returns the score of a stalemate, which is a draw however much material either team has
+// Leaf evals only count the material change from the root, so a stalemate is evaluated with the change which makes the material level instead
+// The team ahead at the root then scores a stalemate below any line which keeps its advantage, and the team behind scores it above
+// Scores are from the root's perspective, so the contempt is always subtracted
+fn stalemate_score(board: &Board, parent_value: f32, is_returning_max: bool, config: &SearchConfig) -> f32 {
+    let root_color = if is_returning_max {
+        board.piece_to_move
+    } else {
+        board.piece_to_move.opposite()
+    };
+
+    let material_balance = config.eval_weights.piece_values.material_balance(board, root_color);
+    eval_weighted(parent_value - material_balance, board, &config.eval_weights) - config.contempt
+}
+
+// Returns the material gained by the team making a move, from the piece captured and from promoting
+// Calculated here instead of using the value from take_turn so the piece values can be changed
+fn material_gain(
+    board: &Board,
+    perspective_boards: &PerspectiveBoards<'_>,
+    piece_id: usize,
+    move_information: &MoveInformation,
+    piece_values: &PieceValues,
+) -> f32 {
+
+    // En-passant captures a pawn which isn't on the final bit
+    let capture_piece_id = match move_information.ep_bits.1 {
+        Some(_) => pieces::PAWN_ID,
+        None => board_representation::read_piece_id(perspective_boards.enemy_board, move_information.final_bit),
+    };
+
+    let promotion_value = if piece_id == pieces::PAWN_ID && turn::is_promotion_bit(move_information.final_bit, board.piece_to_move) {
+        let color = board.piece_to_move;
+        piece_values.value(move_information.promotion_piece_id.unwrap_or(pieces::QUEEN_ID), color) - piece_values.value(pieces::PAWN_ID, color)
+    } else {
+        0.0
+    };
+
+    piece_values.value(capture_piece_id, board.piece_to_move.opposite()) + promotion_value
+}
+
+// Picks a pseudo random move from the moves scoring within TIE_BREAK_EPSILON of min_or_max
+// Moves keep their search order before picking, so the same seed always gives the same move
+fn tie_break(root_moves: &[(f32, MoveInformation)], min_or_max: f32, min_max_multiplier: i16, seed: u64) -> Option<MoveInformation> {
+    let equal_moves: Vec<MoveInformation> = root_moves
+        .iter()
+        .filter(|(score, _)| (score - min_or_max) * min_max_multiplier as f32 > -TIE_BREAK_EPSILON)
+        .map(|(_, move_information)| *move_information)
+        .collect();
+
+    if equal_moves.is_empty() {
+        return None;
     }
 
-    return (min_or_max, best_move, false);
+    let mut state = seed;
+    let index = generic_math::splitmix64(&mut state) % equal_moves.len() as u64;
+    Some(equal_moves[index as usize])
 }
 
 // Return true if the min_or_max value should be updated to the branch_value
@@ -243,77 +825,122 @@ fn prune(parent_min_max: f32, min_or_max: f32, is_returning_max: bool) -> bool {
 
 // Returns a FixedVector of mostly valid moves, with the format (initial_bit, final_bit, move_score)
 // This does not consider king safety
+// Same as order_moves_into, returning a new move list
 fn order_moves(
     board: &Board,
-    pv_move: Option<MoveInformation>,
+    pv_move: Option<Move>,
+    perspective_boards: &PerspectiveBoards<'_>,
+    captures_only: bool,
+    piece_values: &PieceValues,
+    see_ordering: bool,
+) -> MoveList {
+    let mut moves = FixedVector::new(MoveInformation::new());
+    order_moves_into(&mut moves, board, pv_move, perspective_boards, captures_only, piece_values, see_ordering);
+    moves
+}
+
+// Generates the moves into moves_fixed_vector, replacing the moves already in it
+fn order_moves_into(
+    moves_fixed_vector: &mut MoveList,
+    board: &Board,
+    pv_move: Option<Move>,
     perspective_boards: &PerspectiveBoards<'_>,
-) -> FixedVector<MoveInformation, MAX_TEAM_MOVES>{
-    let mut moves_fixed_vector: FixedVector<MoveInformation, MAX_TEAM_MOVES> = FixedVector::new(MoveInformation::new());
+    captures_only: bool, // Only generate capture moves, for quiescence search
+
+    // Move scores are whole numbers, so the values are rounded to the nearest pawn
+    piece_values: &PieceValues,
+
+    // Score captures by static exchange evaluation, which uses the piece values in pieces::PieceInformation
+    see_ordering: bool,
+) {
+    moves_fixed_vector.clear();
+    let pinned_bitboard = check_validation::pinned_pieces(board, board.piece_to_move);
 
     for initial_bit in 0..64 {
         let piece_id = board_representation::read_piece_id(perspective_boards.friendly_board, initial_bit);
-        let piece_value = perspective_boards.friendly_piece_information[piece_id].piece_value;
+        let piece_value = piece_values.value(piece_id, board.piece_to_move).round() as i16;
 
         if piece_id == 0 {
             continue;
         }
 
         // Generate moves for this piece
+        // Captures never include castling or a pawn double move, so only the en-passant capture bits are needed
         let (
             move_bitboard,
             en_passant_target_bit,
             en_passant_cap_bits
-        ) = move_generation::generate_moves(board, initial_bit, piece_id, board.piece_to_move, &perspective_boards);
+        ) = if captures_only {
+            let capture_bitboard = move_generation::generate_captures(board, initial_bit, piece_id, board.piece_to_move, perspective_boards);
+            (
+                move_generation::restrict_to_pin_ray(board, initial_bit, pinned_bitboard, capture_bitboard),
+                None,
+                en_passant::get_en_passant_capture(board, perspective_boards.friendly_board, perspective_boards.enemy_board, initial_bit)
+            )
+        } else {
+            move_generation::generate_turn_moves(board, initial_bit, piece_id, perspective_boards, pinned_bitboard)
+        };
 
-        let final_bits_vec: FixedVector<u8, MAX_MOVE_BITBOARD_BITS_ON> = bitboard_manipulation::bits_on(move_bitboard, FIXED_VECTOR_PLACEHOLDER_VALUE);
+        // A queen in the centre of an empty board has the most moves (27), so this should never fail
+        let final_bits_vec: FixedVector<u8, MAX_MOVE_BITBOARD_BITS_ON> = bitboard_manipulation::bits_on_checked(move_bitboard, FIXED_VECTOR_PLACEHOLDER_VALUE)
+            .expect("MAX_MOVE_BITBOARD_BITS_ON is smaller than the number of moves for a piece");
 
         // Iterate over each move
         for i in 0..final_bits_vec.len() {
             let final_bit = final_bits_vec.internal_array[i];
 
-            // Skip over pv_move bits so they dont get added to the output vec twice
-            if let Some(pv_move) = pv_move {
-                if initial_bit == pv_move.initial_bit && final_bit == pv_move.final_bit {
-                    continue;
-                }
-            }
-            
             // Get enemy piece value
+            // Castling in Chess960 moves the king onto a friendly rook, which isn't a capture
             let enemy_piece_id = board_representation::read_piece_id(perspective_boards.enemy_board, final_bit);
-            let enemy_piece_value = if enemy_piece_id == 0 {
-                0
-            } else {
-                perspective_boards.friendly_piece_information[enemy_piece_id].piece_value
-            };
+            let enemy_piece_value = piece_values.value(enemy_piece_id, board.piece_to_move.opposite()).round() as i16;
 
-            // Calculate move score
-            let move_score = if enemy_piece_value == 0 {
-                NON_CAPTURE_WEIGHT
+            // Pawns moving onto the last row get a seperate move for each promotion piece
+            let promotion_piece_ids: &[Option<usize>] = if piece_id == pieces::PAWN_ID && turn::is_promotion_bit(final_bit, board.piece_to_move) {
+                &PROMOTION_OPTIONS
             } else {
-                enemy_piece_value - piece_value
+                &[None]
             };
 
             let ep_bits = turn::get_ep_bits_for_turn(en_passant_target_bit, en_passant_cap_bits, final_bit);
-            let move_information = MoveInformation {
-                initial_bit,
-                final_bit,
-                move_score,
-                ep_bits,
-            };
+            for &promotion_piece_id in promotion_piece_ids {
+
+                // Material gained by promoting the pawn
+                let promotion_value = promotion_piece_id.map_or(0, |promotion_piece_id| {
+                    piece_values.value(promotion_piece_id, board.piece_to_move).round() as i16 - piece_value
+                });
+
+                // Calculate move score
+                // The pv move gets the max move score so it is sorted ontop of the array
+                // Promotions are searched before other non captures
+                let move_score = if pv_move.is_some_and(|pv_move| pv_move.to_tuple() == (initial_bit, final_bit, promotion_piece_id)) {
+                    i16::MAX
+                } else if enemy_piece_value == 0 && promotion_value == 0 {
+                    NON_CAPTURE_WEIGHT
+                } else if enemy_piece_value == 0 {
+                    promotion_value
+                } else if see_ordering {
+                    see::see(board, initial_bit, final_bit) + promotion_value
+                } else {
+                    enemy_piece_value - piece_value + promotion_value
+                };
+
+                let move_information = MoveInformation {
+                    initial_bit,
+                    final_bit,
+                    move_score,
+                    ep_bits,
+                    promotion_piece_id,
+                };
 
-            moves_fixed_vector.push(move_information);
+                moves_fixed_vector.push(move_information);
+            }
         }
     }
 
-    // Add pv move with max move score so it is sorted ontop of the array
-    if let Some(mut pv_move) = pv_move {
-        pv_move.move_score = i8::MAX;
-        moves_fixed_vector.push(pv_move);
-    }
-    
     // Sort moves
-    moves_fixed_vector.internal_array.sort_by(|a, b| b.move_score.cmp(&a.move_score));
-    moves_fixed_vector
+    // Only the moves generated for this position are sorted, the rest of the array can hold moves left over from another position
+    let length = moves_fixed_vector.len();
+    moves_fixed_vector.internal_array[..length].sort_by_key(|move_information| std::cmp::Reverse(move_information.move_score));
 }
 
 
@@ -321,21 +948,526 @@ fn order_moves(
 mod tests {
     use super::*;
     use crate::board_representation::fen::read_fen;
+    use crate::board_representation::PieceColor;
+
+    // Searches from the root to a fixed depth with no time limit, returning the score, best move and statistics
+    fn search_to_depth(board: &Board, depth: u8, config: &SearchConfig) -> (f32, MoveInformation, SearchStats) {
+        let mut search = SearchState::new(config);
+        let (score, move_information, _) = minimax(board, 0.0, None, None, true, 0, depth, false, &mut search);
+        (score, move_information, search.stats)
+    }
 
     #[test]
     fn test_bot() {
+        use crate::legal_moves::{has_legal_move, legal_move_boards};
+
+        // Be6 takes the last square from the white king, which is stalemate
         let board = board_representation::fen::read_fen("7k/6pp/8/1r6/6b1/8/8/K7 b - - 0 1");
+        let best_move = gen_best_move(&board, Duration::from_secs(1)).unwrap();
+        assert_ne!(best_move, (33, 19, None));
+
+        let (_, new_board) = legal_move_boards(&board).find(|&(legal_move, _)| legal_move == best_move).unwrap();
+        assert!(has_legal_move(&new_board) || new_board.in_check());
+
+        let board = board_representation::fen::read_fen("7k/6pp/8/8/8/7r/6r1/K7 b - - 0 1");
         let best_move = gen_best_move(&board, Duration::from_secs(1));
 
-        assert_eq!(best_move, Ok((33, 19)));
+        assert_eq!(best_move, Ok((40, 56, None)));
+    }
+
+    #[test]
+    fn test_stalemate_avoidance() {
+        use crate::legal_moves::{has_legal_move, legal_move_boards};
+
+        let is_checkmate = |board: &Board| !has_legal_move(board) && board.in_check();
+        let is_stalemate = |board: &Board| !has_legal_move(board) && !board.in_check();
+
+        // Qf7 and Qg6 are stalemate, but Kf7 mates next move (Kf7 Kh7 Qh3#)
+        let board = read_fen("7k/4K3/4Q3/8/8/8/8/8 w - - 0 1");
+        assert_eq!(legal_move_boards(&board).filter(|(_, new_board)| is_stalemate(new_board)).count(), 2);
+
+        let config = SearchConfig { max_duration: Duration::MAX, ..Default::default() };
+        let (score, move_information, _) = search_to_depth(&board, 4, &config);
+        let (_, new_board) = legal_move_boards(&board)
+            .find(|&((initial_bit, final_bit, _), _)| (initial_bit, final_bit) == (move_information.initial_bit, move_information.final_bit))
+            .unwrap();
+
+        // The move isn't stalemate, and white can mate after every reply
+        assert_eq!(score, CHECKMATE_WEIGHT);
+        assert!(has_legal_move(&new_board));
+        assert!(legal_move_boards(&new_board).all(|(_, reply_board)| legal_move_boards(&reply_board).any(|(_, mate_board)| is_checkmate(&mate_board))));
+    }
+
+    #[test]
+    fn test_contempt() {
+        let config = SearchConfig { max_duration: Duration::MAX, ..Default::default() };
+        let contempt_config = SearchConfig { contempt: 0.25, ..config.clone() };
+
+        // Stalemates score lower for the root team with a positive contempt, whichever team is to move
+        let stalemate = read_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+        for is_returning_max in [true, false] {
+            let score = stalemate_score(&stalemate, 0.0, is_returning_max, &config);
+            assert_eq!(stalemate_score(&stalemate, 0.0, is_returning_max, &contempt_config), score - 0.25);
+        }
+
+        // Kxb2 leaves a lone bishop, which is a draw by insufficient material, while Kd1 keeps the rook and bishop on the board
+        // Taking the draw is best, unless the draw is scored below losing
+        let board = read_fen("k7/7b/8/8/8/8/1r6/2K5 w - - 0 1");
+        let best_move = |contempt: f32| {
+            let config = SearchConfig { max_duration: Duration::from_millis(100), contempt, ..Default::default() };
+            let best_move = gen_best_moves(&board, 1, &config)[0];
+            ((best_move.initial_bit, best_move.final_bit), best_move.reaches_draw)
+        };
+
+        assert_eq!(best_move(0.0), ((61, 54), true));
+        assert_eq!(best_move(1.0), ((61, 60), false));
+    }
+
+    #[test]
+    fn test_search_symmetry() {
+        let config = SearchConfig { max_duration: Duration::MAX, ..Default::default() };
+
+        // Returns the best move, and the score from the white teams perspective (rescaled like eval_white_perspective)
+        let search = |board: &Board| {
+            let (score, move_information, _) = search_to_depth(board, 3, &config);
+            let sign = match board.piece_to_move {
+                PieceColor::Black => -1.0,
+                PieceColor::White => 1.0,
+            };
+
+            ((move_information.initial_bit, move_information.final_bit, move_information.promotion_piece_id), (score * 2.0 - 1.0) * sign)
+        };
+
+        // A sign bug in the search shows up as the mirrored position picking a different move, or scoring differently
+        // The stalemate position from test_bot isn't used, because Kg8 and Bd1 tie there and the mirrored search orders them the other way
+        for fen in [
+            "7k/6pp/8/8/8/7r/6r1/K7 b - - 0 1",
+            "4k3/8/8/3q4/8/8/3R4/3QK3 w - - 0 1",
+            "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1",
+            "r3k2r/1P6/8/8/8/8/6p1/R3K2R b KQkq - 0 1",
+        ] {
+            let board = read_fen(fen);
+            let ((initial_bit, final_bit, promotion_piece_id), score) = search(&board);
+            let (mirrored_move, mirrored_score) = search(&board.mirror());
+
+            assert_eq!(mirrored_move, (initial_bit ^ 56, final_bit ^ 56, promotion_piece_id), "{}", fen);
+            assert!((score + mirrored_score).abs() < 0.0001, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_search_stats() {
+        let (best_move, stats) = gen_best_move_with_stats(&Board::new(), &SearchConfig::default());
+
+        assert!(best_move.is_ok());
+        assert!(stats.nodes > 0);
+        assert!(stats.quiescence_nodes > 0 && stats.quiescence_nodes < stats.nodes);
+        assert!(stats.depth_reached >= 3);
+        assert!(stats.elapsed >= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_progress_callback() {
+        let reported_depths = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let callback_depths = Arc::clone(&reported_depths);
+
+        let config = SearchConfig {
+            max_duration: Duration::from_millis(300),
+            progress: Some(ProgressCallback::new(move |stats: &SearchStats| {
+                assert!(stats.best_move.is_some());
+                callback_depths.lock().unwrap().push(stats.depth_reached);
+            })),
+            ..Default::default()
+        };
+
+        // Called once for every finished depth, starting from 3
+        let (best_move, stats) = gen_best_move_with_stats(&Board::new(), &config);
+        let reported_depths = reported_depths.lock().unwrap();
+        assert!(!reported_depths.is_empty());
+        assert_eq!(*reported_depths, (3..=stats.depth_reached).collect::<Vec<_>>());
+        assert_eq!(best_move, Ok(stats.best_move.unwrap()));
+    }
+
+    #[test]
+    fn test_early_draw_exit() {
+        let board = read_fen("3k4/r7/8/8/8/8/4R3/4K3 w - - 0 1");
+        let config = SearchConfig { max_duration: Duration::from_secs(10), early_draw_exit: true, ..Default::default() };
+
+        // Stops well before the time limit, once the scores of depths 3 to 5 have settled
+        let (best_move, stats) = gen_best_move_with_stats(&board, &config);
+        assert!(stats.elapsed < Duration::from_secs(5));
+        assert_eq!(stats.depth_reached, 2 + DRAW_EXIT_ITERATIONS as u8);
+        assert!(legal_moves::legal_moves(&board).contains(&best_move.unwrap()));
+
+        // Not used with more material on the board, even if the position is level
+        let config = SearchConfig { max_duration: Duration::from_millis(500), early_draw_exit: true, ..Default::default() };
+        let (_, stats) = gen_best_move_with_stats(&Board::new(), &config);
+        assert!(stats.elapsed >= Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_bench() {
+        let (nodes, best_move) = bench(&Board::new(), 2);
+
+        // Pinned so accidental changes to the search are caught
+        assert_eq!(nodes, 255);
+        assert_eq!(bench(&Board::new(), 2), (nodes, best_move));
+
+        // The move lists are reused between searches, so moves left over from another position must not change the result
+        let config = SearchConfig { max_duration: Duration::MAX, ..Default::default() };
+        let mut reused_search = SearchState::new(&config);
+        for fen in [
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R w KQkq - 4 4",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "7k/6pp/8/1r6/6b1/8/8/K7 b - - 0 1",
+        ] {
+            let board = read_fen(fen);
+            reused_search.stats = SearchStats::default();
+            let (score, move_information, _) = minimax(&board, 0.0, None, None, true, 0, 3, false, &mut reused_search);
+
+            let (fresh_score, fresh_move_information, fresh_stats) = search_to_depth(&board, 3, &config);
+            assert_eq!((score, move_information, reused_search.stats.nodes), (fresh_score, fresh_move_information, fresh_stats.nodes), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_max_team_moves() {
+        // The most legal moves known in a legal position, which all have to fit in a move list
+        let board = read_fen("R6R/3Q4/1Q4Q1/4Q3/2Q4Q/Q4Q2/pp1Q4/kBNN1KB1 w - - 0 1");
+        assert_eq!(crate::legal_moves::legal_moves(&board).len(), 218);
+        assert!(gen_best_move(&board, Duration::from_millis(100)).is_ok());
+    }
+
+    #[test]
+    fn test_max_search_ply() {
+        let config = SearchConfig { max_duration: Duration::MAX, ..Default::default() };
+
+        // Queens and rooks give long capture sequences, and the depth limit is far past the cap
+        let board = read_fen("r2qk2r/8/8/3QR3/8/8/8/R3K3 w - - 0 1");
+        for current_depth in [MAX_SEARCH_PLY - 2, MAX_SEARCH_PLY, u8::MAX] {
+            let mut search = SearchState::new(&config);
+            let (score, _, timeout) = minimax(&board, 0.0, None, None, true, current_depth, u8::MAX, false, &mut search);
+
+            assert!(!timeout && score.is_finite());
+            assert_eq!(search.stats.max_ply, current_depth.max(MAX_SEARCH_PLY));
+        }
+
+        // Quiescence search is counted from the depth it starts at
+        let (_, _, stats) = search_to_depth(&board, 2, &config);
+        assert!(stats.max_ply > 2 && stats.max_ply <= 2 + QUIESCENCE_SEARCH_MAX_DEPTH);
+    }
+
+    #[test]
+    fn test_quiescence_see_threshold() {
+        let search = |board: &Board, quiescence_see_threshold| {
+            let config = SearchConfig { max_duration: Duration::MAX, quiescence_see_threshold, ..Default::default() };
+            let (_, move_information, stats) = search_to_depth(board, 2, &config);
+            ((move_information.initial_bit, move_information.final_bit), stats)
+        };
+
+        // Every black piece is defended, apart from the queen on e4 which the knight on c3 can take
+        let board = read_fen("r1b1k2r/ppp2ppp/2np1b2/4p3/2B1q3/2N2N2/PPPP1PPP/R1BQK2R w KQkq - 0 1");
+        let (best_move, stats) = search(&board, None);
+        let (see_best_move, see_stats) = search(&board, Some(0));
+
+        // Nc3xe4 is still found, with far fewer quiescence nodes
+        assert_eq!(best_move, (45, 35));
+        assert_eq!(see_best_move, best_move);
+        assert!(see_stats.see_pruned > 0);
+        assert!(see_stats.quiescence_nodes < stats.quiescence_nodes);
+    }
+
+    #[test]
+    fn test_stand_pat() {
+        let board = read_fen("4k3/8/3p4/4p3/8/8/8/Q3K3 w - - 0 1");
+        let stand_pat = eval_weighted(0.0, &board, &EvalWeights::default());
+        let search = |stand_pat_margin, parent_min_max| {
+            let config = SearchConfig { max_duration: Duration::MAX, stand_pat_margin, ..Default::default() };
+            let mut search = SearchState::new(&config);
+            let (score, _, _) = minimax(&board, 0.0, parent_min_max, None, true, 0, 0, false, &mut search);
+            (score, search.stats.quiescence_nodes)
+        };
+
+        // The only capture loses the queen for a pawn, so the eval without capturing is returned
+        let (score, quiescence_nodes) = search(0.0, None);
+        assert_eq!(score, stand_pat);
+        assert!(quiescence_nodes > 1);
+
+        // The stand pat cuts off straight away when it already beats the parent
+        assert_eq!(search(0.0, Some(stand_pat - 0.01)), (stand_pat, 1));
+
+        // Unless the margin is larger than the difference, then the capture is still searched
+        let (score, quiescence_nodes) = search(0.05, Some(stand_pat - 0.01));
+        assert_eq!(score, stand_pat);
+        assert!(quiescence_nodes > 1);
+    }
+
+    #[test]
+    fn test_quiescence_disabled() {
+        let board = read_fen("7k/6pp/8/1r6/6b1/8/8/K7 b - - 0 1");
+        let no_quiescence_config = SearchConfig { max_duration: Duration::MAX, quiescence: false, ..Default::default() };
+        let quiescence_config = SearchConfig { max_duration: Duration::MAX, ..Default::default() };
+
+        // Fewer nodes are searched without quiescence search
+        let (_, move_information, no_quiescence_stats) = search_to_depth(&board, 3, &no_quiescence_config);
+        let (_, _, quiescence_stats) = search_to_depth(&board, 3, &quiescence_config);
+
+        assert!(no_quiescence_stats.nodes < quiescence_stats.nodes);
+        assert_eq!(no_quiescence_stats.quiescence_nodes, 0);
+        assert!(crate::legal_moves::legal_moves(&board).contains(&(move_information.initial_bit, move_information.final_bit, None)));
+    }
+
+    #[test]
+    fn test_futility_pruning() {
+        let search = |board: &Board, futility_pruning| {
+            let config = SearchConfig { max_duration: Duration::MAX, futility_pruning, ..Default::default() };
+            let (_, move_information, stats) = search_to_depth(board, 3, &config);
+            ((move_information.initial_bit, move_information.final_bit, move_information.promotion_piece_id), stats)
+        };
+
+        // Fewer nodes are searched once winning the queen puts the quiet moves out of reach
+        let board = read_fen("r1b1k2r/ppp2ppp/2np1b2/4p3/2B1q3/2N2N2/PPPP1PPP/R1BQK2R w KQkq - 0 1");
+        let (_, pruned_stats) = search(&board, true);
+        let (_, unpruned_stats) = search(&board, false);
+
+        assert!(pruned_stats.futility_pruned > 0);
+        assert_eq!(unpruned_stats.futility_pruned, 0);
+        assert!(pruned_stats.nodes < unpruned_stats.nodes);
+
+        // Quiet moves are found from the board, so pruning doesn't depend on the move scores used for ordering
+        let config = SearchConfig { max_duration: Duration::MAX, futility_pruning: true, see_move_ordering: true, ..Default::default() };
+        let (_, _, see_stats) = search_to_depth(&board, 3, &config);
+        assert!(see_stats.futility_pruned > 0);
+
+        // The best moves from test_bot and test_underpromotion are still found
+        let board = read_fen("7k/6pp/8/1r6/6b1/8/8/K7 b - - 0 1");
+        assert_eq!(search(&board, true).0, search(&board, false).0);
+
+        let board = read_fen("7k/6pp/8/8/8/7r/6r1/K7 b - - 0 1");
+        assert_eq!(search(&board, true).0, (40, 56, None));
+
+        let board = read_fen("6br/5Ppk/6pp/8/8/8/8/K7 w - - 0 1");
+        assert_eq!(search(&board, true).0, (10, 2, Some(pieces::KNIGHT_ID)));
+    }
+
+    #[test]
+    fn test_eval_cache() {
+        let search = |board: &Board, eval_cache| {
+            let eval_weights = EvalWeights { material: 0.6, square_table: 0.3, mobility: 0.1, ..Default::default() };
+            let config = SearchConfig { max_duration: Duration::MAX, eval_weights, eval_cache, ..Default::default() };
+            let (score, move_information, stats) = search_to_depth(board, 2, &config);
+            (score, move_information, stats)
+        };
+
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "7k/6pp/8/1r6/6b1/8/8/K7 b - - 0 1",
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R w KQkq - 4 4",
+        ] {
+            let board = read_fen(fen);
+            let (cached_score, cached_move, cached_stats) = search(&board, true);
+            let (score, best_move, stats) = search(&board, false);
+
+            // Caching only skips repeated evals, so the search is otherwise identical
+            assert_eq!((cached_score, cached_move, cached_stats.nodes), (score, best_move, stats.nodes), "{}", fen);
+            assert_eq!(stats.eval_cache_hits, 0);
+        }
+
+        // Transpositions in the quiescence search are found in the cache
+        let (_, _, stats) = search(&read_fen("r2qk2r/ppp2ppp/2np1n2/2b1p1B1/2B1P1b1/2NP1N2/PPP2PPP/R2QK2R w KQkq - 0 1"), true);
+        assert!(stats.eval_cache_hits > 0);
+    }
+
+    #[test]
+    fn test_piece_values() {
+        let search = |board: &Board, knight_value| {
+            let mut config = SearchConfig { max_duration: Duration::MAX, ..Default::default() };
+            config.eval_weights.piece_values.set_value(pieces::KNIGHT_ID, knight_value);
+            let (_, move_information, _) = search_to_depth(board, 3, &config);
+            (move_information.initial_bit, move_information.final_bit)
+        };
+
+        // The bishop can take the knight on e5, but it's recaptured by the pawn on d6
+        // An even trade isn't worth it, but it is once the knight is worth more than the bishop
+        let board = read_fen("4k3/5p2/3p4/4n3/8/8/1B3PP1/4K3 w - - 0 1");
+        assert_ne!(search(&board, 3.0), (54, 27));
+        assert_eq!(search(&board, 6.0), (54, 27));
+
+        // Default piece values match the piece information, so material is counted the same as on the board
+        let board = read_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+        let piece_values = PieceValues::default();
+        assert_eq!(piece_values.material(&board, PieceColor::White), board.white_material as f32);
+        assert_eq!(piece_values.material_balance(&board, PieceColor::Black), -board.material_balance() as f32);
+
+        // Each team has its own values, so changing one team's knights leaves the other team's material alone
+        let mut piece_values = PieceValues::default();
+        piece_values.black[pieces::KNIGHT_ID] = 4.0;
+        assert_eq!(piece_values.material(&board, PieceColor::White), board.white_material as f32);
+        assert_eq!(piece_values.material(&board, PieceColor::Black), board.black_material as f32 + 2.0);
+    }
+
+    #[test]
+    fn test_tie_break_seed() {
+        let board = Board::new();
+        let search = |tie_break_seed| {
+            let config = SearchConfig { max_duration: Duration::MAX, tie_break_seed, ..Default::default() };
+            let (score, move_information, _) = search_to_depth(&board, 3, &config);
+            (score, move_information)
+        };
+
+        let (unseeded_score, _) = search(None);
+        for seed in 0..4 {
+            let (score, move_information) = search(Some(seed));
+
+            // The same seed always picks the same move, and tie breaking never changes the score
+            assert_eq!(search(Some(seed)), (score, move_information));
+            assert_eq!(score, unseeded_score);
+            assert!(crate::legal_moves::legal_moves(&board).contains(&(move_information.initial_bit, move_information.final_bit, None)));
+        }
+    }
+
+    #[test]
+    fn test_book_move() {
+        let opening_book = OpeningBook::from_pairs(&[("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "g1f3")]).unwrap();
+
+        // With no time to search, the book move is returned instead of the fallback move
+        let config = SearchConfig { max_duration: Duration::ZERO, opening_book: Some(Arc::new(opening_book)), ..Default::default() };
+
+        assert_eq!(gen_best_move_with_config(&Board::new(), &config), Ok((57, 42, None)));
+
+        let board = Board::new().apply_uci_move("e2e4").unwrap();
+        assert!(legal_moves::legal_moves(&board).contains(&gen_best_move_with_config(&board, &config).unwrap()));
+    }
+
+    #[test]
+    fn test_stop_flag() {
+        let board = read_fen("r1bqkb1r/pppp1ppp/2n2n2/4p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R w KQkq - 4 4");
+        let stop = StopFlag::new();
+        let config = SearchConfig { max_duration: Duration::from_secs(60), stop: Some(stop.clone()), ..Default::default() };
+
+        // Stopped from another thread long before the time limit, the last finished depth's move is still returned
+        let stopper = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            stop.stop();
+        });
+
+        let (best_move, stats) = gen_best_move_with_stats(&board, &config);
+        stopper.join().unwrap();
+
+        assert!(stats.elapsed < Duration::from_secs(5));
+        assert!(legal_moves::legal_moves(&board).contains(&best_move.unwrap()));
+
+        // Already stopped, so only the fallback move is found
+        let (best_move, stats) = gen_best_move_with_stats(&board, &config);
+        assert_eq!(stats.depth_reached, 0);
+        assert!(legal_moves::legal_moves(&board).contains(&best_move.unwrap()));
+    }
+
+    #[test]
+    fn test_fallback_move() {
+
+        // The first iteration can't finish in a millisecond, but a legal move is still returned
+        let board = Board::new();
+        let best_move = gen_best_move(&board, Duration::from_millis(1)).unwrap();
+        assert!(legal_moves::legal_moves(&board).contains(&best_move));
+
+        // The fallback search isn't counted in the stats, only the node which timed out is
+        let config = SearchConfig { max_duration: Duration::ZERO, ..Default::default() };
+        let (best_move, stats) = gen_best_move_with_stats(&board, &config);
+        assert!(legal_moves::legal_moves(&board).contains(&best_move.unwrap()));
+        assert_eq!((stats.nodes, stats.depth_reached), (1, 0));
+
+        // No move can be returned for checkmate or stalemate
+        let checkmate = read_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3");
+        assert_eq!(gen_best_move(&checkmate, Duration::from_millis(1)), Err(SearchError::NoLegalMoves));
+
+        let stalemate = read_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+        assert_eq!(gen_best_move(&stalemate, Duration::from_millis(1)), Err(SearchError::NoLegalMoves));
+    }
+
+    #[test]
+    fn test_gen_best_moves() {
+        // Rd8# is the only mate
+        let board = read_fen("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1");
+        let config = SearchConfig::default();
+
+        let best_moves = gen_best_moves(&board, 3, &config);
+        assert_eq!(best_moves.len(), 3);
+        assert_eq!((best_moves[0].initial_bit, best_moves[0].final_bit, best_moves[0].score), (60, 4, CHECKMATE_WEIGHT));
+        assert!(best_moves[1].score < CHECKMATE_WEIGHT);
+        assert!(best_moves.windows(2).all(|pair| pair[0].score >= pair[1].score));
+
+        // Asking for more moves than there are gives every legal move
+        let best_moves = gen_best_moves(&board, 100, &config);
+        assert_eq!(best_moves.len(), crate::legal_moves::legal_moves(&board).len());
+    }
+
+    #[test]
+    fn test_underpromotion() {
+
+        // Promoting to a knight is checkmate, promoting to a queen isn't
+        let board = read_fen("6br/5Ppk/6pp/8/8/8/8/K7 w - - 0 1");
+        assert_eq!(gen_best_move(&board, Duration::from_secs(1)), Ok((10, 2, Some(pieces::KNIGHT_ID))));
+
+        // Every promotion piece is a seperate move
+        let perspective_boards = PerspectiveBoards::gen(&board, board.piece_to_move);
+        let result = order_moves(&board, None, &perspective_boards, false, &PieceValues::default(), false);
+        let promotions: Vec<_> = result.internal_array[0..result.len()].iter()
+            .filter(|m| m.initial_bit == 10 && m.final_bit == 2)
+            .map(|m| m.promotion_piece_id)
+            .collect();
+
+        assert_eq!(promotions, PROMOTION_OPTIONS.to_vec());
     }
 
     #[test]
     fn test_order_moves() {
         let board = read_fen("6pk/3p2pp/r7/8/6p1/3Q3q/8/K7 w - - 0 1");
         let perspective_boards = PerspectiveBoards::gen(&board, board.piece_to_move);
-        let result = order_moves(&board, None, &perspective_boards);
+        let result = order_moves(&board, None, &perspective_boards, false, &PieceValues::default(), false);
 
         assert_eq!(result.len(), 27);
+
+        // The pv move is sorted first, and isn't generated twice
+        let pv_move = Move::new(63, 55, None);
+        let result = order_moves(&board, Some(pv_move), &perspective_boards, false, &PieceValues::default(), false);
+        assert_eq!(result.len(), 27);
+        assert_eq!((result.internal_array[0].packed(), result.internal_array[0].move_score), (pv_move, i16::MAX));
+
+        // Only the queen captures on h7, d7, a6, and h3 are left when generating captures
+        let result = order_moves(&board, None, &perspective_boards, true, &PieceValues::default(), false);
+        let mut final_bits: Vec<u8> = result.internal_array[0..result.len()].iter().map(|m| m.final_bit).collect();
+        final_bits.sort();
+        assert_eq!(final_bits, vec![8, 12, 23, 40]);
+
+        // Knight on d2 pinned by a bishop, and rook on e2 pinned by a rook
+        let board = read_fen("4r2k/8/8/8/1b6/8/3NR3/4K3 w - - 0 1");
+        let perspective_boards = PerspectiveBoards::gen(&board, board.piece_to_move);
+        let result = order_moves(&board, None, &perspective_boards, false, &PieceValues::default(), false);
+        let moves = &result.internal_array[0..result.len()];
+
+        assert!(!moves.iter().any(|m| m.initial_bit == 52));
+        assert_eq!(moves.iter().filter(|m| m.initial_bit == 51).count(), 6);
+        assert!(moves.iter().filter(|m| m.initial_bit == 51).all(|m| m.final_bit % 8 == 3));
+
+        // Both rooks can take a knight, but only the one on a5 is undefended
+        // Without exchange evaluation both captures score the same, so the rook on e1 is searched first because of the bit order
+        let board = read_fen("4k3/8/3p4/n3n3/8/8/8/R3RK2 w - - 0 1");
+        let perspective_boards = PerspectiveBoards::gen(&board, board.piece_to_move);
+        let first_capture = |see_ordering| {
+            let result = order_moves(&board, None, &perspective_boards, true, &PieceValues::default(), see_ordering);
+            (result.internal_array[0].initial_bit, result.internal_array[0].final_bit, result.internal_array[0].move_score)
+        };
+
+        assert_eq!(first_capture(false), (59, 27, -2));
+        assert_eq!(first_capture(true), (63, 31, 3));
+
+        // Reusing a move list gives the same moves as a new list, even though the moves from the last position are left in the array
+        // There are more pawn takes queen captures than rook captures, so some are left over, and they would be sorted in front of the rook captures
+        let last_board = read_fen("4k3/8/8/q1q1q3/1P1P4/8/8/4K3 w - - 0 1");
+        let mut moves = order_moves(&last_board, None, &PerspectiveBoards::gen(&last_board, PieceColor::White), false, &PieceValues::default(), false);
+        order_moves_into(&mut moves, &board, None, &perspective_boards, true, &PieceValues::default(), false);
+        let result = order_moves(&board, None, &perspective_boards, true, &PieceValues::default(), false);
+        assert_eq!(moves.internal_array[0..moves.len()], result.internal_array[0..result.len()]);
     }
-}
\ No newline at end of file
+}
@@ -1,23 +1,1044 @@
+// The mop up term is used when the enemy team has at most this much material
+// and the team to move has at least MOP_UP_MIN_ADVANTAGE more material (a rook)
+const MOP_UP_MAX_ENEMY_MATERIAL: i16 = 3;
+const MOP_UP_MIN_ADVANTAGE: i16 = 5;
 
-// Weights
-// All weights are percentages, so the values add to 1
-const MATERIAL_WEIGHT: f32 = 0.7;
-const SQUARE_TABLE_WEIGHT: f32 = 0.3;
+// Number of moves which gives a mobility value of 1.0
+const MAX_MOBILITY: f32 = 80.0;
 
-use crate::board_representation::Board;
+// Value of hanging pieces which gives a hanging penalty of 1.0 (a queen)
+const MAX_HANGING_VALUE: f32 = 9.0;
+
+// The team to move can often save a hanging piece, so their penalty is scaled down by this
+const TEAM_TO_MOVE_HANGING_FACTOR: f32 = 0.5;
+
+// Bonuses for each rook on a semi-open file (no friendly pawns), an open file (no pawns at all),
+// and the enemy's second row (relative seventh rank)
+const SEMI_OPEN_FILE_BONUS: f32 = 0.5;
+const OPEN_FILE_BONUS: f32 = 1.0;
+const SEVENTH_RANK_BONUS: f32 = 1.0;
+
+// Difference in rook bonuses which gives a rook value of 1.0 (two rooks on open files on the seventh rank)
+const MAX_ROOK_DIFFERENCE: f32 = 4.0;
+
+// Bonus for a passed pawn which has reached the row before promoting, pawns further back get a fraction of this
+const PASSED_PAWN_BONUS: f32 = 1.0;
+
+// Passed pawns protected by a pawn, or next to another passed pawn, are multiplied by this
+const CONNECTED_PASSED_PAWN_FACTOR: f32 = 2.0;
+
+// Passed pawn bonuses are multiplied by this in the endgame, and not at all in the midgame
+const ENDGAME_PASSED_PAWN_FACTOR: f32 = 2.0;
+
+// Difference in passed pawn bonuses which gives a passed pawn value of 1.0
+const MAX_PASSED_PAWN_DIFFERENCE: f32 = 8.0;
+
+// Material differences are scaled by this in opposite colored bishop endings (bishops on different bit colors and only pawns otherwise)
+// These are very drawish even a pawn or two up, the factor is used fully in the endgame and faded out towards the midgame
+const OPPOSITE_BISHOP_MATERIAL_FACTOR: f32 = 0.25;
+
+// Bonus for an unblocked pawn one row from promoting, multiplied by ENDGAME_PROMOTING_PAWN_FACTOR in the endgame
+const PROMOTING_PAWN_BONUS: f32 = 1.0;
+const ENDGAME_PROMOTING_PAWN_FACTOR: f32 = 3.0;
+
+// Difference in promoting pawn bonuses which gives a promoting pawn value of 1.0 (one pawn in the endgame)
+const MAX_PROMOTING_PAWN_DIFFERENCE: f32 = 3.0;
+
+// Bonus for a knight on an outpost in the centre, outposts nearer the edge get a fraction of this
+const OUTPOST_BONUS: f32 = 1.0;
+
+// Difference in outpost bonuses which gives an outpost value of 1.0 (two central outposts)
+const MAX_OUTPOST_DIFFERENCE: f32 = 2.0;
+
+// Difference in king activity which gives a king activity value of 1.0 (a king next to two passed pawns and the enemy king)
+const MAX_KING_ACTIVITY_DIFFERENCE: f32 = 3.0;
+
+use crate::board_representation::{Board, PieceColor, PerspectiveBoards};
+use crate::board_representation::fen;
+use crate::board_representation;
+use crate::bitboard_manipulation;
+use crate::direction_bitboards;
+use crate::pieces;
+use crate::move_generation;
+use crate::check_validation;
 use crate::pesto;
 use crate::generic_math;
 
-// Square tables encourage good mobility anyway, I think actually calculating the mobility would be too computationally expensive
-// const MOBILITY_WEIGHT: f32 = 0.2;
+// Weights for each term in the evaluation function
+#[derive(Debug, PartialEq, Clone)]
+pub struct EvalWeights {
+
+    // All weights are percentages, so material, square_table, mobility, and hanging should add to 1
+    pub material: f32,
+    pub square_table: f32,
+
+    // Square tables encourage good mobility anyway, and actually calculating the mobility is expensive
+    // So it isn't used by default
+    pub mobility: f32,
+
+    // Penalises pieces which are attacked and undefended
+    // This needs attack maps for both teams, so like mobility it isn't used by default
+    pub hanging: f32,
+
+    // Rewards rooks on open and semi-open files, and on the seventh rank
+    // Square tables already cover the seventh rank a little, so it isn't used by default until it's tuned
+    pub rooks: f32,
+
+    // Rewards passed pawns, more so when they are close to promoting, connected, or in the endgame
+    // Not used by default until it's tuned
+    pub passed_pawns: f32,
+
+    // Rewards kings close to friendly passed pawns, and close to the enemy king when ahead in material
+    // Only used towards the endgame, and not used by default until it's tuned
+    pub king_activity: f32,
+
+    // Penalises a king boxed in on its back row, when the enemy has a rook or queen which could checkmate it there
+    // Not used by default until it's tuned
+    pub back_rank: f32,
+
+    // Rewards knights on outposts (the enemy half of the board, defended by a pawn, and out of reach of enemy pawns)
+    // Not used by default until it's tuned
+    pub outposts: f32,
+
+    // Rewards pawns on the row before promoting with nothing in front of them, mostly in the endgame
+    // The square tables can't tell a pawn about to become a queen from any other advanced pawn, not used by default until it's tuned
+    pub promoting_pawns: f32,
+
+    // Constant bonus for the team to move, added on top of the other weights
+    // Having the move is worth something, and it reduces the eval swinging between odd and even depths
+    pub tempo: f32,
+
+    // Only used in endgames where the team to move is winning by enough to force a checkmate
+    // The other weights are scaled down by (1 - mop_up) when it is used
+    pub mop_up: f32,
+
+    // Values of each piece used for material by the search, and for move ordering
+    pub piece_values: PieceValues,
+}
+
+// Material value of each piece for each team, indexed by piece id (index 0 is an empty bit, so should stay at 0)
+// Defaults to the values in pieces::PieceInformation, but can be changed to try out other values (e.g. a bishop worth 3.25)
+// The teams are kept seperate like the piece information, instead of assuming both teams value their pieces the same
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct PieceValues {
+    pub white: [f32; 7],
+    pub black: [f32; 7],
+}
+
+impl Default for PieceValues {
+    fn default() -> Self {
+        PieceValues {
+            white: pieces::WHITE_PIECE_INFORMATION.map(|piece_information| piece_information.piece_value as f32),
+            black: pieces::BLACK_PIECE_INFORMATION.map(|piece_information| piece_information.piece_value as f32),
+        }
+    }
+}
+
+impl PieceValues {
+    pub fn value(&self, piece_id: usize, color: PieceColor) -> f32 {
+        match color {
+            PieceColor::Black => self.black[piece_id],
+            PieceColor::White => self.white[piece_id],
+        }
+    }
+
+    // Sets the value of a piece for both teams
+    pub fn set_value(&mut self, piece_id: usize, value: f32) {
+        self.white[piece_id] = value;
+        self.black[piece_id] = value;
+    }
+
+    // Returns the total value of a teams pieces
+    pub fn material(&self, board: &Board, color: PieceColor) -> f32 {
+        let team_board = match color {
+            PieceColor::Black => &board.black_board,
+            PieceColor::White => &board.white_board,
+        };
+
+        let mut material = 0.0;
+        let mut team_bitboard = board_representation::team_occupancy(team_board);
+        while let Some(bit) = bitboard_manipulation::pop_lowest_bit(&mut team_bitboard) {
+            material += self.value(board_representation::read_piece_id(team_board, bit), color);
+        }
+        material
+    }
+
+    // Returns the material of a team minus the material of the other team
+    pub fn material_balance(&self, board: &Board, color: PieceColor) -> f32 {
+        self.material(board, color) - self.material(board, color.opposite())
+    }
+}
+
+impl Default for EvalWeights {
+    fn default() -> Self {
+        EvalWeights {
+            material: 0.7,
+            square_table: 0.3,
+            mobility: 0.0,
+            hanging: 0.0,
+            rooks: 0.0,
+            passed_pawns: 0.0,
+            king_activity: 0.0,
+            back_rank: 0.0,
+            outposts: 0.0,
+            promoting_pawns: 0.0,
+            tempo: 0.01,
+            mop_up: 0.3,
+            piece_values: PieceValues::default(),
+        }
+    }
+}
+
+// Basic evaluation function using the default weights
+pub fn eval(material_change: f32, board: &Board) -> f32 {
+    eval_weighted(material_change, board, &EvalWeights::default())
+}
 
 // Basic evaluation function
 // Called by leaf nodes during minimax search
+// Returns a value from 0.0 to 1.0
 // Only use material change from the starting position, to the board at the leaf node
 // and a piece square table value
-pub fn eval(material_change: i8, board: &Board) -> f32 {
+pub fn eval_weighted(material_change: f32, board: &Board, weights: &EvalWeights) -> f32 {
     let square_table_value = pesto::get_table_value(board);
-    let material_value = generic_math::f32_scale(material_change as f32, -20.0, 20.0);
+    let material_value = generic_math::f32_scale_clamped(material_change * opposite_bishop_factor(board), -20.0, 20.0);
+
+    let mut value = material_value * weights.material + square_table_value * weights.square_table;
+
+    if weights.mobility != 0.0 {
+        value += mobility_value(board) * weights.mobility;
+    }
+
+    if weights.hanging != 0.0 {
+        value += hanging_value(board) * weights.hanging;
+    }
+
+    if weights.rooks != 0.0 {
+        value += rook_value(board) * weights.rooks;
+    }
+
+    if weights.passed_pawns != 0.0 {
+        value += passed_pawn_value(board) * weights.passed_pawns;
+    }
+
+    if weights.king_activity != 0.0 {
+        value += king_activity_value(board) * weights.king_activity;
+    }
+
+    if weights.back_rank != 0.0 {
+        value += back_rank_value(board) * weights.back_rank;
+    }
+
+    if weights.outposts != 0.0 {
+        value += outpost_value(board) * weights.outposts;
+    }
+
+    if weights.promoting_pawns != 0.0 {
+        value += promoting_pawn_value(board) * weights.promoting_pawns;
+    }
+
+    let value = if let Some(mop_up_value) = mop_up_value(board) {
+        value * (1.0 - weights.mop_up) + mop_up_value * weights.mop_up
+    } else {
+        value
+    };
+
+    // The eval is from the perspective of the team to move, so the tempo bonus is always positive
+    (value + weights.tempo).min(1.0)
+}
+
+// Static evaluation of a position from the white teams perspective using the default weights
+pub fn eval_white_perspective(board: &Board) -> f32 {
+    eval_white_perspective_weighted(board, &EvalWeights::default())
+}
+
+// Static evaluation of a position from the white teams perspective
+// Returns a value from -1.0 to 1.0, positive when white is better
+//
+// Every term is scored for the team to move and then negated if black is to move,
+// so eval_white_perspective(board) == -eval_white_perspective(board.mirror())
+// This includes tempo, which always favours the team to move
+pub fn eval_white_perspective_weighted(board: &Board, weights: &EvalWeights) -> f32 {
+    let material_difference = weights.piece_values.material_balance(board, board.piece_to_move);
+    let sign = match board.piece_to_move {
+        PieceColor::Black => -1.0,
+        PieceColor::White => 1.0,
+    };
+
+    // Rescale from 0.0 to 1.0, so an equal position is 0.0
+    let value = eval_weighted(material_difference, board, weights) * 2.0 - 1.0;
+    value * sign
+}
+
+// Static evaluations of a set of positions from the white teams perspective, for tuning weights against game results
+// Fens which can't be read are given an eval of f32::NAN, so the output lines up with the input
+pub fn eval_positions(fens: &[&str], weights: &EvalWeights) -> Vec<f32> {
+    fens.iter()
+        .map(|fen| match fen::try_read_fen(fen) {
+            Ok(board) => eval_white_perspective_weighted(&board, weights),
+            Err(_) => f32::NAN,
+        })
+        .collect()
+}
+
+// Returns a value from 0.0 to 1.0 describing how many bits the team to move attacks
+fn mobility_value(board: &Board) -> f32 {
+    let attack_bitboard = move_generation::attack_map(board, board.piece_to_move);
+    generic_math::f32_scale_clamped(bitboard_manipulation::count_bits(attack_bitboard) as f32, 0.0, MAX_MOBILITY)
+}
+
+// Returns a value from 0.0 to 1.0 which is higher when the enemy has more hanging pieces than the team to move
+fn hanging_value(board: &Board) -> f32 {
+    let enemy_color = board.piece_to_move.opposite();
+
+    let hanging_difference = hanging_penalty(board, enemy_color) - hanging_penalty(board, board.piece_to_move);
+    (hanging_difference + 1.0) / 2.0
+}
+
+// Returns a value from 0.0 to 1.0 describing the value of a teams pieces which are attacked by the enemy and not defended
+// The penalty is lighter for the team to move, because they can often move the piece out of the way
+pub fn hanging_penalty(board: &Board, color: PieceColor) -> f32 {
+    let (team_board, enemy_color) = match color {
+        PieceColor::Black => (&board.black_board, PieceColor::White),
+        PieceColor::White => (&board.white_board, PieceColor::Black),
+    };
+
+    let team_bitboard = board_representation::team_occupancy(team_board);
+    let mut hanging_bitboard = team_bitboard
+        & move_generation::attack_map(board, enemy_color)
+        & !move_generation::attack_map(board, color);
+
+    let mut hanging_total = 0;
+    while let Some(bit) = bitboard_manipulation::pop_lowest_bit(&mut hanging_bitboard) {
+        let piece_id = board_representation::read_piece_id(team_board, bit);
+
+        // An attacked king is in check, which is handled by the search
+        if piece_id != pieces::KING_ID {
+            hanging_total += board_representation::piece_value(piece_id, color);
+        }
+    }
+
+    let penalty = generic_math::f32_scale_clamped(hanging_total as f32, 0.0, MAX_HANGING_VALUE);
+    if color == board.piece_to_move {
+        penalty * TEAM_TO_MOVE_HANGING_FACTOR
+    } else {
+        penalty
+    }
+}
+
+// Returns true if the static eval of a position can be trusted, because nothing is about to change the material
+// A position isn't quiet if the team to move is in check, can promote a pawn,
+// or can capture an undefended piece or a piece worth more than the capturing piece
+pub fn is_quiet(board: &Board) -> bool {
+    let potential_checking_pieces = check_validation::get_potential_checking_pieces(board, board.piece_to_move);
+    if check_validation::is_king_in_check(board, board.piece_to_move, &potential_checking_pieces) {
+        return false;
+    }
+
+    let perspective_boards = PerspectiveBoards::gen(board, board.piece_to_move);
+
+    // Row pawns of the team to move promote from
+    let promotion_row = match board.piece_to_move {
+        PieceColor::Black => 6,
+        PieceColor::White => 1,
+    };
+
+    if pawn_bitboard(perspective_boards.friendly_board) & bitboard_manipulation::RANK_MASKS[promotion_row] != 0 {
+        return false;
+    }
+
+    let defended_bitboard = move_generation::attack_map(board, board.piece_to_move.opposite());
+
+    let mut team_bitboard = perspective_boards.gen_bitboards().0;
+    while let Some(bit) = bitboard_manipulation::pop_lowest_bit(&mut team_bitboard) {
+        let piece_id = board_representation::read_piece_id(perspective_boards.friendly_board, bit);
+
+        let mut capture_bitboard = move_generation::generate_captures(board, bit, piece_id, board.piece_to_move, &perspective_boards);
+        while let Some(capture_bit) = bitboard_manipulation::pop_lowest_bit(&mut capture_bitboard) {
+            let capture_piece_id = board_representation::read_piece_id(perspective_boards.enemy_board, capture_bit);
+
+            if !bitboard_manipulation::bit_on(defended_bitboard, capture_bit)
+                || board_representation::piece_value(capture_piece_id, board.piece_to_move.opposite()) > board_representation::piece_value(piece_id, board.piece_to_move)
+            {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+// Returns a value from 0.0 to 1.0 which is higher when the team to move has more active rooks than the enemy
+fn rook_value(board: &Board) -> f32 {
+    let enemy_color = board.piece_to_move.opposite();
+
+    let rook_difference = rook_activity(board, board.piece_to_move) - rook_activity(board, enemy_color);
+    generic_math::f32_scale_clamped(rook_difference, -MAX_ROOK_DIFFERENCE, MAX_ROOK_DIFFERENCE)
+}
+
+// Returns the total open file and seventh rank bonus for a teams rooks
+// Each rook is counted separately, so doubled rooks on an open file both get the bonus
+fn rook_activity(board: &Board, color: PieceColor) -> f32 {
+    let (team_board, enemy_board, seventh_row) = match color {
+        PieceColor::Black => (&board.black_board, &board.white_board, 6),
+        PieceColor::White => (&board.white_board, &board.black_board, 1),
+    };
+
+    let friendly_pawns = pawn_bitboard(team_board);
+    let all_pawns = friendly_pawns | pawn_bitboard(enemy_board);
+
+    let mut activity = 0.0;
+    let mut team_bitboard = board_representation::team_occupancy(team_board);
+    while let Some(bit) = bitboard_manipulation::pop_lowest_bit(&mut team_bitboard) {
+        if board_representation::read_piece_id(team_board, bit) != pieces::ROOK_ID {
+            continue;
+        }
+
+        let (column, row) = bitboard_manipulation::get_piece_coordinates(bit);
+        let file = bitboard_manipulation::FILE_MASKS[column as usize];
+
+        if all_pawns & file == 0 {
+            activity += OPEN_FILE_BONUS;
+        } else if friendly_pawns & file == 0 {
+            activity += SEMI_OPEN_FILE_BONUS;
+        }
+
+        if row == seventh_row {
+            activity += SEVENTH_RANK_BONUS;
+        }
+    }
+    activity
+}
+
+// Returns a value from 0.0 to 1.0 which is higher when the team to move has stronger passed pawns than the enemy
+fn passed_pawn_value(board: &Board) -> f32 {
+    let enemy_color = board.piece_to_move.opposite();
+
+    let passed_pawn_difference = passed_pawn_bonus(board, board.piece_to_move) - passed_pawn_bonus(board, enemy_color);
+    generic_math::f32_scale_clamped(passed_pawn_difference, -MAX_PASSED_PAWN_DIFFERENCE, MAX_PASSED_PAWN_DIFFERENCE)
+}
+
+// Returns a bitboard of a teams passed pawns (pawns with no enemy pawns in front of them on their own or adjacent columns)
+fn passed_pawns(board: &Board, color: PieceColor) -> u64 {
+    let (team_board, enemy_board) = match color {
+        PieceColor::Black => (&board.black_board, &board.white_board),
+        PieceColor::White => (&board.white_board, &board.black_board),
+    };
+
+    let friendly_pawns = pawn_bitboard(team_board);
+    let enemy_pawns = pawn_bitboard(enemy_board);
+
+    let mut passed_pawns = 0;
+    let mut pawns = friendly_pawns;
+    while let Some(bit) = bitboard_manipulation::pop_lowest_bit(&mut pawns) {
+        let (column, row) = bitboard_manipulation::get_piece_coordinates(bit);
+        let front_span = rows_in_front(row, color) & (bitboard_manipulation::FILE_MASKS[column as usize] | bitboard_manipulation::adjacent_files_mask(column as u8));
+
+        if enemy_pawns & front_span == 0 {
+            passed_pawns |= 1 << bit;
+        }
+    }
+    passed_pawns
+}
+
+// Returns the total bonus for a teams passed pawns
+// The bonus grows as the pawn gets closer to promoting, and is larger for connected passed pawns and in the endgame
+fn passed_pawn_bonus(board: &Board, color: PieceColor) -> f32 {
+    let team_board = match color {
+        PieceColor::Black => &board.black_board,
+        PieceColor::White => &board.white_board,
+    };
+
+    let friendly_pawns = pawn_bitboard(team_board);
+    let passed_pawns = passed_pawns(board, color);
+
+    // ENDGAME_PASSED_PAWN_FACTOR in the endgame, 1.0 in the midgame
+    let endgame_factor = 1.0 + (1.0 - board.game_phase()) * (ENDGAME_PASSED_PAWN_FACTOR - 1.0);
+
+    let mut bonus = 0.0;
+    let mut pawns = passed_pawns;
+    while let Some(bit) = bitboard_manipulation::pop_lowest_bit(&mut pawns) {
+        let (column, row) = bitboard_manipulation::get_piece_coordinates(bit);
+
+        // Rows the pawn has moved from its starting row (0 to 5)
+        // Clamped because a fen can place a pawn behind its starting row
+        let rows_advanced = match color {
+            PieceColor::Black => row - 1,
+            PieceColor::White => 6 - row,
+        }.clamp(0, 5);
+
+        // Protected by a pawn diagonally behind, or next to another passed pawn on the same or a neighbouring row
+        let row_behind = match color {
+            PieceColor::Black => row - 1,
+            PieceColor::White => row + 1,
+        };
+        let adjacent_files = bitboard_manipulation::adjacent_files_mask(column as u8);
+        let neighbouring_rows = rank_mask(row - 1) | rank_mask(row) | rank_mask(row + 1);
+        let is_protected = friendly_pawns & adjacent_files & rank_mask(row_behind) != 0;
+        let is_flanked = passed_pawns & adjacent_files & neighbouring_rows != 0;
+
+        let connected_factor = if is_protected || is_flanked {
+            CONNECTED_PASSED_PAWN_FACTOR
+        } else {
+            1.0
+        };
+
+        bonus += PASSED_PAWN_BONUS * (rows_advanced as f32 / 5.0) * connected_factor * endgame_factor;
+    }
+    bonus
+}
+
+// Returns the mask of a row, or an empty mask for rows off the board
+// Pawns are normally never on the first or last row, but a fen can put them there
+fn rank_mask(row: i8) -> u64 {
+    usize::try_from(row).ok()
+        .and_then(|row| bitboard_manipulation::RANK_MASKS.get(row))
+        .copied()
+        .unwrap_or(0)
+}
+
+// Returns a value from 0.0 to 1.0 which is higher when the team to move has the more active king
+// The value is faded towards 0.5 in the midgame, where the king should stay safe instead
+fn king_activity_value(board: &Board) -> f32 {
+    let enemy_color = board.piece_to_move.opposite();
+
+    let king_activity_difference = king_activity(board, board.piece_to_move) - king_activity(board, enemy_color);
+    let value = generic_math::f32_scale_clamped(king_activity_difference, -MAX_KING_ACTIVITY_DIFFERENCE, MAX_KING_ACTIVITY_DIFFERENCE);
+    0.5 + (value - 0.5) * (1.0 - board.game_phase())
+}
+
+// Returns the activity of a teams king, from how close it is to friendly passed pawns,
+// and how close it is to the enemy king if the team is ahead in material
+fn king_activity(board: &Board, color: PieceColor) -> f32 {
+    let (king_bit, enemy_king_bit, material, enemy_material) = match color {
+        PieceColor::Black => (board.black_king_bit, board.white_king_bit, board.black_material, board.white_material),
+        PieceColor::White => (board.white_king_bit, board.black_king_bit, board.white_material, board.black_material),
+    };
+
+    let mut activity = 0.0;
+    let mut passed_pawns = passed_pawns(board, color);
+    while let Some(bit) = bitboard_manipulation::pop_lowest_bit(&mut passed_pawns) {
+        activity += (7 - bitboard_manipulation::chebyshev_distance(king_bit, bit)) as f32 / 7.0;
+    }
+
+    if material > enemy_material {
+        activity += (7 - bitboard_manipulation::chebyshev_distance(king_bit, enemy_king_bit)) as f32 / 7.0;
+    }
+    activity
+}
+
+// Returns a value from 0.0 to 1.0 which is higher when the enemy has a weak back rank and the team to move doesn't
+fn back_rank_value(board: &Board) -> f32 {
+    let enemy_color = board.piece_to_move.opposite();
+
+    let back_rank_difference = back_rank_penalty(board, enemy_color) - back_rank_penalty(board, board.piece_to_move);
+    (back_rank_difference + 1.0) / 2.0
+}
+
+// Returns 1.0 if a teams king is on its back row with no escape square off it (no luft),
+// and the enemy has a rook or queen to give a back rank checkmate, otherwise 0.0
+// Escape squares are the king moves off the back row which aren't blocked by friendly pieces or attacked by the enemy
+fn back_rank_penalty(board: &Board, color: PieceColor) -> f32 {
+    let (team_board, enemy_board, king_bit, back_row) = match color {
+        PieceColor::Black => (&board.black_board, &board.white_board, board.black_king_bit, 0),
+        PieceColor::White => (&board.white_board, &board.black_board, board.white_king_bit, 7),
+    };
+
+    let (column, row) = bitboard_manipulation::get_piece_coordinates(king_bit);
+    if row != back_row {
+        return 0.0;
+    }
+
+    let mut has_heavy_piece = false;
+    let mut enemy_bitboard = board_representation::team_occupancy(enemy_board);
+    while let Some(bit) = bitboard_manipulation::pop_lowest_bit(&mut enemy_bitboard) {
+        let piece_id = board_representation::read_piece_id(enemy_board, bit);
+        has_heavy_piece |= piece_id == pieces::ROOK_ID || piece_id == pieces::QUEEN_ID;
+    }
+
+    if !has_heavy_piece {
+        return 0.0;
+    }
+
+    let team_bitboard = board_representation::team_occupancy(team_board);
+    let king_moves = bitboard_manipulation::shift_direction_bitboard(king_bit, (column, row), &direction_bitboards::KING_MOVES);
+    let escape_bitboard = king_moves
+        & !bitboard_manipulation::RANK_MASKS[back_row as usize]
+        & !team_bitboard
+        & !move_generation::attack_map(board, color.opposite());
+
+    if escape_bitboard == 0 {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+// Returns the factor to scale material differences by, which is below 1.0 for opposite colored bishop endings
+fn opposite_bishop_factor(board: &Board) -> f32 {
+    let mut bishop_bit_colors = [None; 2];
+
+    for (i, half_board) in [&board.white_board, &board.black_board].into_iter().enumerate() {
+        let mut occupied_bitboard = board_representation::team_occupancy(half_board);
+
+        while let Some(bit) = bitboard_manipulation::pop_lowest_bit(&mut occupied_bitboard) {
+            match board_representation::read_piece_id(half_board, bit) {
+                pieces::KING_ID | pieces::PAWN_ID => (),
+                pieces::BISHOP_ID if bishop_bit_colors[i].is_none() => {
+                    let (column, row) = bitboard_manipulation::get_piece_coordinates(bit);
+                    bishop_bit_colors[i] = Some((column + row) % 2);
+                },
+                _ => return 1.0,
+            }
+        }
+    }
+
+    match bishop_bit_colors {
+        [Some(white_bit_color), Some(black_bit_color)] if white_bit_color != black_bit_color => {
+            1.0 - (1.0 - OPPOSITE_BISHOP_MATERIAL_FACTOR) * (1.0 - board.game_phase())
+        },
+        _ => 1.0,
+    }
+}
+
+// Returns a value from 0.0 to 1.0 which is higher when the team to move has more pawns about to promote than the enemy
+fn promoting_pawn_value(board: &Board) -> f32 {
+    let enemy_color = board.piece_to_move.opposite();
+
+    let promoting_pawn_difference = promoting_pawn_bonus(board, board.piece_to_move) - promoting_pawn_bonus(board, enemy_color);
+    generic_math::f32_scale_clamped(promoting_pawn_difference, -MAX_PROMOTING_PAWN_DIFFERENCE, MAX_PROMOTING_PAWN_DIFFERENCE)
+}
+
+// Returns the total bonus for a teams pawns on the row before promoting, which aren't blocked by a piece in front of them
+fn promoting_pawn_bonus(board: &Board, color: PieceColor) -> f32 {
+    let (team_board, seventh_row) = match color {
+        PieceColor::Black => (&board.black_board, 6),
+        PieceColor::White => (&board.white_board, 1),
+    };
+
+    let seventh_row_pawns = pawn_bitboard(team_board) & bitboard_manipulation::RANK_MASKS[seventh_row];
+
+    // Bits in front of the pawns, white pawns move towards row 0 and black pawns towards row 7
+    let unblocked_pawns = match color {
+        PieceColor::Black => seventh_row_pawns & !(board.occupied() >> 8),
+        PieceColor::White => seventh_row_pawns & !(board.occupied() << 8),
+    };
+
+    // ENDGAME_PROMOTING_PAWN_FACTOR in the endgame, 1.0 in the midgame
+    let endgame_factor = 1.0 + (1.0 - board.game_phase()) * (ENDGAME_PROMOTING_PAWN_FACTOR - 1.0);
+    PROMOTING_PAWN_BONUS * unblocked_pawns.count_ones() as f32 * endgame_factor
+}
+
+// Returns a value from 0.0 to 1.0 which is higher when the team to move has better knight outposts than the enemy
+fn outpost_value(board: &Board) -> f32 {
+    let enemy_color = board.piece_to_move.opposite();
+
+    let outpost_difference = outpost_bonus(board, board.piece_to_move) - outpost_bonus(board, enemy_color);
+    generic_math::f32_scale_clamped(outpost_difference, -MAX_OUTPOST_DIFFERENCE, MAX_OUTPOST_DIFFERENCE)
+}
+
+// Returns the total bonus for a teams knights on outposts, scaled by how central each one is
+fn outpost_bonus(board: &Board, color: PieceColor) -> f32 {
+    let (team_board, enemy_board) = match color {
+        PieceColor::Black => (&board.black_board, &board.white_board),
+        PieceColor::White => (&board.white_board, &board.black_board),
+    };
+
+    let friendly_pawns = pawn_bitboard(team_board);
+    let enemy_pawns = pawn_bitboard(enemy_board);
+
+    let mut bonus = 0.0;
+    let mut team_bitboard = board_representation::team_occupancy(team_board);
+    while let Some(bit) = bitboard_manipulation::pop_lowest_bit(&mut team_bitboard) {
+        if board_representation::read_piece_id(team_board, bit) != pieces::KNIGHT_ID {
+            continue;
+        }
+
+        let (column, row) = bitboard_manipulation::get_piece_coordinates(bit);
+        let (in_enemy_half, row_behind) = match color {
+            PieceColor::Black => (row >= 4, row - 1),
+            PieceColor::White => (row <= 3, row + 1),
+        };
+
+        let adjacent_files = bitboard_manipulation::adjacent_files_mask(bitboard_manipulation::file_of(bit));
+        // The row behind is only on the board for knights in the enemy half
+        let is_defended = in_enemy_half && friendly_pawns & adjacent_files & rank_mask(row_behind) != 0;
+
+        // Enemy pawns can only attack the knight if they are on a neighbouring file, and haven't already moved past it
+        let can_be_challenged = enemy_pawns & adjacent_files & rows_in_front(row, color) != 0;
+
+        if in_enemy_half && is_defended && !can_be_challenged {
+
+            // Distance from the centre of the board (0 to 6)
+            let centre_distance = (3 - column).max(column - 4) + (3 - row).max(row - 4);
+            bonus += OUTPOST_BONUS * (6 - centre_distance) as f32 / 6.0;
+        }
+    }
+    bonus
+}
+
+// Returns a bitboard of the rows in front of $row for a team, white pawns move towards row 0 and black pawns towards row 7
+fn rows_in_front(row: i8, color: PieceColor) -> u64 {
+    match color {
+        PieceColor::Black => u64::MAX.checked_shl((row as u32 + 1) * 8).unwrap_or(0),
+        PieceColor::White => (1u64 << (row * 8)) - 1,
+    }
+}
+
+// Returns a bitboard of the pawns on a team board
+fn pawn_bitboard(team_board: &[u64; 3]) -> u64 {
+    let mut pawn_bitboard = 0;
+    let mut team_bitboard = board_representation::team_occupancy(team_board);
+    while let Some(bit) = bitboard_manipulation::pop_lowest_bit(&mut team_bitboard) {
+        if board_representation::read_piece_id(team_board, bit) == pieces::PAWN_ID {
+            pawn_bitboard |= 1 << bit;
+        }
+    }
+    pawn_bitboard
+}
+
+// Returns a value from 0.0 to 1.0 rewarding the team to move for driving the enemy king to the edge of the board,
+// and for bringing it's own king close to the enemy king (both are needed to checkmate a lone king)
+// Returns None if the team to move isn't winning by enough for this to matter
+fn mop_up_value(board: &Board) -> Option<f32> {
+    let (friendly_material, enemy_material, friendly_king_bit, enemy_king_bit) = match board.piece_to_move {
+        PieceColor::Black => (board.black_material, board.white_material, board.black_king_bit, board.white_king_bit),
+        PieceColor::White => (board.white_material, board.black_material, board.white_king_bit, board.black_king_bit),
+    };
+
+    if enemy_material > MOP_UP_MAX_ENEMY_MATERIAL || friendly_material - enemy_material < MOP_UP_MIN_ADVANTAGE {
+        return None;
+    }
+
+    let (enemy_column, enemy_row) = bitboard_manipulation::get_piece_coordinates(enemy_king_bit);
+
+    // Distance of the enemy king from the centre of the board (0 to 6)
+    let centre_distance = (3 - enemy_column).max(enemy_column - 4) + (3 - enemy_row).max(enemy_row - 4);
+
+    // Distance between the kings (0 to 14)
+    let king_distance = bitboard_manipulation::manhattan_distance(friendly_king_bit, enemy_king_bit);
+
+    // Weighting from https://www.chessprogramming.org/Mop-up_Evaluation
+    let mop_up_total = 4.7 * centre_distance as f32 + 1.6 * (14 - king_distance) as f32;
+    Some(generic_math::f32_scale_clamped(mop_up_total, 0.0, 4.7 * 6.0 + 1.6 * 14.0))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board_representation::fen::read_fen;
+
+    #[test]
+    fn test_eval_saturates() {
+        let board = read_fen("4k3/8/8/8/8/8/8/QQQQK3 w - - 0 1");
+
+        // Material changes outside the scaling range shouldn't push the eval outside 0.0 to 1.0
+        assert!(eval(100.0, &board) <= 1.0);
+        assert!(eval(-100.0, &board) >= 0.0);
+        assert_eq!(eval(100.0, &board), eval(20.0, &board));
+    }
+
+    #[test]
+    fn test_eval_symmetry() {
+        let all_weights = EvalWeights {
+            material: 0.4,
+            square_table: 0.2,
+            mobility: 0.1,
+            hanging: 0.1,
+            rooks: 0.1,
+            passed_pawns: 0.1,
+            king_activity: 0.1,
+            back_rank: 0.1,
+            outposts: 0.1,
+            promoting_pawns: 0.1,
+            ..Default::default()
+        };
+
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r1bqk2r/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP3PPP/R1BQKB1R b KQkq - 0 7",
+            "4k3/8/8/8/4R3/8/6b1/4K3 w - - 0 1",
+            "r4rk1/1P3ppp/8/3P4/8/8/5PPP/R5K1 w - - 0 1",
+            "8/8/3k4/8/8/8/8/QQ2K3 b - - 0 1",
+        ] {
+            let board = read_fen(fen);
+            for weights in [EvalWeights::default(), all_weights.clone()] {
+                let value = eval_white_perspective_weighted(&board, &weights);
+                let mirrored_value = eval_white_perspective_weighted(&board.mirror(), &weights);
+                assert!((value + mirrored_value).abs() < 0.0001, "{}", fen);
+            }
+        }
+
+        // A white queen up is good for white whoever is to move
+        assert!(eval_white_perspective(&read_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1")) > 0.0);
+        assert!(eval_white_perspective(&read_fen("4k3/8/8/8/8/8/8/3QK3 b - - 0 1")) > 0.0);
+    }
+
+    #[test]
+    fn test_eval_positions() {
+        let fens = [
+            "4k3/8/8/8/8/8/8/3QK3 w - - 0 1",                             // White is a queen up
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",   // Even
+            "not a fen",
+            "3qk3/8/8/8/8/8/8/4K3 w - - 0 1",                             // Black is a queen up
+        ];
+
+        let evals = eval_positions(&fens, &EvalWeights::default());
+        assert_eq!(evals.len(), fens.len());
+        assert!(evals[2].is_nan());
+
+        let evals: Vec<f32> = evals.into_iter().filter(|eval| !eval.is_nan()).collect();
+        assert!(evals.iter().all(|eval| eval.is_finite()));
+        assert!(evals[0] > evals[1] && evals[1] > evals[2]);
+    }
+
+    #[test]
+    fn test_opposite_bishop_ending() {
+
+        // White is a pawn up with a dark squared bishop, against a light or a dark squared bishop
+        let opposite_board = read_fen("4k3/5p2/4p3/3b4/8/4P3/4PP2/2B1K3 w - - 0 1");
+        let same_board = read_fen("4k3/5p2/4p3/2b5/8/4P3/4PP2/2B1K3 w - - 0 1");
+
+        assert!(opposite_bishop_factor(&opposite_board) < 0.5);
+        assert_eq!(opposite_bishop_factor(&same_board), 1.0);
+
+        let material_weights = EvalWeights { material: 1.0, square_table: 0.0, tempo: 0.0, ..Default::default() };
+        let opposite_value = eval_white_perspective_weighted(&opposite_board, &material_weights);
+        let same_value = eval_white_perspective_weighted(&same_board, &material_weights);
+        assert!(opposite_value > 0.0);
+        assert!(opposite_value < same_value * 0.5);
+
+        // Any other piece means it isn't an opposite colored bishop ending
+        let knight_board = read_fen("4k3/5p2/4p3/3b4/8/4P3/4PP2/2B1K1N1 w - - 0 1");
+        assert_eq!(opposite_bishop_factor(&knight_board), 1.0);
+    }
+
+    #[test]
+    fn test_eval_weights() {
+
+        // Knights on the edge of the board with an extra pawn, against knights in the centre
+        let rim_board = read_fen("rnbqkbnr/pppppppp/8/8/8/N6N/PPPPPPPP/R1BQKB1R w KQkq - 0 1");
+        let centre_board = read_fen("rnbqkbnr/pppppppp/8/8/8/2N2N2/PPPPPPPP/R1BQKB1R w KQkq - 0 1");
+
+        let material_weights = EvalWeights { material: 1.0, square_table: 0.0, ..Default::default() };
+        let square_table_weights = EvalWeights { material: 0.0, square_table: 1.0, ..Default::default() };
+
+        assert!(eval_weighted(1.0, &rim_board, &material_weights) > eval_weighted(0.0, &centre_board, &material_weights));
+        assert!(eval_weighted(1.0, &rim_board, &square_table_weights) < eval_weighted(0.0, &centre_board, &square_table_weights));
+    }
+
+    #[test]
+    fn test_hanging_penalty() {
+
+        // White rook on e4 attacked by a bishop, and the same with a pawn defending the rook
+        let hanging_board = read_fen("4k3/8/8/8/4R3/8/6b1/4K3 w - - 0 1");
+        let defended_board = read_fen("4k3/8/8/8/4R3/3P4/6b1/4K3 w - - 0 1");
+
+        assert!(hanging_penalty(&hanging_board, PieceColor::White) > 0.0);
+        assert_eq!(hanging_penalty(&defended_board, PieceColor::White), 0.0);
+
+        // The penalty is lighter for the team to move
+        let mut black_to_move = hanging_board.clone();
+        black_to_move.piece_to_move = PieceColor::Black;
+        assert!(hanging_penalty(&hanging_board, PieceColor::White) < hanging_penalty(&black_to_move, PieceColor::White));
+
+        let hanging_weights = EvalWeights { hanging: 0.3, material: 0.4, ..Default::default() };
+        assert!(eval_weighted(0.0, &hanging_board, &hanging_weights) < eval_weighted(0.0, &defended_board, &hanging_weights));
+    }
+
+    #[test]
+    fn test_is_quiet() {
+        assert!(is_quiet(&Board::new()));
+        assert!(is_quiet(&read_fen("r1bqkb1r/pppp1ppp/2n2n2/4p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R w KQkq - 4 4")));
+
+        // Black queen on h4 can be taken by the knight on f3
+        assert!(!is_quiet(&read_fen("rnb1kbnr/pppp1ppp/8/4p3/4P2q/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3")));
+
+        // White is in check
+        assert!(!is_quiet(&read_fen("rnb1kbnr/pppp1ppp/8/4p3/5PPq/8/PPPPP2P/RNBQKBNR w KQkq - 1 3")));
+
+        // White pawn about to promote
+        assert!(!is_quiet(&read_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1")));
+    }
+
+    #[test]
+    fn test_tempo() {
+        let no_tempo_weights = EvalWeights { tempo: 0.0, ..Default::default() };
+        let tempo = EvalWeights::default().tempo;
+
+        // The same position with either team to move, the team to move always gets the bonus
+        for fen in ["rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1", "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1"] {
+            let board = read_fen(fen);
+            assert!((eval(0.0, &board) - eval_weighted(0.0, &board, &no_tempo_weights) - tempo).abs() < 0.0001);
+        }
+    }
+
+    #[test]
+    fn test_rook_activity() {
+
+        // White rook moving from the closed a file to the open d file
+        let closed_board = read_fen("4k3/pp3ppp/8/8/8/8/PP3PPP/R3K3 w - - 0 1");
+        let open_board = read_fen("4k3/pp3ppp/8/8/8/8/PP3PPP/3RK3 w - - 0 1");
+
+        let rook_weights = EvalWeights { rooks: 0.3, material: 0.4, ..Default::default() };
+        assert!(eval_weighted(0.0, &open_board, &rook_weights) > eval_weighted(0.0, &closed_board, &rook_weights));
+
+        // Doubled rooks on an open file both count, and a rook on the seventh rank gets an extra bonus
+        assert_eq!(rook_activity(&read_fen("4k3/pp3ppp/8/8/8/8/PP3PPP/3RRK2 w - - 0 1"), PieceColor::White), 2.0 * OPEN_FILE_BONUS);
+        assert_eq!(rook_activity(&read_fen("4k3/pp1R1ppp/8/8/8/8/PP3PPP/4K3 w - - 0 1"), PieceColor::White), OPEN_FILE_BONUS + SEVENTH_RANK_BONUS);
+
+        // Semi-open file for black, with only a white pawn on it
+        assert_eq!(rook_activity(&read_fen("r3k3/1p3ppp/8/8/8/8/PP3PPP/4K3 b - - 0 1"), PieceColor::Black), SEMI_OPEN_FILE_BONUS);
+    }
+
+    #[test]
+    fn test_passed_pawns() {
+
+        // Connected passed pawns on the sixth rank, against the same pawns on seperate columns
+        let connected_board = read_fen("4k3/8/3PP3/8/8/8/8/4K3 w - - 0 1");
+        let seperated_board = read_fen("4k3/8/P6P/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!(passed_pawn_bonus(&connected_board, PieceColor::White), CONNECTED_PASSED_PAWN_FACTOR * passed_pawn_bonus(&seperated_board, PieceColor::White));
+
+        let pawn_weights = EvalWeights { passed_pawns: 0.3, material: 0.7, square_table: 0.0, ..Default::default() };
+        assert!(eval_weighted(0.0, &connected_board, &pawn_weights) > eval_weighted(0.0, &seperated_board, &pawn_weights) + 0.05);
+
+        // Pawns further from promoting get a smaller bonus
+        assert!(passed_pawn_bonus(&read_fen("4k3/8/8/8/3PP3/8/8/4K3 w - - 0 1"), PieceColor::White) < passed_pawn_bonus(&connected_board, PieceColor::White));
+
+        // An enemy pawn on an adjacent column in front stops a pawn being passed, one behind doesn't
+        assert_eq!(passed_pawn_bonus(&read_fen("4k3/2p5/3P4/8/8/8/8/4K3 w - - 0 1"), PieceColor::White), 0.0);
+        assert!(passed_pawn_bonus(&read_fen("4k3/8/3P4/2p5/8/8/8/4K3 w - - 0 1"), PieceColor::White) > 0.0);
+
+        // Passed pawns count for black as well, and are worth less in the midgame
+        assert!(passed_pawn_bonus(&read_fen("4k3/8/8/8/8/3pp3/8/4K3 b - - 0 1"), PieceColor::Black) > 0.0);
+        assert!(
+            passed_pawn_bonus(&read_fen("rnbqkbnr/8/3PP3/8/8/8/8/RNBQKBNR w - - 0 1"), PieceColor::White)
+            < passed_pawn_bonus(&connected_board, PieceColor::White)
+        );
+
+        // Pawns on the first or last row, which a fen can give, don't look off the board
+        let weights = EvalWeights { passed_pawns: 0.1, ..Default::default() };
+        for fen in [
+            "4k3/8/8/8/8/8/8/P3K3 w - - 0 1",
+            "p3k3/8/8/8/8/8/8/4K3 w - - 0 1",
+            "P3k3/8/8/8/8/8/8/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/p3K3 w - - 0 1",
+        ] {
+            let board = read_fen(fen);
+            eval_weighted(0.0, &board, &weights);
+            assert!(passed_pawn_bonus(&board, PieceColor::White) >= 0.0, "{}", fen);
+            assert!(passed_pawn_bonus(&board, PieceColor::Black) >= 0.0, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_king_activity() {
+
+        // Pawn endgame, with the white king next to it's passed pawn against a white king stuck on the back rank
+        let active_board = read_fen("8/8/4k3/8/1PK5/8/6p1/8 w - - 0 1");
+        let passive_board = read_fen("8/8/4k3/8/1P6/8/6p1/2K5 w - - 0 1");
+        assert!(king_activity(&active_board, PieceColor::White) > king_activity(&passive_board, PieceColor::White));
+
+        let king_weights = EvalWeights { king_activity: 0.3, material: 0.7, square_table: 0.0, ..Default::default() };
+        assert!(eval_weighted(0.0, &active_board, &king_weights) > eval_weighted(0.0, &passive_board, &king_weights));
+
+        // Kings are rewarded for approaching the enemy king only when ahead in material
+        let near_board = read_fen("8/8/4k3/8/4K3/8/6p1/8 w - - 0 1");
+        let far_board = read_fen("8/8/4k3/8/8/8/6p1/K7 w - - 0 1");
+        assert_eq!(king_activity(&near_board, PieceColor::White), king_activity(&far_board, PieceColor::White));
+        assert!(king_activity(&near_board, PieceColor::Black) > king_activity(&far_board, PieceColor::Black));
+
+        // Faded out in the midgame
+        assert_eq!(king_activity_value(&Board::new()), 0.5);
+    }
+
+    #[test]
+    fn test_back_rank() {
+
+        // White king boxed in by its pawns against a black rook, and the same with luft on h3
+        let open_board = read_fen("r5k1/5pp1/7p/8/8/8/5PPP/6K1 w - - 0 1");
+        let luft_board = read_fen("r5k1/5pp1/7p/8/8/7P/5PP1/6K1 w - - 0 1");
+        assert_eq!(back_rank_penalty(&open_board, PieceColor::White), 1.0);
+        assert_eq!(back_rank_penalty(&luft_board, PieceColor::White), 0.0);
+        assert_eq!(back_rank_penalty(&open_board, PieceColor::Black), 0.0);
+
+        let back_rank_weights = EvalWeights { back_rank: 0.3, material: 0.4, ..Default::default() };
+        assert!(eval_weighted(0.0, &open_board, &back_rank_weights) < eval_weighted(0.0, &luft_board, &back_rank_weights));
+
+        // Luft which the enemy attacks doesn't count
+        let covered_board = read_fen("r5k1/5pp1/3b3p/8/8/7P/5PP1/6K1 w - - 0 1");
+        assert_eq!(back_rank_penalty(&covered_board, PieceColor::White), 1.0);
+
+        // No penalty without a rook or queen to give the checkmate
+        let minor_board = read_fen("b5k1/5pp1/7p/8/8/8/5PPP/6K1 w - - 0 1");
+        assert_eq!(back_rank_penalty(&minor_board, PieceColor::White), 0.0);
+    }
+
+    #[test]
+    fn test_outposts() {
+
+        // White knight on d5 defended by the e4 pawn, and the same knight with a black pawn on c7 which can push to c6
+        let outpost_board = read_fen("4k3/pp3ppp/8/3N4/4P3/8/PP3PPP/4K3 w - - 0 1");
+        let challenged_board = read_fen("4k3/ppp2ppp/8/3N4/4P3/8/PP3PPP/4K3 w - - 0 1");
+        assert!(outpost_bonus(&outpost_board, PieceColor::White) > 0.0);
+        assert_eq!(outpost_bonus(&challenged_board, PieceColor::White), 0.0);
+
+        let outpost_weights = EvalWeights { outposts: 0.3, material: 0.4, ..Default::default() };
+        let challenged_board = read_fen("4k3/p1p2ppp/8/3N4/4P3/8/PP3PPP/4K3 w - - 0 1");
+        assert!(eval_weighted(0.0, &outpost_board, &outpost_weights) > eval_weighted(0.0, &challenged_board, &outpost_weights));
+
+        // Undefended, or in its own half, isn't an outpost
+        assert_eq!(outpost_bonus(&read_fen("4k3/pp3ppp/8/3N4/8/4P3/PP3PPP/4K3 w - - 0 1"), PieceColor::White), 0.0);
+        assert_eq!(outpost_bonus(&read_fen("4k3/pp3ppp/8/8/8/3N4/PP2PPPP/4K3 w - - 0 1"), PieceColor::White), 0.0);
+
+        // Central outposts are worth more than outposts on the edge, and black outposts are found the same way
+        let edge_board = read_fen("4k3/5ppp/8/N7/1P6/8/P4PPP/4K3 w - - 0 1");
+        assert!(outpost_bonus(&edge_board, PieceColor::White) > 0.0);
+        assert!(outpost_bonus(&outpost_board, PieceColor::White) > outpost_bonus(&edge_board, PieceColor::White));
+        assert_eq!(outpost_bonus(&outpost_board.mirror(), PieceColor::Black), outpost_bonus(&outpost_board, PieceColor::White));
+    }
+
+    #[test]
+    fn test_promoting_pawns() {
+
+        // White pawn on e7 against the same pawn on e6, with black to move
+        let seventh_board = read_fen("7k/4P3/8/8/8/8/8/K7 b - - 0 1");
+        let sixth_board = read_fen("7k/8/4P3/8/8/8/8/K7 b - - 0 1");
+        assert!(promoting_pawn_bonus(&seventh_board, PieceColor::White) > PROMOTING_PAWN_BONUS * (ENDGAME_PROMOTING_PAWN_FACTOR - 0.1));
+        assert_eq!(promoting_pawn_bonus(&sixth_board, PieceColor::White), 0.0);
+
+        // The bonus adds much more than the square tables already give for the extra row
+        let weights = EvalWeights { promoting_pawns: 0.3, material: 0.4, ..Default::default() };
+        let table_weights = EvalWeights { promoting_pawns: 0.0, ..weights.clone() };
+        let eval_difference = |weights: &EvalWeights| eval_weighted(0.0, &sixth_board, weights) - eval_weighted(0.0, &seventh_board, weights);
+        assert!(eval_difference(&weights) > 0.0);
+        assert!(eval_difference(&weights) > 2.0 * eval_difference(&table_weights));
+
+        // A blocked pawn doesn't count, and black pawns are found the same way
+        assert_eq!(promoting_pawn_bonus(&read_fen("4k3/4P3/8/8/8/8/8/K7 b - - 0 1"), PieceColor::White), 0.0);
+        assert_eq!(promoting_pawn_bonus(&seventh_board.mirror(), PieceColor::Black), promoting_pawn_bonus(&seventh_board, PieceColor::White));
+
+        // Smaller in the midgame
+        let midgame_board = read_fen("r1bqkbnr/pPpppppp/8/8/8/8/P1PPPPPP/RNBQKBNR w KQkq - 0 1");
+        let midgame_bonus = promoting_pawn_bonus(&midgame_board, PieceColor::White);
+        assert!((PROMOTING_PAWN_BONUS..PROMOTING_PAWN_BONUS * 1.5).contains(&midgame_bonus));
+    }
+
+    #[test]
+    fn test_mop_up_value() {
+
+        // Black king being driven from the centre to the corner by a white king and queen
+        let boards = [
+            read_fen("8/8/8/3k4/8/4K3/8/Q7 w - - 0 1"),
+            read_fen("8/5k2/8/8/8/4K3/8/Q7 w - - 0 1"),
+            read_fen("7k/8/8/8/8/4K3/8/Q7 w - - 0 1"),
+        ];
+
+        for i in 1..boards.len() {
+            assert!(eval(9.0, &boards[i]) > eval(9.0, &boards[i - 1]));
+        }
 
-    material_value * MATERIAL_WEIGHT + square_table_value * SQUARE_TABLE_WEIGHT
-}
\ No newline at end of file
+        // Not used when the material is even
+        assert_eq!(mop_up_value(&read_fen("8/8/8/3k4/8/4K3/8/8 w - - 0 1")), None);
+    }
+}
@@ -0,0 +1,285 @@
+// This file is for generating castling moves and keeping castling availability up to date
+
+use crate::board_representation;
+use crate::board_representation::{Board, PieceColor, CastlingAvailability};
+use crate::check_validation;
+use crate::pieces;
+
+// Bits involved in castling to one side of the board
+// See board_representation.rs for the bit layout
+pub struct CastlingBits {
+    pub king_initial_bit: u8,
+    pub king_final_bit: u8,
+    pub rook_initial_bit: u8,
+    pub rook_final_bit: u8,
+
+    // Bits that have to be empty for the castle to be made, ignoring the king and rook
+    pub empty_bitboard: u64,
+
+    // The final bit of the castling move
+    // In standard chess this is the kings final bit, e.g. e1g1
+    // In Chess960 the king moves onto the rook, so castling can't be confused with a normal king move
+    pub target_bit: u8,
+}
+
+// Columns the king and rook end up on after castling, the same as in standard chess
+const KINGSIDE_FINAL_COLUMNS: (u8, u8) = (1, 2);
+const QUEENSIDE_FINAL_COLUMNS: (u8, u8) = (5, 4);
+
+// Columns the king and rooks start on in standard chess
+pub const STANDARD_KING_COLUMN: u8 = 3;
+pub const STANDARD_KINGSIDE_ROOK_COLUMN: u8 = 0;
+pub const STANDARD_QUEENSIDE_ROOK_COLUMN: u8 = 7;
+
+// Returns the row a team castles on
+pub fn back_row(king_color: PieceColor) -> u8 {
+    match king_color {
+        PieceColor::Black => 0,
+        PieceColor::White => 7,
+    }
+}
+
+// Returns the castling bits for castling to one side of the board
+// None is returned if castling to that side isn't available,
+// or the king and rook aren't positioned so that castling to that side is possible
+pub fn get_castling_bits(board: &Board, king_color: PieceColor, kingside: bool) -> Option<CastlingBits> {
+    let castling_availability = &board.castling_availability;
+    let (rook_column, king_bit) = match (king_color, kingside) {
+        (PieceColor::Black, true) => (castling_availability.b_ks?, board.black_king_bit),
+        (PieceColor::Black, false) => (castling_availability.b_qs?, board.black_king_bit),
+        (PieceColor::White, true) => (castling_availability.w_ks?, board.white_king_bit),
+        (PieceColor::White, false) => (castling_availability.w_qs?, board.white_king_bit),
+    };
+
+    let (king_final_column, rook_final_column) = if kingside {
+        KINGSIDE_FINAL_COLUMNS
+    } else {
+        QUEENSIDE_FINAL_COLUMNS
+    };
+
+    let row = back_row(king_color);
+    let king_column = king_bit % 8;
+    if king_bit / 8 != row || (kingside && rook_column >= king_column) || (!kingside && rook_column <= king_column) {
+        return None;
+    }
+
+    let king_initial_bit = king_bit;
+    let king_final_bit = row * 8 + king_final_column;
+    let rook_initial_bit = row * 8 + rook_column;
+    let rook_final_bit = row * 8 + rook_final_column;
+
+    let empty_bitboard = (bits_spanned(king_initial_bit, king_final_bit) | bits_spanned(rook_initial_bit, rook_final_bit))
+        & !(1 << king_initial_bit | 1 << rook_initial_bit);
+
+    let standard_rook_column = if kingside {
+        STANDARD_KINGSIDE_ROOK_COLUMN
+    } else {
+        STANDARD_QUEENSIDE_ROOK_COLUMN
+    };
+
+    let target_bit = if king_column == STANDARD_KING_COLUMN && rook_column == standard_rook_column {
+        king_final_bit
+    } else {
+        rook_initial_bit
+    };
+
+    Some(CastlingBits {
+        king_initial_bit,
+        king_final_bit,
+        rook_initial_bit,
+        rook_final_bit,
+        empty_bitboard,
+        target_bit,
+    })
+}
+
+// Returns a bitboard of the bits from bit_a to bit_b inclusive, the bits are assumed to be on the same row
+fn bits_spanned(bit_a: u8, bit_b: u8) -> u64 {
+    let (low, high) = (bit_a.min(bit_b), bit_a.max(bit_b));
+    (u64::MAX >> (63 - high)) & (u64::MAX << low)
+}
+
+// Returns a bitboard containing the target bits of the castling moves the king can make
+//
+// This isn't part of generate_moves because generate_moves is used for check validation,
+// and castling needs check validation itself
+pub fn get_castling_moves(board: &Board, king_color: PieceColor) -> u64 {
+    let mut output_move_bitboard = 0;
+    for kingside in [true, false] {
+        let castling_bits = match get_castling_bits(board, king_color, kingside) {
+            Some(castling_bits) => castling_bits,
+            None => continue,
+        };
+
+        if !castling_unobstructed(board, king_color, &castling_bits) || king_path_attacked(board, king_color, &castling_bits) {
+            continue;
+        }
+
+        output_move_bitboard |= 1 << castling_bits.target_bit;
+    }
+
+    output_move_bitboard
+}
+
+// Returns true if the castling rook is in place, and all bits the king and rook move through are empty
+// Whether the king is attacked isn't checked
+pub fn castling_unobstructed(board: &Board, king_color: PieceColor, castling_bits: &CastlingBits) -> bool {
+    let friendly_board = match king_color {
+        PieceColor::Black => &board.black_board,
+        PieceColor::White => &board.white_board,
+    };
+
+    let occupied_bitboard = board.occupied();
+
+    board_representation::read_piece_id(friendly_board, castling_bits.rook_initial_bit) == pieces::ROOK_ID
+        && occupied_bitboard & castling_bits.empty_bitboard == 0
+}
+
+// Returns true if the king is attacked on the bit it starts on, passes through, or ends on
+// Only the kings path is checked, so on the queenside the bit next to the rook is allowed to be attacked
+fn king_path_attacked(board: &Board, king_color: PieceColor, castling_bits: &CastlingBits) -> bool {
+    let step: i8 = if castling_bits.king_final_bit > castling_bits.king_initial_bit {
+        1
+    } else {
+        -1
+    };
+
+    // The castling rook is removed, in Chess960 it could otherwise block an attack on the kings path
+    let mut path_board = board.clone();
+    match king_color {
+        PieceColor::Black => board_representation::remove_piece(castling_bits.rook_initial_bit, &mut path_board.black_board),
+        PieceColor::White => board_representation::remove_piece(castling_bits.rook_initial_bit, &mut path_board.white_board),
+    }
+
+    let mut path_bit = castling_bits.king_initial_bit as i8;
+    loop {
+
+        // Put the king on the bit in the path being checked
+        move_king(&mut path_board, king_color, path_bit as u8);
+
+        let potential_checking_pieces = check_validation::get_potential_checking_pieces(&path_board, king_color);
+        if check_validation::is_king_in_check(&path_board, king_color, &potential_checking_pieces) {
+            return true;
+        }
+
+        if path_bit == castling_bits.king_final_bit as i8 {
+            return false;
+        }
+
+        path_bit += step;
+    }
+}
+
+// Moves the king to a new bit, without any validation
+fn move_king(board: &mut Board, king_color: PieceColor, final_bit: u8) {
+    let (friendly_board, king_bit) = match king_color {
+        PieceColor::Black => (&mut board.black_board, &mut board.black_king_bit),
+        PieceColor::White => (&mut board.white_board, &mut board.white_king_bit),
+    };
+
+    board_representation::remove_piece(*king_bit, friendly_board);
+    board_representation::insert_piece(final_bit, pieces::KING_ID, friendly_board);
+    *king_bit = final_bit;
+}
+
+// Returns the castling bits if the king of the team to move, moving from initial_bit to final_bit is castling
+pub fn get_castling_move(board: &Board, initial_bit: u8, final_bit: u8) -> Option<CastlingBits> {
+    [true, false].into_iter()
+        .filter_map(|kingside| get_castling_bits(board, board.piece_to_move, kingside))
+        .find(|castling_bits| castling_bits.king_initial_bit == initial_bit && castling_bits.target_bit == final_bit)
+}
+
+// Removes castling availability when a move is made from a kings bit, or from or to a castling rooks starting bit
+// Moving to a starting bit covers rooks being captured
+// The board is the board before the move is made
+pub fn update_castling_availability(castling_availability: &mut CastlingAvailability, board: &Board, initial_bit: u8, final_bit: u8) {
+    let white_row = back_row(PieceColor::White);
+    let black_row = back_row(PieceColor::Black);
+
+    let castling_sides = [
+        (&mut castling_availability.w_ks, board.white_king_bit, white_row),
+        (&mut castling_availability.w_qs, board.white_king_bit, white_row),
+        (&mut castling_availability.b_ks, board.black_king_bit, black_row),
+        (&mut castling_availability.b_qs, board.black_king_bit, black_row),
+    ];
+
+    for (rook_column, king_bit, row) in castling_sides {
+        if let Some(column) = *rook_column {
+            let rook_bit = row * 8 + column;
+
+            if initial_bit == king_bit || initial_bit == rook_bit || final_bit == rook_bit {
+                *rook_column = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board_representation::fen::read_fen;
+
+    #[test]
+    fn test_get_castling_moves() {
+
+        // Test both sides being available
+        let board = read_fen("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1");
+        assert_eq!(get_castling_moves(&board, PieceColor::White), 1 << 57 | 1 << 61);
+
+        // Test a rook attacking the kings transit bit (f1) blocking kingside castling
+        let board = read_fen("4kr2/8/8/8/8/8/8/R3K2R w KQ - 0 1");
+        assert_eq!(get_castling_moves(&board, PieceColor::White), 1 << 61);
+
+        // Test a rook attacking the kings destination bit (c8) blocking queenside castling
+        let board = read_fen("r3k2r/8/8/8/8/8/8/2R1K3 b kq - 0 1");
+        assert_eq!(get_castling_moves(&board, PieceColor::Black), 1 << 1);
+
+        // Test a rook attacking only b1 not blocking queenside castling
+        let board = read_fen("1r2k3/8/8/8/8/8/8/R3K2R w KQ - 0 1");
+        assert_eq!(get_castling_moves(&board, PieceColor::White), 1 << 57 | 1 << 61);
+
+        // Test castling out of check
+        let board = read_fen("4r1k1/8/8/8/8/8/8/R3K2R w KQ - 0 1");
+        assert_eq!(get_castling_moves(&board, PieceColor::White), 0);
+
+        // Test a piece between the king and rook
+        let board = read_fen("4k3/8/8/8/8/8/8/RN2K2R w KQ - 0 1");
+        assert_eq!(get_castling_moves(&board, PieceColor::White), 1 << 57);
+    }
+
+    #[test]
+    fn test_chess960_castling() {
+
+        // King on b1 with rooks on a1 and h1, castling kingside moves the h1 rook to f1 and the king to g1
+        let board = read_fen("1k6/8/8/8/8/8/8/RK5R w AHah - 0 1");
+        assert_eq!(board.castling_availability.w_ks, Some(0));
+        assert_eq!(board.castling_availability.w_qs, Some(7));
+
+        // In Chess960 the castling move targets the rooks bit
+        assert_eq!(get_castling_moves(&board, PieceColor::White), 1 << 56 | 1 << 63);
+
+        let castling_bits = get_castling_move(&board, 62, 56).unwrap();
+        assert_eq!((castling_bits.king_final_bit, castling_bits.rook_final_bit), (57, 58));
+
+        // Standard castling still targets the kings final bit
+        let board = read_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        assert!(get_castling_move(&board, 59, 56).is_none());
+        assert_eq!(get_castling_move(&board, 59, 57).unwrap().rook_initial_bit, 56);
+    }
+
+    #[test]
+    fn test_update_castling_availability() {
+        let board = read_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        let mut castling_availability = board.castling_availability.clone();
+
+        // White rook captures black queenside rook
+        update_castling_availability(&mut castling_availability, &board, 63, 7);
+
+        assert_eq!(castling_availability, CastlingAvailability {
+            w_ks: Some(0),
+            w_qs: None,
+            b_ks: Some(0),
+            b_qs: None,
+        });
+    }
+}
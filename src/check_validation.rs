@@ -6,62 +6,117 @@ use crate::board_representation::{Board, PieceColor, PerspectiveBoards};
 use crate::direction_bitboards::ALL_CAPTURE_BITBOARDS;
 use crate::bitboard_manipulation;
 use crate::move_generation::generate_moves;
+use crate::pieces;
 
-const FIXED_VECTOR_PLACEHOLDER_VALUE: u8 = 255;
+pub const FIXED_VECTOR_PLACEHOLDER_VALUE: u8 = 255;
 pub const MAX_CHECKING_PIECES: usize = 16; // Maximum number of pieces that can potentially be putting the king in check
 
+// Returns the bit of a teams king, or None if the team has no king
+// Boards built by hand (e.g. puzzle editors or tests) can leave out the kings, in which case the king bit is just left at it's default
+pub fn get_king_bit(board: &Board, king_color: PieceColor) -> Option<u8> {
+    let (team_board, king_bit) = match king_color {
+        PieceColor::Black => (&board.black_board, board.black_king_bit),
+        PieceColor::White => (&board.white_board, board.white_king_bit),
+    };
+
+    if board_representation::read_piece_id(team_board, king_bit) == pieces::KING_ID {
+        Some(king_bit)
+    } else {
+        None
+    }
+}
+
 // Returns a vector of pieces which could potentially be putting the king in check
+// The vector is empty if there is no king
 pub fn get_potential_checking_pieces(board: &Board, king_color: PieceColor) -> FixedVector<u8, MAX_CHECKING_PIECES> {
-    let mut potential_checking_pieces_bitboard: u64 = 0;
+    match get_king_bit(board, king_color) {
+        Some(king_bit) => get_potential_attackers(board, king_bit, king_color.opposite()),
+        None => FixedVector::new(FIXED_VECTOR_PLACEHOLDER_VALUE),
+    }
+}
+
+// Returns a vector of pieces belonging to the attacking team which could potentially be attacking a bit
+// These are all pieces on lines, or a knights move, away from the bit
+pub fn get_potential_attackers(board: &Board, bit: u8, attacking_color: PieceColor) -> FixedVector<u8, MAX_CHECKING_PIECES> {
+    let mut potential_attackers_bitboard: u64 = 0;
 
-    let (enemy_board, king_bit) = match king_color {
-        PieceColor::Black => (&board.white_board, board.black_king_bit),
-        PieceColor::White => (&board.black_board, board.white_king_bit),
+    let attacking_board = match attacking_color {
+        PieceColor::Black => &board.black_board,
+        PieceColor::White => &board.white_board,
     };
 
-    let enemy_bitboard = enemy_board[0] | enemy_board[1] | enemy_board[2];
+    let attacking_bitboard = board_representation::team_occupancy(attacking_board);
 
-    let king_coordinates = bitboard_manipulation::get_piece_coordinates(king_bit);
+    let coordinates = bitboard_manipulation::get_piece_coordinates(bit);
 
     for direction_bitboard in ALL_CAPTURE_BITBOARDS {
 
-        // Update direction bitboard so it is centered on the king
-        let direction_bitboard = bitboard_manipulation::shift_direction_bitboard(king_bit, king_coordinates, direction_bitboard);
+        // Update direction bitboard so it is centered on the bit
+        let direction_bitboard = bitboard_manipulation::shift_direction_bitboard(bit, coordinates, direction_bitboard);
 
-        // Any collisions are pieces which could be putting the king in check
-        potential_checking_pieces_bitboard |= direction_bitboard & enemy_bitboard
+        // Any collisions are pieces which could be attacking the bit
+        potential_attackers_bitboard |= direction_bitboard & attacking_bitboard
     }
 
+    // A piece on the bit itself can't be attacking it
+    potential_attackers_bitboard &= !(1 << bit);
     
-    //crate::bitboard_manipulation::debugging::print_bytes(potential_checking_pieces_bitboard);
-    
-    bitboard_manipulation::bits_on(potential_checking_pieces_bitboard, FIXED_VECTOR_PLACEHOLDER_VALUE)
+    bitboard_manipulation::bits_on(potential_attackers_bitboard, FIXED_VECTOR_PLACEHOLDER_VALUE)
 }
 
 // Returns true if the king is in check
+// A team without a king is never in check
 pub fn is_king_in_check(
     board: &Board,
     king_color: PieceColor,
     potential_checking_pieces: &FixedVector<u8, MAX_CHECKING_PIECES>
 ) -> bool {
 
-    let (enemy_color, king_bit) = match king_color {
-        PieceColor::Black => (PieceColor::White, board.black_king_bit),
-        PieceColor::White => (PieceColor::Black, board.white_king_bit),
+    let king_bit = match get_king_bit(board, king_color) {
+        Some(king_bit) => king_bit,
+        None => return false,
     };
-    
-    // Go through all pieces which could be putting the king in check and generate their moves
-    // Use the moves to see if the pieces can capture the king
-    // If any of the potential pieces can capture the king then the king is in check
-    for i in 0..potential_checking_pieces.len() {
-        let potential_checking_piece_bit = potential_checking_pieces.internal_array[i];
-
-        if potential_checking_piece_bit != FIXED_VECTOR_PLACEHOLDER_VALUE {
-            let enemy_persepective_boards = PerspectiveBoards::gen(board, enemy_color);
-            let enemy_piece_id = board_representation::read_piece_id(enemy_persepective_boards.friendly_board, potential_checking_piece_bit);
-            let enemy_piece_moves = generate_moves(board, potential_checking_piece_bit, enemy_piece_id, enemy_color, &enemy_persepective_boards).0;
-
-            if bitboard_manipulation::bit_on(enemy_piece_moves, king_bit) {
+
+    is_attacked_by_potential_attackers(board, king_bit, king_color.opposite(), potential_checking_pieces)
+}
+
+impl Board {
+
+    // Returns true if the team to move is in check
+    pub fn in_check(&self) -> bool {
+        let potential_checking_pieces = get_potential_checking_pieces(self, self.piece_to_move);
+        is_king_in_check(self, self.piece_to_move, &potential_checking_pieces)
+    }
+}
+
+// Returns true if any piece belonging to the attacking team could capture a piece on the bit
+// Whether the bit is empty, or has a piece of either team on it, doesn't matter
+pub fn is_square_attacked(board: &Board, bit: u8, attacking_color: PieceColor) -> bool {
+    let potential_attackers = get_potential_attackers(board, bit, attacking_color);
+    is_attacked_by_potential_attackers(board, bit, attacking_color, &potential_attackers)
+}
+
+// Goes through all pieces which could be attacking the bit and generates their moves
+// If any of the potential attackers can move to the bit then it is attacked
+fn is_attacked_by_potential_attackers(
+    board: &Board,
+    bit: u8,
+    attacking_color: PieceColor,
+    potential_attackers: &FixedVector<u8, MAX_CHECKING_PIECES>
+) -> bool {
+
+    let placeholder_board = placeholder_target_board(board, bit, attacking_color);
+    let board = placeholder_board.as_ref().unwrap_or(board);
+
+    let attacker_persepective_boards = PerspectiveBoards::gen(board, attacking_color);
+    for i in 0..potential_attackers.len() {
+        let potential_attacker_bit = potential_attackers.internal_array[i];
+
+        if potential_attacker_bit != FIXED_VECTOR_PLACEHOLDER_VALUE {
+            let attacker_piece_id = board_representation::read_piece_id(attacker_persepective_boards.friendly_board, potential_attacker_bit);
+            let attacker_moves = generate_moves(board, potential_attacker_bit, attacker_piece_id, attacking_color, &attacker_persepective_boards).0;
+
+            if bitboard_manipulation::bit_on(attacker_moves, bit) {
                 return true;
             }
         }
@@ -70,6 +125,119 @@ pub fn is_king_in_check(
     false
 }
 
+// Returns the bits of every piece belonging to the attacking team which attacks a bit
+// Like is_square_attacked, pieces of the attacking team on the bit itself aren't counted, so this also works for counting defenders
+pub fn attackers_to(board: &Board, bit: u8, attacking_color: PieceColor) -> FixedVector<u8, MAX_CHECKING_PIECES> {
+    let potential_attackers = get_potential_attackers(board, bit, attacking_color);
+
+    let placeholder_board = placeholder_target_board(board, bit, attacking_color);
+    let board = placeholder_board.as_ref().unwrap_or(board);
+
+    let mut attackers = FixedVector::new(FIXED_VECTOR_PLACEHOLDER_VALUE);
+    let attacker_persepective_boards = PerspectiveBoards::gen(board, attacking_color);
+    for i in 0..potential_attackers.len() {
+        let potential_attacker_bit = potential_attackers.internal_array[i];
+        let attacker_piece_id = board_representation::read_piece_id(attacker_persepective_boards.friendly_board, potential_attacker_bit);
+        let attacker_moves = generate_moves(board, potential_attacker_bit, attacker_piece_id, attacking_color, &attacker_persepective_boards).0;
+
+        if bitboard_manipulation::bit_on(attacker_moves, bit) {
+            attackers.push(potential_attacker_bit);
+        }
+    }
+
+    attackers
+}
+
+// Pieces can only move to a bit by capturing if there is an enemy piece on it
+// (pawns can't capture empty bits, and pawn pushes aren't attacks)
+// So this returns a board with a placeholder enemy piece on the bit, or None if there is one already
+fn placeholder_target_board(board: &Board, bit: u8, attacking_color: PieceColor) -> Option<Board> {
+    let defending_board = match attacking_color {
+        PieceColor::Black => &board.white_board,
+        PieceColor::White => &board.black_board,
+    };
+
+    if board_representation::read_piece_id(defending_board, bit) != 0 {
+        return None;
+    }
+
+    let mut new_board = board.clone();
+    let (attacking_board, defending_board) = match attacking_color {
+        PieceColor::Black => (&mut new_board.black_board, &mut new_board.white_board),
+        PieceColor::White => (&mut new_board.white_board, &mut new_board.black_board),
+    };
+
+    board_representation::remove_piece(bit, attacking_board);
+    board_representation::insert_piece(bit, pieces::PAWN_ID, defending_board);
+
+    Some(new_board)
+}
+
+// Returns a bitboard of the pieces belonging to a team which are pinned to their king
+pub fn pinned_pieces(board: &Board, color: PieceColor) -> u64 {
+    let mut pinned_bitboard = 0;
+
+    for (pinned_bit, _) in get_pins(board, color) {
+        pinned_bitboard |= 1 << pinned_bit;
+    }
+
+    pinned_bitboard
+}
+
+// Returns the bits a pinned piece can move to without exposing it's king
+// This is the ray between the king and the pinning piece, including the pinning piece
+// Returns None if the piece isn't pinned
+pub fn get_pin_ray(board: &Board, color: PieceColor, piece_bit: u8) -> Option<u64> {
+    get_pins(board, color).into_iter()
+        .find(|&(pinned_bit, _)| pinned_bit == piece_bit)
+        .map(|(_, pin_ray)| pin_ray)
+}
+
+// Returns the bit of every pinned piece belonging to a team, along with it's pin ray
+fn get_pins(board: &Board, color: PieceColor) -> Vec<(u8, u64)> {
+    let perspective_boards = PerspectiveBoards::gen(board, color);
+    let (friendly_bitboard, enemy_bitboard) = perspective_boards.gen_bitboards();
+
+    // Nothing can be pinned without a king
+    let king_bit = match get_king_bit(board, color) {
+        Some(king_bit) => king_bit,
+        None => return Vec::new(),
+    };
+    let king_coordinates = bitboard_manipulation::get_piece_coordinates(king_bit);
+
+    let mut pins = Vec::new();
+    for enemy_bit in 0..64 {
+        let enemy_piece_id = board_representation::read_piece_id(perspective_boards.enemy_board, enemy_bit);
+
+        // Only sliding pieces can pin
+        if ![pieces::BISHOP_ID, pieces::ROOK_ID, pieces::QUEEN_ID].contains(&enemy_piece_id) {
+            continue;
+        }
+
+        let between_bitboard = match bitboard_manipulation::bits_between(king_bit, enemy_bit) {
+            Some(between_bitboard) => between_bitboard,
+            None => continue,
+        };
+
+        // Check the piece can slide in the direction of the king
+        let enemy_coordinates = bitboard_manipulation::get_piece_coordinates(enemy_bit);
+        let is_diagonal = king_coordinates.0 != enemy_coordinates.0 && king_coordinates.1 != enemy_coordinates.1;
+        let can_slide = match enemy_piece_id {
+            pieces::BISHOP_ID => is_diagonal,
+            pieces::ROOK_ID => !is_diagonal,
+            _ => true,
+        };
+
+        // The piece is pinned if it is the only piece between the king and the enemy piece
+        let blocking_bitboard = between_bitboard & (friendly_bitboard | enemy_bitboard);
+        if can_slide && bitboard_manipulation::count_bits(blocking_bitboard) == 1 && blocking_bitboard & friendly_bitboard != 0 {
+            pins.push((blocking_bitboard.trailing_zeros() as u8, between_bitboard | 1 << enemy_bit));
+        }
+    }
+
+    pins
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -108,4 +276,92 @@ mod tests {
         assert_eq!(result, true);
         
     }
+
+    #[test]
+    fn test_attackers_to() {
+        let attackers = |board: &Board, bit: u8, color: PieceColor| {
+            let attackers = attackers_to(board, bit, color);
+            let mut attackers = attackers.internal_array[0..attackers.len()].to_vec();
+            attackers.sort();
+            attackers
+        };
+
+        // d5 is attacked by the e4 pawn, c3 knight, and c4 bishop, and defended by the f6 knight
+        // The d7 pawn and d8 queen don't count, pawn pushes aren't attacks and the queen is blocked
+        let board = read_fen("r1bqk2r/pppp1ppp/2n2n2/2b1p3/2B1P3/2N2N2/PPPP1PPP/R1BQK2R w KQkq - 0 1");
+        assert_eq!(attackers(&board, 28, PieceColor::White), vec![35, 37, 45]);
+        assert_eq!(attackers(&board, 28, PieceColor::Black), vec![18]);
+
+        // The e5 pawn is attacked by the f3 knight and defended by the c6 knight
+        assert_eq!(attackers(&board, 27, PieceColor::White), vec![42]);
+        assert_eq!(attackers(&board, 27, PieceColor::Black), vec![21]);
+    }
+
+    #[test]
+    fn test_missing_king() {
+
+        // No white king, so the rook attacking bit 0 (h8) isn't a check, and nothing is pinned
+        let board = read_fen("R3b3/8/8/8/3N4/8/8/4k3 w - - 0 1");
+        assert_eq!(get_king_bit(&board, PieceColor::White), None);
+        assert_eq!(get_king_bit(&board, PieceColor::Black), Some(59));
+        assert!(!board.in_check());
+        assert_eq!(get_potential_checking_pieces(&board, PieceColor::White).len(), 0);
+        assert_eq!(pinned_pieces(&board, PieceColor::White), 0);
+
+        // Moves are still generated for the remaining pieces
+        let perspective_boards = PerspectiveBoards::gen(&board, PieceColor::White);
+        assert_ne!(generate_moves(&board, 36, pieces::KNIGHT_ID, PieceColor::White, &perspective_boards).0, 0);
+        assert!(!crate::legal_moves::legal_moves(&board).is_empty());
+
+        // The same with no kings at all
+        let board = read_fen("7r/8/8/8/8/8/8/7R w - - 0 1");
+        assert!(!board.in_check());
+        assert_eq!(crate::legal_moves::legal_moves(&board).len(), 14);
+    }
+
+    #[test]
+    fn test_pinned_pieces() {
+
+        // Knight on d2 pinned by a bishop, and rook on e2 pinned by a rook
+        // The queen on a5 is behind the bishop on b4, so it doesn't pin anything
+        let board = read_fen("4r2k/8/8/q7/1b6/8/PB1NR3/4K3 w - - 0 1");
+
+        assert_eq!(pinned_pieces(&board, PieceColor::White), 1 << 52 | 1 << 51);
+        assert_eq!(get_pin_ray(&board, PieceColor::White, 52), Some(1 << 52 | 1 << 45 | 1 << 38));
+        assert_eq!(get_pin_ray(&board, PieceColor::White, 51), Some(1 << 51 | 1 << 43 | 1 << 35 | 1 << 27 | 1 << 19 | 1 << 11 | 1 << 3));
+        assert_eq!(get_pin_ray(&board, PieceColor::White, 54), None);
+
+        // A rook can't pin along a diagonal
+        let board = read_fen("7k/8/8/8/1r6/8/3N4/4K3 w - - 0 1");
+        assert_eq!(pinned_pieces(&board, PieceColor::White), 0);
+    }
+
+    #[test]
+    fn test_is_square_attacked() {
+
+        // White pawn on e4, black pawn on d6
+        let board = read_fen("4k3/8/3p4/8/4P3/8/8/4K3 w - - 0 1");
+
+        // Pawns only attack diagonally forwards
+        assert!(is_square_attacked(&board, 28, PieceColor::White)); // d5
+        assert!(is_square_attacked(&board, 26, PieceColor::White)); // f5
+        assert!(!is_square_attacked(&board, 27, PieceColor::White)); // e5 (pawn push)
+        assert!(!is_square_attacked(&board, 44, PieceColor::White)); // d3 (behind)
+        assert!(is_square_attacked(&board, 27, PieceColor::Black)); // e5
+        assert!(!is_square_attacked(&board, 28, PieceColor::Black)); // d5 (pawn push)
+        assert!(!is_square_attacked(&board, 35, PieceColor::Black)); // e4 (occupied by white)
+
+        // Rook on a1 attacking along the first row up to and including the king
+        let board = read_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1");
+        assert!(is_square_attacked(&board, 60, PieceColor::White)); // d1
+        assert!(is_square_attacked(&board, 59, PieceColor::White)); // e1 (defended king)
+        assert!(!is_square_attacked(&board, 58, PieceColor::Black)); // f1
+        assert!(is_square_attacked(&board, 7, PieceColor::White)); // a8
+
+        // Knight on f3
+        let board = read_fen("4k3/8/8/8/8/5N2/8/4K3 w - - 0 1");
+        assert!(is_square_attacked(&board, 27, PieceColor::White)); // e5
+        assert!(is_square_attacked(&board, 48, PieceColor::White)); // h2
+        assert!(!is_square_attacked(&board, 34, PieceColor::White)); // f4
+    }
 }
\ No newline at end of file
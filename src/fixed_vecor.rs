@@ -24,6 +24,11 @@ impl<T: Copy, const L: usize> FixedVector<T, L> {
         self.length += 1
     }
 
+    // The old values are left in internal_array, and are overwritten by the next pushes
+    pub fn clear(&mut self) {
+        self.length = 0;
+    }
+
     pub fn pop(&mut self) -> Option<T> {
         if self.length > 0 {
             self.length -= 1;
@@ -0,0 +1,567 @@
+// A board along with the moves made to reach it
+// Board is kept as a pure position, anything that needs the history of a game (undoing moves, repetitions) goes here
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::board_representation::{Board, PieceColor, PerspectiveBoards};
+use crate::board_representation;
+use crate::bot::{self, DrawReason, SearchConfig, SearchResult};
+use crate::bot_eval::EvalWeights;
+use crate::generic_math;
+use crate::bitboard_manipulation;
+use crate::check_validation;
+use crate::legal_moves;
+use crate::packed_move::Move;
+use crate::pieces;
+use crate::turn::{self, TurnError, TurnMove};
+use crate::uci::MoveError;
+
+// Number of half moves without a capture or pawn advance which makes a draw
+const FIFTY_MOVE_HALFMOVES: i16 = 100;
+
+// A draw is claimed when the team to move is still at least this much material behind after a short search
+const CLAIM_DRAW_MATERIAL_DEFICIT: f32 = 3.0;
+const CLAIM_DRAW_SEARCH_DEPTH: u8 = 2;
+
+// Number of moves in a row the score has to be below the resign threshold before resigning
+const RESIGN_MOVES: usize = 3;
+
+// Everything needed to undo a move
+#[derive(Debug, PartialEq, Clone)]
+pub struct UndoInfo {
+    pub previous_board: Board,
+    pub played_move: (u8, u8, Option<usize>), // (initial_bit, final_bit, promotion_piece_id)
+}
+
+// A move from a parent board, for trees of positions which don't store a full board at every node
+// The board after the move is worked out from the parent when it's needed
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct BoardDelta {
+    pub played_move: Move,
+
+    // Hash of the board after the move, so transpositions in a tree can be found without rebuilding the board
+    pub position_hash: u64,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct GameState {
+    pub board: Board,
+
+    // Hash of the position before each move, along with the information to undo it
+    // The most recent move is last
+    pub history: Vec<(u64, UndoInfo)>,
+}
+
+// Whether the game is over, and why
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum GameResult {
+    Ongoing,
+    Checkmate(PieceColor), // The team that won
+    Stalemate,
+    FiftyMove,
+    Repetition,
+    InsufficientMaterial,
+}
+
+impl Board {
+
+    // Returns a hash of the position, positions which are the same for repetitions have the same hash
+    // The move clocks aren't part of the position so they are left out (see the Hash impl for Board)
+    pub fn position_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    // Returns true if neither team has enough material to checkmate
+    // This is the case for lone kings, a single bishop or knight, or only bishops which are all on the same colour bits
+    pub fn is_insufficient_material(&self) -> bool {
+        let (mut knights, mut bishops) = (0, 0);
+        let mut bishop_bit_colors = [false; 2];
+
+        for half_board in [&self.white_board, &self.black_board] {
+            let mut occupied_bitboard = board_representation::team_occupancy(half_board);
+
+            while let Some(bit) = bitboard_manipulation::pop_lowest_bit(&mut occupied_bitboard) {
+                match board_representation::read_piece_id(half_board, bit) {
+                    pieces::KING_ID => (),
+                    pieces::KNIGHT_ID => knights += 1,
+                    pieces::BISHOP_ID => {
+                        let (column, row) = bitboard_manipulation::get_piece_coordinates(bit);
+                        bishop_bit_colors[((column + row) % 2) as usize] = true;
+                        bishops += 1;
+                    },
+                    _ => return false,
+                }
+            }
+        }
+
+        knights + bishops <= 1 || (knights == 0 && bishop_bit_colors != [true, true])
+    }
+
+    // Returns why the position is a draw by the rules, if it is
+    // Repetitions need the history of the game, so they are left to GameState::draw_reason
+    pub fn draw_reason(&self) -> Option<DrawReason> {
+        if self.is_insufficient_material() {
+            Some(DrawReason::InsufficientMaterial)
+        } else if self.halfmove_clock >= FIFTY_MOVE_HALFMOVES {
+            Some(DrawReason::FiftyMove)
+        } else {
+            None
+        }
+    }
+}
+
+impl BoardDelta {
+
+    // Makes a delta for a move on the parent board
+    // When validate is set the move is checked with legal_moves::classify_move first, otherwise it is assumed to be legal
+    pub fn new(parent: &Board, initial_bit: u8, final_bit: u8, promotion_piece_id: Option<usize>, validate: bool) -> Result<Self, MoveError> {
+        let played_move = Move::from_board(parent, initial_bit, final_bit, promotion_piece_id);
+        let new_board = apply_move(parent, played_move, validate)?;
+
+        Ok(BoardDelta {
+            played_move,
+            position_hash: new_board.position_hash(),
+        })
+    }
+
+    // Returns the board after the move
+    // When validate is set the move is checked with legal_moves::classify_move, and the result has to match the hash (otherwise the delta is from a different parent)
+    pub fn apply(&self, parent: &Board, validate: bool) -> Result<Board, MoveError> {
+        let new_board = apply_move(parent, self.played_move, validate)?;
+
+        if validate && new_board.position_hash() != self.position_hash {
+            return Err(MoveError::IllegalMove);
+        }
+        Ok(new_board)
+    }
+}
+
+// The move in the history is assumed to be legal, it isn't validated again
+impl TryFrom<&UndoInfo> for BoardDelta {
+    type Error = MoveError;
+
+    fn try_from(undo_info: &UndoInfo) -> Result<Self, Self::Error> {
+        let (initial_bit, final_bit, promotion_piece_id) = undo_info.played_move;
+        BoardDelta::new(&undo_info.previous_board, initial_bit, final_bit, promotion_piece_id, false)
+    }
+}
+
+// Rebuilds a board from a root board and the deltas leading from it, in order
+// Returns the index of the first delta which couldn't be applied, along with the error
+pub fn reconstruct(root: &Board, deltas: &[BoardDelta], validate: bool) -> Result<Board, (usize, MoveError)> {
+    deltas.iter().enumerate().try_fold(root.clone(), |board, (i, delta)| {
+        delta.apply(&board, validate).map_err(|error| (i, error))
+    })
+}
+
+// Makes a packed move with take_turn, using its flags for en-passants instead of generating the moves of the piece
+// Castles are found by take_turn itself, and a promotion without a piece is promoted to a queen
+fn apply_move(board: &Board, played_move: Move, validate: bool) -> Result<Board, MoveError> {
+    let (initial_bit, final_bit, promotion_piece_id) = played_move.to_tuple();
+
+    if validate {
+        legal_moves::classify_move(board, initial_bit, final_bit, promotion_piece_id)?;
+    }
+
+    let perspective_boards = PerspectiveBoards::gen(board, board.piece_to_move);
+    let piece_id = board_representation::read_piece_id(perspective_boards.friendly_board, initial_bit);
+    if piece_id == 0 {
+        return Err(MoveError::NoPiece);
+    }
+
+    // The en-passant target bit is the bit of the pawn which can be captured, and is set to the final bit of a double move
+    let ep_bits = if played_move.is_en_passant() {
+        (None, board.en_passant_target_bit)
+    } else if piece_id == pieces::PAWN_ID && initial_bit.abs_diff(final_bit) == 16 {
+        (Some(final_bit), None)
+    } else {
+        (None, None)
+    };
+
+    let potential_checking_pieces = check_validation::get_potential_checking_pieces(board, board.piece_to_move);
+    match turn::take_turn(board, piece_id, TurnMove::new(initial_bit, final_bit, ep_bits, promotion_piece_id), false, potential_checking_pieces) {
+        Ok((new_board, _)) => Ok(new_board),
+        Err(TurnError::Check) => Err(MoveError::Check),
+        Err(_) => Err(MoveError::IllegalMove),
+    }
+}
+
+impl GameState {
+    pub fn new(board: Board) -> Self {
+        GameState {
+            board,
+            history: Vec::new(),
+        }
+    }
+
+    // Makes a move if it is legal, adding it to the history
+    pub fn push_move(&mut self, initial_bit: u8, final_bit: u8, promotion_piece_id: Option<usize>) -> Result<(), MoveError> {
+        let played_move = (initial_bit, final_bit, promotion_piece_id);
+        let new_board = apply_move(&self.board, Move::from_board(&self.board, initial_bit, final_bit, promotion_piece_id), true)?;
+
+        let previous_board = std::mem::replace(&mut self.board, new_board);
+        self.history.push((previous_board.position_hash(), UndoInfo {
+            previous_board,
+            played_move,
+        }));
+
+        Ok(())
+    }
+
+    // Undoes the last move, returning it
+    pub fn pop_move(&mut self) -> Option<(u8, u8, Option<usize>)> {
+        let (_, undo_info) = self.history.pop()?;
+        self.board = undo_info.previous_board;
+
+        Some(undo_info.played_move)
+    }
+
+    // Returns how many times the current position has occured, including the current occurence
+    // Hashes are compared first since they are stored, and the boards are only compared when the hashes match
+    // Captures and pawn moves reset the halfmove clock and can't be undone,
+    // so only positions since the halfmove clock was last reset are checked
+    pub fn repetition_count(&self) -> usize {
+        let hash = self.board.position_hash();
+        let window = (self.board.halfmove_clock.max(0) as usize).min(self.history.len());
+
+        1 + self.history[self.history.len() - window..].iter()
+            .filter(|(position_hash, undo_info)| *position_hash == hash && board_representation::positions_equal(&undo_info.previous_board, &self.board))
+            .count()
+    }
+
+    // Returns the first board of the game and the deltas from it to the current board
+    // The history already has the hash of the position after each move, so no moves are made
+    pub fn deltas(&self) -> (&Board, Vec<BoardDelta>) {
+        let root = self.history.first().map_or(&self.board, |(_, undo_info)| &undo_info.previous_board);
+        let next_hashes = self.history.iter().skip(1).map(|(position_hash, _)| *position_hash).chain([self.board.position_hash()]);
+
+        let deltas = self.history.iter().zip(next_hashes).map(|((_, undo_info), position_hash)| {
+            let (initial_bit, final_bit, promotion_piece_id) = undo_info.played_move;
+            BoardDelta {
+                played_move: Move::from_board(&undo_info.previous_board, initial_bit, final_bit, promotion_piece_id),
+                position_hash,
+            }
+        }).collect();
+
+        (root, deltas)
+    }
+
+    pub fn is_threefold_repetition(&self) -> bool {
+        self.repetition_count() >= 3
+    }
+
+    // Returns why the current position is a draw by the rules, if it is
+    pub fn draw_reason(&self) -> Option<DrawReason> {
+        self.board.draw_reason().or(self.is_threefold_repetition().then_some(DrawReason::Repetition))
+    }
+}
+
+// Same as bot::gen_best_moves, also using the history of the game to find repetitions
+// for the draw_reason and reaches_draw of each result
+pub fn gen_best_moves_in_game(state: &GameState, n: usize, config: &SearchConfig) -> Vec<SearchResult> {
+    let draw_reason = state.draw_reason();
+    let mut state = state.clone();
+
+    bot::gen_best_moves(&state.board.clone(), n, config).into_iter()
+        .map(|result| {
+            state.push_move(result.initial_bit, result.final_bit, result.promotion_piece_id)
+                .expect("search returned an illegal move");
+            let reaches_draw = result.reaches_draw || state.is_threefold_repetition();
+            state.pop_move();
+
+            SearchResult { draw_reason, reaches_draw, ..result }
+        })
+        .collect()
+}
+
+// Returns whether the game is over, and why
+// Checkmate and stalemate take precedence over the draws, so a checkmate on the fiftieth move still wins
+pub fn game_result(state: &GameState) -> GameResult {
+    let board = &state.board;
+
+    if legal_moves::legal_move_boards(board).next().is_none() {
+        if board.in_check() {
+            return GameResult::Checkmate(board.piece_to_move.opposite());
+        }
+
+        return GameResult::Stalemate;
+    }
+
+    if board.is_insufficient_material() {
+        GameResult::InsufficientMaterial
+    } else if state.is_threefold_repetition() {
+        GameResult::Repetition
+    } else if board.halfmove_clock >= FIFTY_MOVE_HALFMOVES {
+        GameResult::FiftyMove
+    } else {
+        GameResult::Ongoing
+    }
+}
+
+// Returns true if the team to move can claim a draw by repetition or the fifty move rule, and is losing enough that it should
+// A draw is never claimed when the team to move has a checkmate available
+pub fn should_claim_draw(state: &GameState) -> bool {
+    if !matches!(game_result(state), GameResult::Repetition | GameResult::FiftyMove) {
+        return false;
+    }
+
+    let board = &state.board;
+    if legal_moves::legal_move_boards(board).any(|(_, new_board)| new_board.is_checkmate()) {
+        return false;
+    }
+
+    // Only material is evaluated, the square tables make the score too noisy to compare against a fixed threshold
+    let config = SearchConfig {
+        eval_weights: EvalWeights {
+            material: 1.0,
+            square_table: 0.0,
+            tempo: 0.0,
+            mop_up: 0.0,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let max_score = generic_math::f32_scale_clamped(-CLAIM_DRAW_MATERIAL_DEFICIT, -20.0, 20.0);
+    bot::position_score(board, CLAIM_DRAW_SEARCH_DEPTH, &config) <= max_score
+}
+
+// Returns true if the score of a search result is below -threshold, for adjudicating lost games
+// Scores are rescaled so an equal position is 0.0 (like eval_white_perspective), and a checkmate for the team to move never resigns
+pub fn should_resign(result: &SearchResult, threshold: f32) -> bool {
+    if result.score >= bot::CHECKMATE_WEIGHT {
+        return false;
+    }
+
+    result.score * 2.0 - 1.0 < -threshold
+}
+
+// Counts the moves in a row a team has been losing by more than a threshold
+// A test harness updates it with the best search result of each move the team makes, and stops the game once it returns true
+#[derive(Debug, PartialEq, Clone)]
+pub struct ResignCounter {
+    pub threshold: f32,
+    pub losing_moves: usize,
+}
+
+impl ResignCounter {
+    pub fn new(threshold: f32) -> Self {
+        ResignCounter {
+            threshold,
+            losing_moves: 0,
+        }
+    }
+
+    // Returns true once the score has been below the threshold for RESIGN_MOVES moves in a row
+    pub fn update(&mut self, result: &SearchResult) -> bool {
+        if should_resign(result, self.threshold) {
+            self.losing_moves += 1;
+        } else {
+            self.losing_moves = 0;
+        }
+
+        self.losing_moves >= RESIGN_MOVES
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board_representation::fen::read_fen;
+
+    #[test]
+    fn test_threefold_repetition() {
+        let mut game_state = GameState::new(Board::new());
+
+        // Ng1-f3, Ng8-f6, Nf3-g1, Nf6-g8, played twice
+        let knight_shuffle = [(57, 42), (1, 18), (42, 57), (18, 1)];
+        let expected_counts = [1, 1, 1, 2, 2, 2, 2, 3];
+        for (&(initial_bit, final_bit), expected_count) in knight_shuffle.iter().chain(knight_shuffle.iter()).zip(expected_counts) {
+            assert!(!game_state.is_threefold_repetition());
+            assert_eq!(game_state.push_move(initial_bit, final_bit, None), Ok(()));
+            assert_eq!(game_state.repetition_count(), expected_count);
+        }
+
+        assert!(game_state.is_threefold_repetition());
+
+        // Undoing a move brings back the previous board
+        let previous_board = game_state.history.last().unwrap().1.previous_board.clone();
+        assert_eq!(game_state.pop_move(), Some((18, 1, None)));
+        assert!(!game_state.is_threefold_repetition());
+        assert_eq!(game_state.board, previous_board);
+        assert_eq!(game_state.board.position_hash(), Board::new().apply_uci_move("g1f3").unwrap()
+            .apply_uci_move("g8f6").unwrap()
+            .apply_uci_move("f3g1").unwrap()
+            .position_hash());
+
+        // A pawn move resets the repetition window
+        let mut game_state = GameState::new(Board::new());
+        assert_eq!(game_state.push_move(51, 27, None), Err(MoveError::IllegalMove));
+        assert_eq!(game_state.push_move(51, 35, None), Ok(()));
+        assert_eq!(game_state.repetition_count(), 1);
+    }
+
+    #[test]
+    fn test_game_result() {
+        let result = |fen: &str| game_result(&GameState::new(read_fen(fen)));
+
+        assert_eq!(game_result(&GameState::new(Board::new())), GameResult::Ongoing);
+        assert_eq!(result("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1"), GameResult::Checkmate(PieceColor::White));
+        assert_eq!(result("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"), GameResult::Stalemate);
+        assert_eq!(result("4k3/8/8/8/8/8/4P3/4K3 w - - 100 80"), GameResult::FiftyMove);
+
+        // Checkmate takes precedence over the fifty move rule
+        assert_eq!(result("R5k1/5ppp/8/8/8/8/8/6K1 b - - 100 80"), GameResult::Checkmate(PieceColor::White));
+
+        // Lone kings, a single minor piece, and bishops on the same colour bits
+        assert_eq!(result("4k3/8/8/8/8/8/8/4K3 w - - 0 1"), GameResult::InsufficientMaterial);
+        assert_eq!(result("4k3/8/8/8/8/8/8/4KN2 w - - 0 1"), GameResult::InsufficientMaterial);
+        assert_eq!(result("4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1"), GameResult::InsufficientMaterial);
+        assert_eq!(result("4k1b1/8/8/8/8/8/8/2B1K3 w - - 0 1"), GameResult::Ongoing);
+        assert_eq!(result("4kn2/8/8/8/8/8/8/2B1K3 w - - 0 1"), GameResult::Ongoing);
+
+        let mut game_state = GameState::new(Board::new());
+        for _ in 0..2 {
+            for (initial_bit, final_bit) in [(57, 42), (1, 18), (42, 57), (18, 1)] {
+                game_state.push_move(initial_bit, final_bit, None).unwrap();
+            }
+        }
+        assert_eq!(game_result(&game_state), GameResult::Repetition);
+    }
+
+    #[test]
+    fn test_draw_reason() {
+        let config = SearchConfig { max_duration: std::time::Duration::from_millis(500), ..Default::default() };
+
+        // King shuffle played twice so the position repeats three times, the search still gives every move
+        let mut game_state = GameState::new(read_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1"));
+        for _ in 0..2 {
+            for uci_move in ["e1d1", "e8d8", "d1e1", "d8e8"] {
+                let (initial_bit, final_bit, promotion_piece_id) = crate::uci::parse_uci_move(uci_move).unwrap();
+                game_state.push_move(initial_bit, final_bit, promotion_piece_id).unwrap();
+            }
+        }
+
+        let results = gen_best_moves_in_game(&game_state, 100, &config);
+        assert_eq!(results.len(), legal_moves::legal_moves(&game_state.board).len());
+        assert!(results.iter().all(|result| result.draw_reason == Some(DrawReason::Repetition)));
+
+        // Going back a move, Kd8-e8 repeats the position for the third time
+        game_state.pop_move();
+        let results = gen_best_moves_in_game(&game_state, 100, &config);
+        assert!(results.iter().all(|result| result.draw_reason.is_none()));
+        assert!(results.iter().any(|result| result.reaches_draw && (result.initial_bit, result.final_bit) == (4, 3)));
+        assert!(results.iter().any(|result| !result.reaches_draw));
+
+        // The fifty move rule is found from the board alone
+        let board = read_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 100 80");
+        assert_eq!(board.draw_reason(), Some(DrawReason::FiftyMove));
+        assert!(bot::gen_best_moves(&board, 100, &config).iter().all(|result| result.draw_reason == Some(DrawReason::FiftyMove)));
+
+        assert_eq!(Board::new().draw_reason(), None);
+    }
+
+    #[test]
+    fn test_should_resign() {
+        let result = |score: f32| SearchResult {
+            initial_bit: 0,
+            final_bit: 0,
+            promotion_piece_id: None,
+            score,
+            draw_reason: None,
+            reaches_draw: false,
+        };
+
+        assert!(should_resign(&result(0.1), 0.5));
+        assert!(!should_resign(&result(0.4), 0.5));
+        assert!(!should_resign(&result(bot::CHECKMATE_WEIGHT), -10.0));
+
+        // Losing for long enough resigns
+        let mut counter = ResignCounter::new(0.5);
+        assert!(!counter.update(&result(0.1)));
+        assert!(!counter.update(&result(0.05)));
+        assert!(counter.update(&result(0.1)));
+
+        // A score which recovers starts the count again
+        let mut counter = ResignCounter::new(0.5);
+        assert!(!counter.update(&result(0.1)));
+        assert!(!counter.update(&result(0.1)));
+        assert!(!counter.update(&result(0.6)));
+        assert!(!counter.update(&result(0.1)));
+        assert!(!counter.update(&result(0.1)));
+    }
+
+    #[test]
+    fn test_should_claim_draw() {
+
+        // Plays a king (or queen) shuffle twice so the position repeats three times
+        let repeat = |fen: &str, shuffle: [&str; 4]| {
+            let mut game_state = GameState::new(read_fen(fen));
+            for uci_move in shuffle.iter().chain(shuffle.iter()) {
+                let (initial_bit, final_bit, promotion_piece_id) = crate::uci::parse_uci_move(uci_move).unwrap();
+                game_state.push_move(initial_bit, final_bit, promotion_piece_id).unwrap();
+            }
+
+            assert_eq!(game_result(&game_state), GameResult::Repetition);
+            game_state
+        };
+
+        // Black is a rook behind, so should claim the draw, but white shouldn't
+        assert!(should_claim_draw(&repeat("4k3/8/8/8/8/8/8/R3K3 b - - 0 1", ["e8d8", "e1d1", "d8e8", "d1e1"])));
+        assert!(!should_claim_draw(&repeat("3k4/8/8/8/8/8/8/R3K3 w - - 0 1", ["e1d1", "d8e8", "d1e1", "e8d8"])));
+
+        // No draw to claim
+        assert!(!should_claim_draw(&GameState::new(read_fen("4k3/8/8/8/8/8/8/R3K3 b - - 0 1"))));
+
+        // Black is far behind on material, but has a back row checkmate with Ra1
+        assert!(!should_claim_draw(&repeat("r5k1/8/8/8/7Q/7B/5PPP/6K1 b - - 0 1", ["g8f8", "h4h5", "f8g8", "h5h4"])));
+    }
+
+    #[test]
+    fn test_board_deltas() {
+
+        // En-passant and a promotion, then castling
+        let uci_moves = ["e2e4", "d7d5", "e4e5", "f7f5", "e5f6", "b8c6", "f6g7", "c6d4", "g7h8q", "c8e6", "g1f3", "d8d7", "f1e2", "e8c8"];
+        let mut game_state = GameState::new(Board::new());
+        for uci_move in uci_moves {
+            let (initial_bit, final_bit, promotion_piece_id) = crate::uci::parse_uci_move(uci_move).unwrap();
+            game_state.push_move(initial_bit, final_bit, promotion_piece_id).unwrap();
+        }
+
+        // Rebuilding from the deltas gives the same board as applying the moves directly, with or without validating them
+        let (root, deltas) = game_state.deltas();
+        assert_eq!(root, &Board::new());
+        assert_eq!(deltas.len(), uci_moves.len());
+        assert_eq!(reconstruct(root, &deltas, true), Board::from_moves(&uci_moves));
+        assert_eq!(reconstruct(root, &deltas, false), Board::from_moves(&uci_moves));
+        assert_eq!(deltas[uci_moves.len() - 1].position_hash, game_state.board.position_hash());
+        assert_eq!(reconstruct(root, &[], true), Ok(Board::new()));
+
+        // The deltas made from the history match the ones made by applying each move
+        for (delta, (_, undo_info)) in deltas.iter().zip(&game_state.history) {
+            assert_eq!(BoardDelta::try_from(undo_info).as_ref(), Ok(delta));
+        }
+        assert!(deltas[4].played_move.is_en_passant() && deltas[13].played_move.is_castle());
+
+        // Two branches from the same parent
+        let parent = Board::new().apply_uci_move("e2e4").unwrap();
+        let d5 = BoardDelta::new(&parent, 12, 28, None, true).unwrap();
+        let c5 = BoardDelta::new(&parent, 13, 29, None, false).unwrap();
+        assert_eq!(d5.apply(&parent, true), parent.apply_uci_move("d7d5"));
+        assert_eq!(c5.apply(&parent, false), parent.apply_uci_move("c7c5"));
+
+        // Illegal moves, and deltas applied to the wrong parent, are errors when validating
+        assert_eq!(BoardDelta::new(&parent, 52, 36, None, true), Err(MoveError::NoPiece));
+        assert_eq!(BoardDelta::new(&parent, 12, 36, None, true), Err(MoveError::IllegalMove));
+        assert_eq!(reconstruct(&Board::new(), &[d5], true), Err((0, MoveError::NoPiece)));
+        assert_eq!(reconstruct(root, &deltas[1..], true), Err((0, MoveError::NoPiece)));
+
+        // The same pawn move from a different position has the wrong hash
+        let other_parent = Board::new().apply_uci_move("d2d4").unwrap();
+        assert!(d5.apply(&other_parent, false).is_ok());
+        assert_eq!(d5.apply(&other_parent, true), Err(MoveError::IllegalMove));
+    }
+}
@@ -5,6 +5,24 @@ pub fn f32_scale(input: f32, input_min: f32, input_max: f32) -> f32 {
 }
 
 
+// Scales input to a floating point number between 0.0 and 1.0
+// Inputs outside the range are clamped, so the output is always within 0.0 and 1.0
+pub fn f32_scale_clamped(input: f32, input_min: f32, input_max: f32) -> f32 {
+    f32_scale(input, input_min, input_max).clamp(0.0, 1.0)
+}
+
+
+// SplitMix64 pseudo random number generator step
+// Advances state and returns the next number, the same state always gives the same sequence
+pub fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E3779B97F4A7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -15,4 +33,19 @@ mod tests {
         assert_eq!(f32_scale(30.0, -10.0, 30.0), 1.0);
         assert_eq!(f32_scale(39.0, 0.0, 39.0), 1.0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_f32_scale_clamped() {
+        assert_eq!(f32_scale_clamped(10.0, -10.0, 30.0), 0.5);
+        assert_eq!(f32_scale_clamped(50.0, -10.0, 30.0), 1.0);
+        assert_eq!(f32_scale_clamped(-50.0, -10.0, 30.0), 0.0);
+    }
+
+    #[test]
+    fn test_splitmix64() {
+        let (mut a, mut b) = (1, 1);
+        let sequence: Vec<u64> = (0..4).map(|_| splitmix64(&mut a)).collect();
+        assert_eq!(sequence, (0..4).map(|_| splitmix64(&mut b)).collect::<Vec<u64>>());
+        assert_ne!(sequence[0], sequence[1]);
+    }
+}
@@ -0,0 +1,469 @@
+// This file is for generating lists of fully legal moves for the team to move
+// The bot uses it's own move ordering instead, this is for anything that needs to know exactly which moves can be made
+
+use crate::board_representation;
+use crate::board_representation::{Board, PerspectiveBoards, PieceColor};
+use crate::bitboard_manipulation;
+use crate::move_generation;
+use crate::check_validation;
+use crate::castling;
+use crate::fixed_vecor::FixedVector;
+use crate::pieces;
+use crate::turn::{self, TurnMove};
+
+// Reasons a move can be illegal
+#[derive(Debug, PartialEq)]
+pub enum IllegalReason {
+    NoPieceThere,           // The initial bit is empty
+    NotYourPiece,           // The piece on the initial bit belongs to the team not moving
+    NotAPseudoLegalTarget,  // The piece can't move to the final bit
+    LeavesKingInCheck,      // The move would leave the king in check
+    BadPromotion,           // A promotion piece was given for a move that isn't a promotion, or the other way round
+    CastlingThroughCheck,   // The king would castle out of, through, or into check
+}
+
+// Returns every legal move for the team to move
+// Moves are in the format (initial_bit, final_bit, promotion_piece_id)
+// Pawns moving onto the last row have a seperate move for each promotion piece
+pub fn legal_moves(board: &Board) -> Vec<(u8, u8, Option<usize>)> {
+    legal_move_boards(board).map(|(legal_move, _)| legal_move).collect()
+}
+
+// Iterates over every legal move for the team to move, along with the board after the move is made
+// Moves are in the same format as legal_moves
+pub fn legal_move_boards(board: &Board) -> impl Iterator<Item = ((u8, u8, Option<usize>), Board)> + '_ {
+    masked_legal_move_boards(board, u64::MAX, u64::MAX)
+}
+
+// Returns the bits of every piece of a color which can legally move to target_bit
+// Unlike check_validation::attackers_to, pinned pieces are left out and pawn pushes are included
+// If the color isn't the team to move, the moves are found as if it was their turn (without en-passant)
+pub fn movers_to(board: &Board, target_bit: u8, color: PieceColor) -> FixedVector<u8, 16> {
+    let switched_board = (color != board.piece_to_move).then(|| Board {
+        piece_to_move: color,
+        en_passant_target_bit: None,
+        ..board.clone()
+    });
+    let board = switched_board.as_ref().unwrap_or(board);
+
+    // Promotions give several moves from the same bit, so each mover is only added once
+    let mut mover_bitboard: u64 = 0;
+    for ((initial_bit, _, _), _) in masked_legal_move_boards(board, u64::MAX, 1 << target_bit) {
+        mover_bitboard |= 1 << initial_bit;
+    }
+
+    let mut movers = FixedVector::new(check_validation::FIXED_VECTOR_PLACEHOLDER_VALUE);
+    while let Some(bit) = bitboard_manipulation::pop_lowest_bit(&mut mover_bitboard) {
+        movers.push(bit);
+    }
+    movers
+}
+
+// Returns a bitboard of the bits the piece on initial_bit can legally move to, e.g. for highlighting moves in a gui
+// Empty if the bit is empty or the piece doesn't belong to the team to move
+// Castling and en-passant use the same final bits as legal_moves, and each promotion only adds its final bit once
+pub fn legal_moves_from(board: &Board, initial_bit: u8) -> u64 {
+    masked_legal_move_boards(board, 1 << initial_bit, u64::MAX)
+        .fold(0, |final_bitboard, ((_, final_bit, _), _)| final_bitboard | 1 << final_bit)
+}
+
+// Returns true if the team to move has any legal move
+// Moves are generated lazily, so this stops at the first legal move instead of building the whole list
+pub fn has_legal_move(board: &Board) -> bool {
+    legal_move_boards(board).next().is_some()
+}
+
+// Returns the number of legal moves for the team to move, without collecting them
+pub fn legal_move_count(board: &Board) -> usize {
+    legal_move_boards(board).count()
+}
+
+// Same as legal_move_boards, but only for pieces on initial_bitboard moving to bits on final_bitboard
+fn masked_legal_move_boards(board: &Board, initial_bitboard: u64, final_bitboard: u64) -> impl Iterator<Item = ((u8, u8, Option<usize>), Board)> + '_ {
+    let perspective_boards = PerspectiveBoards::gen(board, board.piece_to_move);
+    let pinned_bitboard = check_validation::pinned_pieces(board, board.piece_to_move);
+    let potential_checking_pieces = check_validation::get_potential_checking_pieces(board, board.piece_to_move);
+
+    (0..64).flat_map(move |initial_bit| {
+        let piece_id = if bitboard_manipulation::bit_on(initial_bitboard, initial_bit) {
+            board_representation::read_piece_id(perspective_boards.friendly_board, initial_bit)
+        } else {
+            0
+        };
+
+        let (
+            move_bitboard,
+            en_passant_target_bit,
+            en_passant_cap_bits
+        ) = if piece_id == 0 {
+            (0, None, None)
+        } else {
+            move_generation::generate_turn_moves(board, initial_bit, piece_id, &perspective_boards, pinned_bitboard)
+        };
+
+        let mut move_bitboard = move_bitboard & final_bitboard;
+
+        let potential_checking_pieces = potential_checking_pieces.clone();
+        std::iter::from_fn(move || bitboard_manipulation::pop_lowest_bit(&mut move_bitboard)).flat_map(move |final_bit| {
+            let ep_bits = turn::get_ep_bits_for_turn(en_passant_target_bit, en_passant_cap_bits, final_bit);
+            let potential_checking_pieces = potential_checking_pieces.clone();
+
+            // Only keep the move if it doesn't leave the king in check
+            promotion_options(board, piece_id, final_bit).into_iter().filter_map(move |promotion_piece_id| {
+                let turn_data = turn::take_turn(
+                    board,
+                    piece_id,
+                    TurnMove::new(initial_bit, final_bit, ep_bits, promotion_piece_id),
+                    false,
+                    potential_checking_pieces.clone()
+                );
+
+                turn_data.ok().map(|(new_board, _)| ((initial_bit, final_bit, promotion_piece_id), new_board))
+            })
+        })
+    })
+}
+
+// Returns the promotion piece ids that can be used for a move
+// This is None unless a pawn is moving onto the last row
+fn promotion_options(board: &Board, piece_id: usize, final_bit: u8) -> Vec<Option<usize>> {
+    if piece_id == pieces::PAWN_ID && turn::is_promotion_bit(final_bit, board.piece_to_move) {
+        pieces::PROMOTION_PIECE_IDS.iter().map(|&promotion_piece_id| Some(promotion_piece_id)).collect()
+    } else {
+        vec![None]
+    }
+}
+
+// Returns Ok if the move is legal for the team to move, otherwise the reason it is illegal
+pub fn classify_move(board: &Board, initial_bit: u8, final_bit: u8, promotion: Option<usize>) -> Result<(), IllegalReason> {
+    let perspective_boards = PerspectiveBoards::gen(board, board.piece_to_move);
+    let piece_id = board_representation::read_piece_id(perspective_boards.friendly_board, initial_bit);
+
+    if piece_id == 0 {
+        if board_representation::read_piece_id(perspective_boards.enemy_board, initial_bit) != 0 {
+            return Err(IllegalReason::NotYourPiece);
+        }
+
+        return Err(IllegalReason::NoPieceThere);
+    }
+
+    let pinned_bitboard = check_validation::pinned_pieces(board, board.piece_to_move);
+    let (
+        move_bitboard,
+        en_passant_target_bit,
+        en_passant_cap_bits
+    ) = move_generation::generate_turn_moves(board, initial_bit, piece_id, &perspective_boards, pinned_bitboard);
+
+    if !bitboard_manipulation::bit_on(move_bitboard, final_bit) {
+
+        // Castling moves are only missing from the move bitboard when they aren't obstructed if the king's path is attacked
+        if piece_id == pieces::KING_ID {
+            let castling_through_check = [true, false].into_iter()
+                .filter_map(|kingside| castling::get_castling_bits(board, board.piece_to_move, kingside))
+                .any(|castling_bits| castling_bits.king_initial_bit == initial_bit
+                    && castling_bits.target_bit == final_bit
+                    && castling::castling_unobstructed(board, board.piece_to_move, &castling_bits));
+
+            if castling_through_check {
+                return Err(IllegalReason::CastlingThroughCheck);
+            }
+        }
+
+        // Pinned pieces moving off their pin ray would leave the king in check
+        let unpinned_bitboard = move_generation::generate_turn_moves(board, initial_bit, piece_id, &perspective_boards, 0).0;
+        if bitboard_manipulation::bit_on(unpinned_bitboard, final_bit) {
+            return Err(IllegalReason::LeavesKingInCheck);
+        }
+
+        return Err(IllegalReason::NotAPseudoLegalTarget);
+    }
+
+    // A promotion piece has to be given if, and only if, a pawn is moving onto the last row
+    let is_promotion = piece_id == pieces::PAWN_ID && turn::is_promotion_bit(final_bit, board.piece_to_move);
+    let valid_promotion = match promotion {
+        Some(promotion_piece_id) => is_promotion && pieces::PROMOTION_PIECE_IDS.contains(&promotion_piece_id),
+        None => !is_promotion,
+    };
+
+    if !valid_promotion {
+        return Err(IllegalReason::BadPromotion);
+    }
+
+    let ep_bits = turn::get_ep_bits_for_turn(en_passant_target_bit, en_passant_cap_bits, final_bit);
+    let potential_checking_pieces = check_validation::get_potential_checking_pieces(board, board.piece_to_move);
+
+    match turn::take_turn(board, piece_id, TurnMove::new(initial_bit, final_bit, ep_bits, promotion), false, potential_checking_pieces) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(IllegalReason::LeavesKingInCheck),
+    }
+}
+
+// Returns true if the team to move has a legal move, assuming it is in check
+// Only king moves, and moves which capture or block a single checking piece, are tried
+// So this is much cheaper than generating every legal move
+pub fn has_check_evasion(board: &Board) -> bool {
+    let (king_bit, enemy_color) = match board.piece_to_move {
+        PieceColor::Black => (board.black_king_bit, PieceColor::White),
+        PieceColor::White => (board.white_king_bit, PieceColor::Black),
+    };
+
+    // The king moving out of check
+    if masked_legal_move_boards(board, 1 << king_bit, u64::MAX).next().is_some() {
+        return true;
+    }
+
+    // Only the king can move out of a double check
+    let checking_bitboard = checking_pieces(board, king_bit, enemy_color);
+    let checking_bit = match bitboard_manipulation::lowest_bit(checking_bitboard) {
+        Some(checking_bit) if bitboard_manipulation::count_bits(checking_bitboard) == 1 => checking_bit,
+        _ => return false,
+    };
+
+    // Other pieces can capture the checking piece, or move between it and the king
+    // Knights and pawns can't be blocked, which bits_between gives None or 0 for
+    let mut evasion_bitboard = checking_bitboard | bitboard_manipulation::bits_between(king_bit, checking_bit).unwrap_or(0);
+
+    // A pawn giving check after a double move can also be captured en-passant
+    if board.en_passant_target_bit == Some(checking_bit) {
+        evasion_bitboard |= match board.piece_to_move {
+            PieceColor::Black => 1 << (checking_bit + 8),
+            PieceColor::White => 1 << (checking_bit - 8),
+        };
+    }
+
+    masked_legal_move_boards(board, !(1 << king_bit), evasion_bitboard).next().is_some()
+}
+
+// Returns a bitboard of the enemy pieces giving check to the king on king_bit
+fn checking_pieces(board: &Board, king_bit: u8, enemy_color: PieceColor) -> u64 {
+    let enemy_perspective_boards = PerspectiveBoards::gen(board, enemy_color);
+    let potential_checking_pieces = check_validation::get_potential_attackers(board, king_bit, enemy_color);
+
+    let mut checking_bitboard = 0;
+    for i in 0..potential_checking_pieces.len() {
+        let piece_bit = potential_checking_pieces.internal_array[i];
+        let piece_id = board_representation::read_piece_id(enemy_perspective_boards.friendly_board, piece_bit);
+        let move_bitboard = move_generation::generate_moves(board, piece_bit, piece_id, enemy_color, &enemy_perspective_boards).0;
+
+        if bitboard_manipulation::bit_on(move_bitboard, king_bit) {
+            checking_bitboard |= 1 << piece_bit;
+        }
+    }
+
+    checking_bitboard
+}
+
+impl Board {
+
+    // Returns true if the team to move is in checkmate
+    pub fn is_checkmate(&self) -> bool {
+        self.in_check() && !has_check_evasion(self)
+    }
+
+    // Returns true if the team to move isn't in check, but has no legal moves
+    pub fn is_stalemate(&self) -> bool {
+        !self.in_check() && !has_legal_move(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board_representation::PieceColor;
+    use crate::board_representation::fen::read_fen;
+
+    #[test]
+    fn test_legal_moves() {
+        assert_eq!(legal_moves(&Board::new()).len(), 20);
+
+        // Pawn promoting with and without a capture, king can't move next to the enemy king
+        let board = read_fen("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!(legal_moves(&board).len(), 8 + 5);
+    }
+
+    #[test]
+    fn test_has_legal_move() {
+        assert!(has_legal_move(&Board::new()));
+        assert_eq!(legal_move_count(&Board::new()), 20);
+
+        // Checkmate and stalemate
+        for fen in ["rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3", "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"] {
+            let board = read_fen(fen);
+            assert!(!has_legal_move(&board));
+            assert_eq!(legal_move_count(&board), 0);
+        }
+
+        let board = read_fen("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!(legal_move_count(&board), legal_moves(&board).len());
+    }
+
+    #[test]
+    fn test_legal_move_boards() {
+        let boards: Vec<_> = legal_move_boards(&Board::new()).collect();
+
+        assert_eq!(boards.len(), 20);
+        assert!(boards.iter().all(|(_, board)| board.piece_to_move == PieceColor::Black));
+
+        // En-passant removes the captured pawn
+        let board = read_fen("4k3/8/8/3pP3/8/8/8/4K3 w - 28 0 1");
+        let (_, en_passant_board) = legal_move_boards(&board).find(|&(legal_move, _)| legal_move == (27, 20, None)).unwrap();
+        assert_eq!(en_passant_board, read_fen("4k3/8/3P4/8/8/8/8/4K3 b - - 0 1"));
+
+        // Each promotion gives a different board
+        let board = read_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1");
+        let promotion_boards: Vec<_> = legal_move_boards(&board).filter(|&((initial_bit, _, _), _)| initial_bit == 15).collect();
+        assert_eq!(promotion_boards.len(), 4);
+        assert_eq!(promotion_boards[3].1, read_fen("N3k3/8/8/8/8/8/8/4K3 b - - 0 1"));
+    }
+
+    #[test]
+    fn test_legal_moves_from() {
+
+        // Knight on d2 pinned by a bishop can't move, the knight on b1 can
+        let board = read_fen("4r2k/8/8/8/1b6/8/3NR3/1N2K3 w - - 0 1");
+        assert_eq!(legal_moves_from(&board, 52), 0);
+        assert_eq!(legal_moves_from(&board, 62), 1 << 47 | 1 << 45);
+
+        // Rook on e2 pinned by a rook can only move along the pin, up to capturing the rook
+        assert_eq!(legal_moves_from(&board, 51), bitboard_manipulation::FILE_MASKS[3] & !(1 << 59 | 1 << 51));
+
+        // Empty bits and enemy pieces have no moves
+        assert_eq!(legal_moves_from(&board, 35), 0);
+        assert_eq!(legal_moves_from(&board, 33), 0);
+
+        // Castling and en-passant, every move matches legal_moves
+        for fen in ["r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq 26 0 3"] {
+            let board = read_fen(fen);
+            let legal_moves = legal_moves(&board);
+
+            for initial_bit in 0..64 {
+                let expected = legal_moves.iter()
+                    .filter(|&&(legal_initial_bit, _, _)| legal_initial_bit == initial_bit)
+                    .fold(0, |final_bitboard, &(_, final_bit, _)| final_bitboard | 1u64 << final_bit);
+                assert_eq!(legal_moves_from(&board, initial_bit), expected, "{} {}", fen, initial_bit);
+            }
+        }
+
+        let board = read_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        assert_eq!(legal_moves_from(&board, 59).count_ones(), 7);
+        let board = read_fen("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq 26 0 3");
+        assert_ne!(legal_moves_from(&board, 27) & 1 << 18, 0);
+    }
+
+    #[test]
+    fn test_movers_to() {
+        let board = Board::new();
+        let movers = |target_bit, color| {
+            let movers = movers_to(&board, target_bit, color);
+            movers.internal_array[..movers.len()].to_vec()
+        };
+
+        // c3 and f3 can each be reached by a knight and a pawn, e4 only by the e-pawn
+        assert_eq!(movers(45, PieceColor::White), vec![53, 62]);
+        assert_eq!(movers(42, PieceColor::White), vec![50, 57]);
+        assert_eq!(movers(35, PieceColor::White), vec![51]);
+        assert_eq!(movers(27, PieceColor::White), vec![]);
+
+        // Works for the team not to move
+        assert_eq!(movers(21, PieceColor::Black), vec![6, 13]);
+
+        // The pinned knight can't move to c3 or capture on g2, but both black rooks can capture the knight
+        let board = read_fen("4r2k/8/8/8/8/8/4N1r1/4K3 w - - 0 1");
+        assert_eq!(movers_to(&board, 45, PieceColor::White).len(), 0);
+        assert_eq!(movers_to(&board, 49, PieceColor::White).len(), 0);
+        assert_eq!(movers_to(&board, 51, PieceColor::Black).len(), 2);
+    }
+
+    #[test]
+    fn test_en_passant_discovered_check() {
+
+        // Capturing en-passant removes both pawns from the kings row, exposing the king to the rook
+        let board = read_fen("8/8/8/K1Pp3r/8/8/8/7k w - 28 0 1");
+        assert!(!legal_moves(&board).contains(&(29, 20, None)));
+        assert_eq!(board.apply_uci_move("c5d6"), Err(crate::uci::MoveError::Check));
+
+        // Without the rook the capture is legal
+        let board = read_fen("8/8/8/K1Pp4/8/8/8/7k w - 28 0 1");
+        assert!(legal_moves(&board).contains(&(29, 20, None)));
+    }
+
+    #[test]
+    fn test_has_check_evasion() {
+        let checked_fens = [
+            "R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1",                            // Back rank mate
+            "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1",                            // Not in check
+            "4k3/8/8/8/8/8/4r3/4K3 w - - 0 1",                              // Capture the checking rook
+            "4k3/8/8/8/8/8/8/r2BK3 w - - 0 1",                              // Block is blocked, king escapes
+            "3qk3/8/8/8/8/8/3PPP2/r2NKB2 w - - 0 1",                        // Smothered, blocked by a piece
+            "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3", // Fool's mate
+            "4k3/8/8/2pP4/1K6/8/8/8 w - 29 0 1",                            // Pawn check, which can be captured en-passant
+            "4k3/8/8/8/8/5n2/4r3/4K3 w - - 0 1",                            // Double check
+            "7k/8/8/8/8/8/1q6/K6R w - - 0 1",                               // Capture with the king
+        ];
+
+        for fen in checked_fens {
+            let board = read_fen(fen);
+            if board.in_check() {
+                assert_eq!(has_check_evasion(&board), !legal_moves(&board).is_empty(), "{}", fen);
+            }
+        }
+
+        // With the king unable to move, only en-passant captures get out of check
+        let board = read_fen("4k3/8/p7/PpP5/K7/PP6/3b4/8 w - 30 0 1");
+        assert_eq!(legal_moves(&board), vec![(29, 22, None), (31, 22, None)]);
+        assert!(has_check_evasion(&board));
+    }
+
+    #[test]
+    fn test_classify_move() {
+        let board = Board::new();
+        assert_eq!(classify_move(&board, 51, 35, None), Ok(()));
+        assert_eq!(classify_move(&board, 35, 27, None), Err(IllegalReason::NoPieceThere));
+        assert_eq!(classify_move(&board, 11, 27, None), Err(IllegalReason::NotYourPiece));
+        assert_eq!(classify_move(&board, 51, 27, None), Err(IllegalReason::NotAPseudoLegalTarget));
+        assert_eq!(classify_move(&board, 51, 35, Some(pieces::QUEEN_ID)), Err(IllegalReason::BadPromotion));
+
+        // Promoting to a king or a pawn, and not naming a promotion piece
+        let board = read_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!(classify_move(&board, 15, 7, Some(pieces::KNIGHT_ID)), Ok(()));
+        assert_eq!(classify_move(&board, 15, 7, Some(pieces::KING_ID)), Err(IllegalReason::BadPromotion));
+        assert_eq!(classify_move(&board, 15, 7, None), Err(IllegalReason::BadPromotion));
+
+        // Pinned knight, and king walking into check
+        let board = read_fen("4r2k/8/8/8/8/8/4N1r1/4K3 w - - 0 1");
+        assert_eq!(classify_move(&board, 51, 45, None), Err(IllegalReason::LeavesKingInCheck));
+        assert_eq!(classify_move(&board, 59, 50, None), Err(IllegalReason::LeavesKingInCheck));
+
+        // Castling through a bit attacked by a rook
+        let board = read_fen("4kr2/8/8/8/8/8/8/R3K2R w KQ - 0 1");
+        assert_eq!(classify_move(&board, 59, 57, None), Err(IllegalReason::CastlingThroughCheck));
+        assert_eq!(classify_move(&board, 59, 61, None), Ok(()));
+    }
+
+    #[test]
+    fn test_checkmate_and_stalemate() {
+
+        // Fools mate
+        let board = read_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3");
+        assert!(board.in_check());
+        assert!(board.is_checkmate());
+        assert!(!board.is_stalemate());
+
+        // Stalemate
+        let board = read_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+        assert!(!board.in_check());
+        assert!(!board.is_checkmate());
+        assert!(board.is_stalemate());
+
+        // Check that can be escaped
+        let board = read_fen("4k3/8/8/8/8/8/4r3/4K3 w - - 0 1");
+        assert!(board.in_check());
+        assert!(!board.is_checkmate());
+        assert!(!board.is_stalemate());
+
+        let board = Board::new();
+        assert!(!board.in_check());
+        assert!(!board.is_checkmate());
+        assert!(!board.is_stalemate());
+    }
+}
@@ -0,0 +1,28 @@
+pub mod pieces;
+pub mod direction_bitboards;
+pub mod board_representation;
+
+pub mod generic_math;
+pub mod fixed_vecor;
+pub mod bitboard_manipulation;
+
+pub mod move_generation;
+pub mod check_validation;
+pub mod en_passant;
+pub mod castling;
+pub mod legal_moves;
+pub mod see;
+pub mod packed_move;
+
+pub mod turn;
+pub mod game_state;
+pub mod uci;
+pub mod san;
+pub mod bot;
+pub mod book;
+pub mod options;
+pub mod bot_eval;
+pub mod pesto;
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -1,23 +1,7 @@
-pub mod pieces;
-pub mod direction_bitboards;
-pub mod board_representation;
-
-pub mod generic_math;
-pub mod fixed_vecor;
-pub mod bitboard_manipulation;
-
-pub mod move_generation;
-pub mod check_validation;
-pub mod en_passant;
-pub mod castling;
-
-pub mod turn;
-pub mod bot;
-pub mod bot_eval;
-pub mod pesto;
-
 use std::time::Duration;
 
+use chess3::{board_representation, bot};
+
 
 
 fn main() {
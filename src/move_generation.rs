@@ -5,6 +5,9 @@ use crate::board_representation::{Board, PieceColor, PerspectiveBoards};
 use crate::bitboard_manipulation::*;
 use crate::direction_bitboards::DirectionBitboard;
 use crate::en_passant::get_en_passant_capture;
+use crate::castling;
+use crate::check_validation;
+use crate::pieces;
 
 // // Get friendly, enemy, and piece information corresponding to the given PieceColor
 // let (friendly_board, friendly_starting_board, enemy_board, piece_information) = match piece_color {
@@ -80,10 +83,16 @@ pub fn generate_moves(
                 output_move_bitboard |= intercepted_mbb;
 
                 // Add pawn capture bitboard to output
+                // Mask to the columns beside the pawn so a capture can never wrap around the edge of the board
                 let capture_bitboard = shift_direction_bitboard(piece_bit, piece_coordinates, &piece_information.pawn_capture_bitboard.as_ref().unwrap());
+                let capture_bitboard = capture_bitboard & adjacent_files_mask(piece_coordinates.0 as u8);
                 output_move_bitboard |= enemy_bitboard & capture_bitboard;
             }
             
+        } else if intercepted_mbb == move_bitboard {
+
+            // Fast path for sparse boards (mostly endgames), nothing blocks this direction so the move bitboard doesn't need fixing
+            output_move_bitboard |= move_bitboard;
         } else {
             
             // Fix the move bitboard so sliding pieces can't move on the other side of pieces blocking thier path
@@ -113,6 +122,103 @@ pub fn generate_moves(
     (output_move_bitboard, en_passant_target_bit, en_passant_cap_bits)
 }
 
+// Generates moves for a piece belonging to the team to move, ready to be used for a turn
+// Unlike generate_moves this includes castling, and restricts pinned pieces to their pin ray
+// The moves can still leave the king in check, so take_turn still has to validate them
+//
+// pinned_bitboard should come from check_validation::pinned_pieces for the team to move
+pub fn generate_turn_moves(
+    board: &Board,
+    piece_bit: u8,
+    piece_id: usize,
+    perspective_boards: &PerspectiveBoards,
+    pinned_bitboard: u64,
+) -> (u64, Option<u8>, Option<(u8, u8)>) {
+    let (
+        move_bitboard,
+        en_passant_target_bit,
+        en_passant_cap_bits
+    ) = generate_moves(board, piece_bit, piece_id, board.piece_to_move, perspective_boards);
+
+    // Add castling moves for the king
+    let move_bitboard = if piece_id == pieces::KING_ID {
+        move_bitboard | castling::get_castling_moves(board, board.piece_to_move)
+    } else {
+        move_bitboard
+    };
+
+    let move_bitboard = restrict_to_pin_ray(board, piece_bit, pinned_bitboard, move_bitboard);
+
+    (move_bitboard, en_passant_target_bit, en_passant_cap_bits)
+}
+
+// Generates only the capture moves for a piece, in the form of a bitboard
+// These are the bits from generate_moves occupied by enemy pieces, plus the en-passant move bit
+// Used by quiescence search so non captures are never turned into moves
+pub fn generate_captures(
+    board: &Board,
+    piece_bit: u8,
+    piece_id: usize,
+    piece_color: PieceColor,
+    perspective_boards: &PerspectiveBoards,
+) -> u64 {
+    let (move_bitboard, _, en_passant_cap_bits) = generate_moves(board, piece_bit, piece_id, piece_color, perspective_boards);
+    let (_, enemy_bitboard) = perspective_boards.gen_bitboards();
+
+    let en_passant_bitboard = en_passant_cap_bits.map_or(0, |en_passant_cap_bits| 1 << en_passant_cap_bits.1);
+    move_bitboard & (enemy_bitboard | en_passant_bitboard)
+}
+
+// Pinned pieces can only move along their pin ray
+// Moves of pieces which aren't pinned are returned unchanged
+pub fn restrict_to_pin_ray(board: &Board, piece_bit: u8, pinned_bitboard: u64, move_bitboard: u64) -> u64 {
+    if bit_on(pinned_bitboard, piece_bit) {
+        move_bitboard & check_validation::get_pin_ray(board, board.piece_to_move, piece_bit).unwrap_or(u64::MAX)
+    } else {
+        move_bitboard
+    }
+}
+
+// Returns a bitboard of every bit attacked by a team
+// Unlike generate_moves, bits occupied by the attacking teams own pieces are included (they are defended)
+// Pawn pushes are never included, only the bits pawns could capture on
+pub fn attack_map(board: &Board, color: PieceColor) -> u64 {
+    let perspective_boards = PerspectiveBoards::gen(board, color);
+    let (friendly_bitboard, enemy_bitboard) = perspective_boards.gen_bitboards();
+
+    // Generate moves as if every piece on the board was an enemy piece
+    // This way the attacking pieces are still blocked by all pieces, but can capture any of them
+    let occupied_board = [friendly_bitboard | enemy_bitboard, 0, 0];
+    let attack_perspective_boards = PerspectiveBoards {
+        friendly_board: &[0; 3],
+        enemy_board: &occupied_board,
+        friendly_starting_board: &[0; 3],
+        friendly_piece_information: perspective_boards.friendly_piece_information,
+        enemy_team_color: perspective_boards.enemy_team_color,
+    };
+
+    let mut output_attack_bitboard = 0;
+    for piece_bit in 0..64 {
+        let piece_id = board_representation::read_piece_id(perspective_boards.friendly_board, piece_bit);
+
+        if piece_id == 0 {
+            continue;
+        }
+
+        let piece_information = &attack_perspective_boards.friendly_piece_information[piece_id];
+
+        // Pawns attack every bit on their capture bitboard, whether or not there is a piece there
+        if let Some(capture_bitboard) = &piece_information.pawn_capture_bitboard {
+            let piece_coordinates = get_piece_coordinates(piece_bit);
+            output_attack_bitboard |= shift_direction_bitboard(piece_bit, piece_coordinates, capture_bitboard) & adjacent_files_mask(piece_coordinates.0 as u8);
+        } else {
+            output_attack_bitboard |= generate_moves(board, piece_bit, piece_id, color, &attack_perspective_boards).0;
+        }
+    }
+
+    output_attack_bitboard
+}
+
 // Calculate en-passant target bit given a pawns shifted double move bitboard and color
 fn calc_ep_target_bit(move_bitboard: &u64, piece_color: PieceColor) -> u8 {
     match piece_color {
@@ -140,6 +246,7 @@ fn calc_move_bitboards(piece_bit: u8, piece_coordinates: (i8, i8), direction_bit
 mod tests {
     use super::*;
     use crate::board_representation::PieceColor;
+    use crate::board_representation::fen::read_fen;
 
     // Gets information needed to run the function
     fn generate_moves_result(board: &Board, piece_bit: u8, for_team: PieceColor) -> (u64, Option<u8>, Option<(u8, u8)>) {
@@ -150,7 +257,6 @@ mod tests {
 
     #[test]
     fn test_generate_moves() {
-        use crate::board_representation::fen::read_fen;
 
         // Test white queen movement
         let board = read_fen("rnbqkbnr/pppppppp/8/8/2Q5/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
@@ -181,4 +287,123 @@ mod tests {
         let expected_bitboard: u64 = 0b0000000000000000000000000000000000000000000001100000000000000000;
         assert_eq!(generate_moves_result(&board, 25, PieceColor::White), (expected_bitboard, None, Some((26, 18))))
     }
+
+    // Slow reference for sliding piece moves, walking each ray one bit at a time
+    fn reference_sliding_moves(board: &Board, piece_bit: u8, for_team: PieceColor) -> u64 {
+        let perspective_boards = PerspectiveBoards::gen(board, for_team);
+        let (friendly_bitboard, enemy_bitboard) = perspective_boards.gen_bitboards();
+        let piece_id = board_representation::read_piece_id(perspective_boards.friendly_board, piece_bit);
+
+        let straight = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+        let diagonal = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+        let directions: Vec<(i8, i8)> = match piece_id {
+            pieces::ROOK_ID => straight.to_vec(),
+            pieces::BISHOP_ID => diagonal.to_vec(),
+            pieces::QUEEN_ID => [straight, diagonal].concat(),
+            _ => panic!("not a sliding piece"),
+        };
+
+        let mut move_bitboard = 0;
+        for (column_step, row_step) in directions {
+            let (mut column, mut row) = get_piece_coordinates(piece_bit);
+            loop {
+                column += column_step;
+                row += row_step;
+                if !(0..8).contains(&column) || !(0..8).contains(&row) {
+                    break;
+                }
+
+                let bit = (row * 8 + column) as u8;
+                if bit_on(friendly_bitboard, bit) {
+                    break;
+                }
+
+                move_bitboard |= 1 << bit;
+                if bit_on(enemy_bitboard, bit) {
+                    break;
+                }
+            }
+        }
+        move_bitboard
+    }
+
+    #[test]
+    fn test_sparse_sliding_moves() {
+        let sparse_fens = [
+            "8/8/8/8/3Q4/8/8/8 w - - 0 1",
+            "8/8/8/8/8/8/8/R7 w - - 0 1",
+            "7b/8/8/8/8/8/8/K7 b - - 0 1",
+            "4k3/8/8/8/4Q3/8/8/4K3 w - - 0 1",
+            "8/8/2k5/8/8/5B2/8/1K5r w - - 0 1",
+            "q7/8/8/3K4/8/8/6k1/7R b - - 0 1",
+            "8/1r4k1/8/8/8/8/1R4K1/8 w - - 0 1",
+        ];
+
+        for fen in sparse_fens {
+            let board = read_fen(fen);
+            for color in PieceColor::all() {
+                let perspective_boards = PerspectiveBoards::gen(&board, color);
+                for bit in 0..64 {
+                    let piece_id = board_representation::read_piece_id(perspective_boards.friendly_board, bit);
+                    if matches!(piece_id, pieces::ROOK_ID | pieces::BISHOP_ID | pieces::QUEEN_ID) {
+                        assert_eq!(generate_moves_result(&board, bit, color).0, reference_sliding_moves(&board, bit, color), "{} {}", fen, bit);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_pawn_captures_at_board_edge() {
+
+        // White pawn on a4 with black pieces on b5, h5, and h4
+        // Only the capture on b5 is valid, the others would wrap around the board
+        let board = read_fen("4k3/8/8/1p5p/P6r/8/8/4K3 w - - 0 1");
+        let expected_bitboard: u64 = 1 << 31 | 1 << 30;
+        assert_eq!(generate_moves_result(&board, 39, PieceColor::White), (expected_bitboard, None, None));
+
+        // Black pawn on h5 with white pieces on g4, a4, and a5
+        let board = read_fen("4k3/8/8/R6p/P5P1/8/8/4K3 b - - 0 1");
+        let expected_bitboard: u64 = 1 << 32 | 1 << 33;
+        assert_eq!(generate_moves_result(&board, 24, PieceColor::Black), (expected_bitboard, None, None));
+    }
+
+    #[test]
+    fn test_generate_captures() {
+
+        // Pawn captures, promotions with capture, and sliding pieces
+        // (fen, piece bit, piece id, expected captures)
+        let positions = [
+            ("4k3/8/8/3p1p2/4P3/8/8/4K3 w - - 0 1", 35, pieces::PAWN_ID, 1 << 28 | 1 << 26),
+            ("r3k2r/1P6/8/8/8/8/6p1/4K2R w K - 0 1", 14, pieces::PAWN_ID, 1 << 7),
+            ("r3k2r/1P6/8/8/8/8/6p1/4K2R b K - 0 1", 49, pieces::PAWN_ID, 1 << 56),
+            ("r1bqkb1r/pppp1ppp/2n2n2/4p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R w KQkq - 0 1", 42, pieces::KNIGHT_ID, 1 << 27),
+            ("4k3/8/3p4/8/3R4/8/8/7K w - - 0 1", 36, pieces::ROOK_ID, 1 << 20),
+        ];
+
+        for (fen, piece_bit, piece_id, expected_bitboard) in positions {
+            let board = read_fen(fen);
+            let perspective_boards = PerspectiveBoards::gen(&board, board.piece_to_move);
+            assert_eq!(generate_captures(&board, piece_bit, piece_id, board.piece_to_move, &perspective_boards), expected_bitboard, "{}", fen);
+        }
+
+        // The en-passant move bit is a capture even though it is empty
+        let board = read_fen("rnbqkbnr/ppppp1pp/8/5pP1/8/8/PPPPPP1P/RNBQKBNR w KQkq 26 0 1");
+        let perspective_boards = PerspectiveBoards::gen(&board, PieceColor::White);
+        assert_eq!(generate_captures(&board, 25, pieces::PAWN_ID, PieceColor::White, &perspective_boards), 1 << 18);
+    }
+
+    #[test]
+    fn test_attack_map() {
+
+        // Starting position, the third row is attacked by pawns but the fourth isn't (pawn pushes aren't attacks)
+        // Every bit on the second row is defended, as is the first row apart from the corners
+        let expected_bitboard: u64 = 0xFF << 40 | 0xFF << 48 | 0x7E << 56;
+        assert_eq!(attack_map(&Board::new(), PieceColor::White), expected_bitboard);
+
+        // Rook on d4 blocked by a black pawn on d6, white king on h1
+        let board = read_fen("4k3/8/3p4/8/3R4/8/8/7K w - - 0 1");
+        let expected_bitboard: u64 = 1 << 20 | 1 << 28 | 0xEF << 32 | 1 << 44 | 1 << 52 | 1 << 60 | 1 << 48 | 1 << 49 | 1 << 57;
+        assert_eq!(attack_map(&board, PieceColor::White), expected_bitboard);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,261 @@
+// Engine options which can be changed with the uci setoption command, e.g. "setoption name MultiPV value 3"
+// Every option is listed in OPTIONS along with how it is read from and written to the search settings,
+// so this is also the list of everything about the engine which can be tuned from outside
+//
+// Hash and Threads are accepted because uci guis send them, but there is no transposition table and the search is single threaded,
+// so they are only stored and don't change the search
+
+use crate::bot::SearchConfig;
+
+// Eval weights are floats, but uci spin options are whole numbers, so weights are set as percentages
+const WEIGHT_SCALE: f32 = 100.0;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum OptionType {
+    Check,                      // true or false
+    Spin { min: i64, max: i64 },  // A whole number between min and max (inclusive)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum OptionError {
+    UnknownName,        // No option has the name
+    InvalidValue,       // The value couldn't be parsed as the type of the option
+    OutOfRange,         // A spin value was outside of the options range
+    InvalidCommand,     // A setoption command wasn't in the form "setoption name <name> value <value>"
+}
+
+// An option, with functions to read and write its value on the engine options
+// Check options are read and written as 0 or 1
+pub struct EngineOption {
+    pub name: &'static str,
+    pub option_type: OptionType,
+    get: fn(&EngineOptions) -> i64,
+    set: fn(&mut EngineOptions, i64),
+}
+
+// Everything the options can change
+#[derive(Debug, PartialEq, Clone)]
+pub struct EngineOptions {
+    pub search_config: SearchConfig,
+    pub multi_pv: usize, // Number of best moves to report, for bot::gen_best_moves
+    pub hash_size_mb: usize, // Unused
+    pub threads: usize, // Unused
+}
+
+impl Default for EngineOptions {
+    fn default() -> Self {
+        EngineOptions {
+            search_config: SearchConfig::default(),
+            multi_pv: 1,
+            hash_size_mb: 16,
+            threads: 1,
+        }
+    }
+}
+
+// Makes an option for a bool in the search config
+macro_rules! check_option {
+    ($name:expr, $($field:ident).+) => {
+        EngineOption {
+            name: $name,
+            option_type: OptionType::Check,
+            get: |options| options.search_config.$($field).+ as i64,
+            set: |options, value| options.search_config.$($field).+ = value != 0,
+        }
+    };
+}
+
+// Makes an option for an eval weight, set as a percentage
+macro_rules! weight_option {
+    ($name:expr, $field:ident) => {
+        EngineOption {
+            name: $name,
+            option_type: OptionType::Spin { min: 0, max: 100 },
+            get: |options| (options.search_config.eval_weights.$field * WEIGHT_SCALE).round() as i64,
+            set: |options, value| options.search_config.eval_weights.$field = value as f32 / WEIGHT_SCALE,
+        }
+    };
+}
+
+pub const OPTIONS: [EngineOption; 23] = [
+    EngineOption {
+        name: "MultiPV",
+        option_type: OptionType::Spin { min: 1, max: 64 },
+        get: |options| options.multi_pv as i64,
+        set: |options, value| options.multi_pv = value as usize,
+    },
+    EngineOption {
+        name: "Hash",
+        option_type: OptionType::Spin { min: 1, max: 1024 },
+        get: |options| options.hash_size_mb as i64,
+        set: |options, value| options.hash_size_mb = value as usize,
+    },
+    EngineOption {
+        name: "Threads",
+        option_type: OptionType::Spin { min: 1, max: 64 },
+        get: |options| options.threads as i64,
+        set: |options, value| options.threads = value as usize,
+    },
+    EngineOption {
+        name: "Contempt",
+        option_type: OptionType::Spin { min: -100, max: 100 },
+        get: |options| (options.search_config.contempt * WEIGHT_SCALE).round() as i64,
+        set: |options, value| options.search_config.contempt = value as f32 / WEIGHT_SCALE,
+    },
+    EngineOption {
+        name: "QuiescenceDepth",
+        option_type: OptionType::Spin { min: 0, max: 16 },
+        get: |options| options.search_config.quiescence_depth as i64,
+        set: |options, value| options.search_config.quiescence_depth = value as u8,
+    },
+    EngineOption {
+        name: "StandPatMargin",
+        option_type: OptionType::Spin { min: 0, max: 100 },
+        get: |options| (options.search_config.stand_pat_margin * WEIGHT_SCALE).round() as i64,
+        set: |options, value| options.search_config.stand_pat_margin = value as f32 / WEIGHT_SCALE,
+    },
+    check_option!("Quiescence", quiescence),
+    check_option!("FutilityPruning", futility_pruning),
+    check_option!("EvalCache", eval_cache),
+    check_option!("EarlyDrawExit", early_draw_exit),
+    check_option!("SeeMoveOrdering", see_move_ordering),
+    weight_option!("MaterialWeight", material),
+    weight_option!("SquareTableWeight", square_table),
+    weight_option!("MobilityWeight", mobility),
+    weight_option!("HangingWeight", hanging),
+    weight_option!("RooksWeight", rooks),
+    weight_option!("PassedPawnsWeight", passed_pawns),
+    weight_option!("KingActivityWeight", king_activity),
+    weight_option!("BackRankWeight", back_rank),
+    weight_option!("OutpostsWeight", outposts),
+    weight_option!("PromotingPawnsWeight", promoting_pawns),
+    weight_option!("TempoWeight", tempo),
+    weight_option!("MopUpWeight", mop_up),
+];
+
+impl EngineOptions {
+
+    // Sets an option from its name and value, as given to setoption
+    // Names aren't case sensitive, like in the uci protocol
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), OptionError> {
+        let option = find_option(name)?;
+
+        let value = match option.option_type {
+            OptionType::Check => match value {
+                "true" => 1,
+                "false" => 0,
+                _ => return Err(OptionError::InvalidValue),
+            },
+            OptionType::Spin { min, max } => {
+                let value: i64 = value.parse().map_err(|_| OptionError::InvalidValue)?;
+                if !(min..=max).contains(&value) {
+                    return Err(OptionError::OutOfRange);
+                }
+                value
+            },
+        };
+
+        (option.set)(self, value);
+        Ok(())
+    }
+
+    // Returns the value of an option, formatted the same as it is given to set
+    pub fn get(&self, name: &str) -> Result<String, OptionError> {
+        let option = find_option(name)?;
+        let value = (option.get)(self);
+
+        Ok(match option.option_type {
+            OptionType::Check => (value != 0).to_string(),
+            OptionType::Spin { .. } => value.to_string(),
+        })
+    }
+
+    // Applies a full setoption command, e.g. "setoption name MultiPV value 3"
+    pub fn apply_setoption(&mut self, command: &str) -> Result<(), OptionError> {
+        let command = command.trim().strip_prefix("setoption name ").ok_or(OptionError::InvalidCommand)?;
+        let (name, value) = command.split_once(" value ").ok_or(OptionError::InvalidCommand)?;
+
+        self.set(name.trim(), value.trim())
+    }
+
+    // Returns the uci option lines sent in reply to the uci command, using the current values as the defaults
+    // E.g. "option name MultiPV type spin default 1 min 1 max 64"
+    pub fn uci_option_lines(&self) -> Vec<String> {
+        OPTIONS.iter().map(|option| {
+            let default = self.get(option.name).expect("option is in OPTIONS");
+            match option.option_type {
+                OptionType::Check => format!("option name {} type check default {}", option.name, default),
+                OptionType::Spin { min, max } => format!("option name {} type spin default {} min {} max {}", option.name, default, min, max),
+            }
+        }).collect()
+    }
+}
+
+fn find_option(name: &str) -> Result<&'static EngineOption, OptionError> {
+    OPTIONS.iter()
+        .find(|option| option.name.eq_ignore_ascii_case(name))
+        .ok_or(OptionError::UnknownName)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::QUIESCENCE_SEARCH_MAX_DEPTH;
+
+    #[test]
+    fn test_setoption() {
+        let mut options = EngineOptions::default();
+
+        assert_eq!(options.apply_setoption("setoption name MultiPV value 3"), Ok(()));
+        assert_eq!(options.apply_setoption("setoption name futilitypruning value true"), Ok(()));
+        assert_eq!(options.apply_setoption("setoption name MobilityWeight value 15"), Ok(()));
+        assert_eq!(options.apply_setoption("setoption name QuiescenceDepth value 5"), Ok(()));
+        assert_eq!(options.apply_setoption("setoption name Contempt value -20"), Ok(()));
+
+        assert_eq!(options.multi_pv, 3);
+        assert!(options.search_config.futility_pruning);
+        assert_eq!(options.search_config.eval_weights.mobility, 0.15);
+        assert_eq!(options.search_config.quiescence_depth, 5);
+        assert_eq!(options.search_config.contempt, -0.2);
+
+        // Hash and Threads are accepted, but only stored
+        let search_config = options.search_config.clone();
+        assert_eq!(options.apply_setoption("setoption name Hash value 64"), Ok(()));
+        assert_eq!(options.apply_setoption("setoption name Threads value 4"), Ok(()));
+        assert_eq!((options.hash_size_mb, options.threads), (64, 4));
+        assert_eq!(options.search_config, search_config);
+
+        // Values read back the same as they were set
+        assert_eq!(options.get("MultiPV"), Ok("3".to_string()));
+        assert_eq!(options.get("FutilityPruning"), Ok("true".to_string()));
+        assert_eq!(options.get("MobilityWeight"), Ok("15".to_string()));
+        assert_eq!(options.get("Contempt"), Ok("-20".to_string()));
+
+        // Invalid names and values are errors, and don't change anything
+        let unchanged = options.clone();
+        assert_eq!(options.apply_setoption("setoption name Ponder value true"), Err(OptionError::UnknownName));
+        assert_eq!(options.apply_setoption("setoption name MultiPV value 0"), Err(OptionError::OutOfRange));
+        assert_eq!(options.apply_setoption("setoption name MultiPV value three"), Err(OptionError::InvalidValue));
+        assert_eq!(options.apply_setoption("setoption name EvalCache value yes"), Err(OptionError::InvalidValue));
+        assert_eq!(options.apply_setoption("setoption name MultiPV"), Err(OptionError::InvalidCommand));
+        assert_eq!(options, unchanged);
+    }
+
+    #[test]
+    fn test_default_options() {
+        let options = EngineOptions::default();
+        assert_eq!(options.get("QuiescenceDepth"), Ok(QUIESCENCE_SEARCH_MAX_DEPTH.to_string()));
+
+        // Every default value is in range, so setting each option to its default changes nothing
+        let mut reset_options = options.clone();
+        for option in &OPTIONS {
+            assert_eq!(reset_options.set(option.name, &options.get(option.name).unwrap()), Ok(()), "{}", option.name);
+        }
+        assert_eq!(reset_options, options);
+
+        let lines = options.uci_option_lines();
+        assert_eq!(lines.len(), OPTIONS.len());
+        assert!(lines.contains(&"option name MultiPV type spin default 1 min 1 max 64".to_string()));
+        assert!(lines.contains(&"option name Quiescence type check default true".to_string()));
+    }
+}
@@ -0,0 +1,156 @@
+// Moves packed into 16 bits, for storing lots of moves without the size of a (u8, u8, Option<usize>) tuple
+//
+// Bits 0-5   initial bit
+// Bits 6-11  final bit
+// Bits 12-15 flag, either a castle, an en-passant, or a promotion with the index of the piece in pieces::PROMOTION_PIECE_IDS
+
+use crate::board_representation;
+use crate::board_representation::{Board, PerspectiveBoards};
+use crate::castling;
+use crate::en_passant;
+use crate::pieces;
+
+const BIT_MASK: u16 = 0b111111;
+const FINAL_BIT_SHIFT: u16 = 6;
+const FLAG_SHIFT: u16 = 12;
+
+const QUIET_FLAG: u16 = 0;
+const CASTLE_FLAG: u16 = 1;
+const EN_PASSANT_FLAG: u16 = 2;
+const PROMOTION_FLAG: u16 = 0b100; // The lowest 2 bits of the flag are the promotion piece index
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Move(pub u16);
+
+impl Move {
+
+    // Packs a move in the same format as legal_moves
+    // Castles and en-passants aren't flagged, use from_board for that
+    pub fn new(initial_bit: u8, final_bit: u8, promotion_piece_id: Option<usize>) -> Self {
+        let flag = match promotion_piece_id {
+            Some(promotion_piece_id) => {
+                let promotion_index = pieces::PROMOTION_PIECE_IDS.iter()
+                    .position(|&piece_id| piece_id == promotion_piece_id)
+                    .expect("not a promotion piece");
+                PROMOTION_FLAG | promotion_index as u16
+            },
+            None => QUIET_FLAG,
+        };
+
+        Move::with_flag(initial_bit, final_bit, flag)
+    }
+
+    // Packs a move for the team to move on a board, flagging castles and en-passants
+    pub fn from_board(board: &Board, initial_bit: u8, final_bit: u8, promotion_piece_id: Option<usize>) -> Self {
+        let perspective_boards = PerspectiveBoards::gen(board, board.piece_to_move);
+        let piece_id = board_representation::read_piece_id(perspective_boards.friendly_board, initial_bit);
+
+        let is_en_passant = en_passant::get_en_passant_capture(board, perspective_boards.friendly_board, perspective_boards.enemy_board, initial_bit)
+            .is_some_and(|(_, ep_move_bit)| ep_move_bit == final_bit);
+
+        if piece_id == pieces::KING_ID && castling::get_castling_move(board, initial_bit, final_bit).is_some() {
+            Move::with_flag(initial_bit, final_bit, CASTLE_FLAG)
+        } else if is_en_passant {
+            Move::with_flag(initial_bit, final_bit, EN_PASSANT_FLAG)
+        } else {
+            Move::new(initial_bit, final_bit, promotion_piece_id)
+        }
+    }
+
+    fn with_flag(initial_bit: u8, final_bit: u8, flag: u16) -> Self {
+        Move(initial_bit as u16 & BIT_MASK | (final_bit as u16 & BIT_MASK) << FINAL_BIT_SHIFT | flag << FLAG_SHIFT)
+    }
+
+    fn flag(self) -> u16 {
+        self.0 >> FLAG_SHIFT
+    }
+
+    pub fn from_bit(self) -> u8 {
+        (self.0 & BIT_MASK) as u8
+    }
+
+    pub fn to_bit(self) -> u8 {
+        (self.0 >> FINAL_BIT_SHIFT & BIT_MASK) as u8
+    }
+
+    // Returns the piece id a pawn is promoted to, if the move is a promotion
+    pub fn promotion(self) -> Option<usize> {
+        if self.flag() & PROMOTION_FLAG != 0 {
+            Some(pieces::PROMOTION_PIECE_IDS[(self.flag() & 0b11) as usize])
+        } else {
+            None
+        }
+    }
+
+    pub fn is_castle(self) -> bool {
+        self.flag() == CASTLE_FLAG
+    }
+
+    pub fn is_en_passant(self) -> bool {
+        self.flag() == EN_PASSANT_FLAG
+    }
+
+    // Returns the move in the format (initial_bit, final_bit, promotion_piece_id)
+    pub fn to_tuple(self) -> (u8, u8, Option<usize>) {
+        (self.from_bit(), self.to_bit(), self.promotion())
+    }
+}
+
+impl From<(u8, u8, Option<usize>)> for Move {
+    fn from((initial_bit, final_bit, promotion_piece_id): (u8, u8, Option<usize>)) -> Self {
+        Move::new(initial_bit, final_bit, promotion_piece_id)
+    }
+}
+
+impl From<Move> for (u8, u8, Option<usize>) {
+    fn from(packed_move: Move) -> Self {
+        packed_move.to_tuple()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board_representation::fen::read_fen;
+    use crate::legal_moves::legal_moves;
+
+    #[test]
+    fn test_round_trip() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/1P6/8/8/4p3/8/3P4/R3K2R w KQkq - 0 1",
+            "4k3/8/8/3pP3/8/8/8/4K3 w - 28 0 1",
+            "1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1",
+            "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1",
+        ];
+
+        for fen in fens {
+            let board = read_fen(fen);
+            for legal_move in legal_moves(&board) {
+                let (initial_bit, final_bit, promotion_piece_id) = legal_move;
+
+                assert_eq!(Move::from(legal_move).to_tuple(), legal_move, "{}", fen);
+                assert_eq!(<(u8, u8, Option<usize>)>::from(Move::from_board(&board, initial_bit, final_bit, promotion_piece_id)), legal_move, "{}", fen);
+            }
+        }
+    }
+
+    #[test]
+    fn test_flags() {
+
+        // Castling queenside, and promoting to a knight
+        let board = read_fen("r3k2r/1P6/8/8/4p3/8/3P4/R3K2R w KQkq - 0 1");
+        assert!(Move::from_board(&board, 59, 61, None).is_castle());
+        assert!(!Move::from_board(&board, 59, 60, None).is_castle());
+        assert_eq!(Move::from_board(&board, 9, 1, Some(pieces::KNIGHT_ID)).promotion(), Some(pieces::KNIGHT_ID));
+
+        // En-passant
+        let board = read_fen("4k3/8/8/3pP3/8/8/8/4K3 w - 28 0 1");
+        let en_passant = Move::from_board(&board, 27, 20, None);
+        assert!(en_passant.is_en_passant() && !en_passant.is_castle());
+        assert_eq!(en_passant.promotion(), None);
+        assert!(!Move::from_board(&board, 27, 19, None).is_en_passant());
+
+        assert_eq!(std::mem::size_of::<Move>(), 2);
+    }
+}
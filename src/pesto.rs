@@ -14,58 +14,94 @@
 use crate::bitboard_manipulation;
 use crate::generic_math;
 use crate::board_representation;
-use crate::board_representation::{Board, TEAM_MATERIAL_VALUE, PieceColor};
+use crate::board_representation::{Board, PieceColor};
 
 
 // Converts bitboatd bit to pesto table index
+// The tables list the squares from a8 to h1 in reading order, so the index comes from the square name
 fn convert_bit_to_index(bit: u8) -> usize {
-    let (column, row) = bitboard_manipulation::get_piece_coordinates(bit);
-    return ((column - 7).abs() + (row * 8)) as usize
+    let square = bitboard_manipulation::square_name(bit).as_bytes();
+    ((square[0] - b'a') + (b'8' - square[1]) * 8) as usize
 }
 
 // The pesto tables only work from the perspective of the white team
 // (when using the index straight out of convert_bit_to_index)
 //
-// This function inverts the index so the tables can be used properly from the
+// This function flips the index vertically so the tables can be used properly from the
 // black teams perspective
+// It has to be a flip rather than a rotation, otherwise a position and it's mirror image don't score the same
 fn invert_index(index: usize) -> usize {
-    (index as i8 - 63).abs() as usize
+    index ^ 56
 }
 
-// Returns a value from 0.0 to 1.0 (generally in this range, but no clamp is applied to enforce this)
-// This value describes how much the board alligns with the piece square tables
-pub fn get_table_value(board: &Board) -> f32 {
-    let (current_material_value, friendly_baord, invert_indices) = match board.piece_to_move {
-        PieceColor::Black => (board.black_material, board.black_board, true),
-        PieceColor::White => (board.white_material, board.white_board, false),
-    };
+// Midgame and endgame piece square table totals for one team
+// Kept on the board and updated incrementally as pieces move, so the tables don't have to be rescanned every evaluation
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
+pub struct TableScore {
+    pub mg: i16,
+    pub eg: i16,
+}
 
-    // 1.0 for midgame, 0.0 for endgame
-    let mg_weight = generic_math::f32_scale(current_material_value as f32, 0.0, TEAM_MATERIAL_VALUE as f32);
-    let mut total_mg: f32 = 0.0;
-    let mut total_eg: f32 = 0.0;
-    for bit in 0..64 {
-        let piece_id = board_representation::read_piece_id(&friendly_baord, bit);
+impl std::ops::AddAssign for TableScore {
+    fn add_assign(&mut self, other: TableScore) {
+        self.mg += other.mg;
+        self.eg += other.eg;
+    }
+}
 
-        if piece_id == 0 {
-            continue;
-        }
+impl std::ops::SubAssign for TableScore {
+    fn sub_assign(&mut self, other: TableScore) {
+        self.mg -= other.mg;
+        self.eg -= other.eg;
+    }
+}
+
+// Returns the table values of a single piece of the given color on a bit
+// An empty bit (piece id 0) scores nothing
+pub fn piece_table_score(piece_id: usize, bit: u8, color: PieceColor) -> TableScore {
+
+    // Get index and invert for black team if neccasary
+    let index = convert_bit_to_index(bit);
+    let index = match color {
+        PieceColor::Black => invert_index(index),
+        PieceColor::White => index,
+    };
+
+    TableScore {
+        mg: MIDGAME_TABLES[piece_id][index] as i16,
+        eg: ENDGAME_TABLES[piece_id][index] as i16,
+    }
+}
 
-        // Get index and invert for black team if neccasary
-        let index = convert_bit_to_index(bit);
-        let index = if invert_indices {
-            invert_index(index)
-        } else {
-            index
-        };
+// Sums the table values of every piece on a team board
+// Used to check the table scores which are updated incrementally
+pub fn recount_table_score(board: &Board, color: PieceColor) -> TableScore {
+    let team_board = match color {
+        PieceColor::Black => &board.black_board,
+        PieceColor::White => &board.white_board,
+    };
 
-        total_mg += MIDGAME_TABLES[piece_id][index] as f32;
-        total_eg += ENDGAME_TABLES[piece_id][index] as f32;
+    let mut total = TableScore::default();
+    for bit in 0..64 {
+        let piece_id = board_representation::read_piece_id(team_board, bit);
+        total += piece_table_score(piece_id, bit, color);
     }
 
-    
-    let total = total_mg * mg_weight + total_eg * (1.0 - mg_weight);
-    generic_math::f32_scale(total, -300.0, 300.0)
+    total
+}
+
+// Returns a value from 0.0 to 1.0
+// This value describes how much the board alligns with the piece square tables
+pub fn get_table_value(board: &Board) -> f32 {
+    let table_score = match board.piece_to_move {
+        PieceColor::Black => board.black_table_score,
+        PieceColor::White => board.white_table_score,
+    };
+
+    // 1.0 for midgame, 0.0 for endgame
+    let mg_weight = board.game_phase();
+    let total = table_score.mg as f32 * mg_weight + table_score.eg as f32 * (1.0 - mg_weight);
+    generic_math::f32_scale_clamped(total, -300.0, 300.0)
 }
 
 // https://www.chessprogramming.org/PeSTO%27s_Evaluation_Function
@@ -237,8 +273,8 @@ mod tests {
 
     #[test]
     fn test_invert_index() {
-        assert_eq!(invert_index(56), 7);
-        assert_eq!(invert_index(14), 49);
+        assert_eq!(invert_index(56), 0);
+        assert_eq!(invert_index(14), 54);
     }
 
     #[test]
@@ -247,4 +283,24 @@ mod tests {
         let board2 = read_fen("rnbqkbnr/pppppppp/8/8/3P4/8/PPP1PPPP/RNBQKBNR w KQkq - 0 1");
         assert!(get_table_value(&board2) > get_table_value(&board1));
     }
+
+    #[test]
+    fn test_incremental_table_score() {
+
+        // Captures by both teams, castling, an en-passant, and a promotion with capture
+        let mut board = read_fen("r3k2r/1P6/8/8/4p3/8/3P4/R3K2R w KQkq - 0 1");
+        for uci_move in ["e1c1", "e8g8", "d2d4", "e4d3", "b7a8q", "d3d2", "c1d2", "f8a8"] {
+            board = board.apply_uci_move(uci_move).expect(uci_move);
+            assert_eq!(board.white_table_score, recount_table_score(&board, PieceColor::White));
+            assert_eq!(board.black_table_score, recount_table_score(&board, PieceColor::Black));
+        }
+    }
+
+    #[test]
+    fn test_mirrored_table_score() {
+        let board = read_fen("r1bqk2r/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP3PPP/R1BQKB1R w KQkq - 0 1");
+        let mirrored = board.mirror();
+        assert_eq!(recount_table_score(&mirrored, PieceColor::White), board.black_table_score);
+        assert_eq!(recount_table_score(&mirrored, PieceColor::Black), board.white_table_score);
+    }
 }
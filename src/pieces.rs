@@ -7,6 +7,13 @@ use crate::direction_bitboards::*;
 
 pub const KING_ID: usize = 6;
 pub const PAWN_ID: usize = 1;
+pub const KNIGHT_ID: usize = 2;
+pub const BISHOP_ID: usize = 3;
+pub const ROOK_ID: usize = 4;
+pub const QUEEN_ID: usize = 5;
+
+// Pieces a pawn can be promoted to
+pub const PROMOTION_PIECE_IDS: [usize; 4] = [QUEEN_ID, ROOK_ID, BISHOP_ID, KNIGHT_ID];
 
 // Question mark used as a placeholder so the index of the character can be used as a piece id
 // This is only used for decoding FEN strings
@@ -17,7 +24,7 @@ pub const WHITE_PIECE_TYPES: [char; 7] = ['?', 'P', 'N', 'B', 'R', 'Q', 'K'];
 // More unique immplenetations than can be defined in this struct will have to be hardcoded
 #[derive(Debug)]
 pub struct PieceInformation {
-    pub piece_value: i8, // Material value of piece
+    pub piece_value: i16, // Material value of piece
     pub is_sliding: bool, // True if the piece can slide (bishop, rook, queen)
 
     pub move_directions: usize, // How many direction bitboards the piece has, corresponds to elements in the $direction_bitboards array
@@ -37,7 +44,7 @@ const GENERIC_KNIGHT: PieceInformation = PieceInformation {
     piece_value: 3,
     is_sliding: false,
     move_directions: 1,
-    direction_bitboards: [Some(KING_MOVES), None, None, None],
+    direction_bitboards: [Some(KNIGHT_MOVES), None, None, None],
     pawn_capture_bitboard: None,
     pawn_double_move_bitboard: None,
 };
@@ -0,0 +1,165 @@
+// Module for reading standard algebraic notation (san) moves, and applying them to a board
+// E.g. e4, Nbd7, exd6 e.p., O-O-O, e8=Q+
+//
+// San only names the piece and the bit it moves to, so moves are resolved against the legal moves of the position
+
+use crate::board_representation::{Board, PieceColor};
+use crate::board_representation;
+use crate::castling;
+use crate::legal_moves;
+use crate::pieces;
+use crate::uci;
+
+#[derive(Debug, PartialEq)]
+pub enum SanError {
+    InvalidNotation,    // The move string couldn't be parsed
+    NoMatchingMove,     // No legal move matches the san
+    Ambiguous,          // More than one legal move matches the san
+}
+
+// Applies a san move to the board, returning the new board
+// Check, checkmate, and annotation suffixes (+, #, !, ?) and an e.p. suffix are allowed but not checked
+pub fn apply_san(board: &Board, san: &str) -> Result<Board, SanError> {
+    let san = san.trim();
+    let san = san.strip_suffix("e.p.").unwrap_or(san).trim_end();
+    let san = san.trim_end_matches(['+', '#', '!', '?']);
+
+    if !san.is_ascii() || san.is_empty() {
+        return Err(SanError::InvalidNotation);
+    }
+
+    // Castling is matched by the castling bits, so it works for Chess960 as well
+    if let Some(kingside) = match san {
+        "O-O" | "0-0" => Some(true),
+        "O-O-O" | "0-0-0" => Some(false),
+        _ => None,
+    } {
+        let castling_bits = castling::get_castling_bits(board, board.piece_to_move, kingside).ok_or(SanError::NoMatchingMove)?;
+        return find_move(board, |initial_bit, final_bit, _, _| {
+            initial_bit == castling_bits.king_initial_bit && final_bit == castling_bits.target_bit
+        });
+    }
+
+    // Piece letter, pawns don't have one
+    let (piece_id, san) = match pieces::WHITE_PIECE_TYPES.iter().position(|&p| san.starts_with(p)) {
+        Some(piece_id) if piece_id != pieces::PAWN_ID && piece_id != 0 => (piece_id, &san[1..]),
+        _ => (pieces::PAWN_ID, san),
+    };
+
+    // Promotion, e.g. e8=Q or e8Q
+    let (promotion_piece_id, san) = match pieces::WHITE_PIECE_TYPES.iter().position(|&p| san.ends_with(p)) {
+        Some(promotion_piece_id) if pieces::PROMOTION_PIECE_IDS.contains(&promotion_piece_id) => {
+            let san = &san[..san.len() - 1];
+            (Some(promotion_piece_id), san.strip_suffix('=').unwrap_or(san))
+        },
+        _ => (None, san),
+    };
+
+    // The final bit is always the last two characters, anything before it is a capture and/or disambiguation
+    if san.len() < 2 {
+        return Err(SanError::InvalidNotation);
+    }
+    let final_bit = uci::square_to_bit(&san[san.len() - 2..]).ok_or(SanError::InvalidNotation)?;
+
+    let disambiguation = &san[..san.len() - 2];
+    let disambiguation = disambiguation.strip_suffix('x').unwrap_or(disambiguation);
+    if disambiguation.len() > 2 || !disambiguation.chars().all(|c| ('a'..='h').contains(&c) || ('1'..='8').contains(&c)) {
+        return Err(SanError::InvalidNotation);
+    }
+
+    find_move(board, |initial_bit, move_final_bit, move_piece_id, move_promotion_piece_id| {
+        let initial_square = uci::bit_to_square(initial_bit);
+
+        move_piece_id == piece_id
+            && move_final_bit == final_bit
+            && move_promotion_piece_id == promotion_piece_id
+            && disambiguation.chars().all(|c| initial_square.contains(c))
+    })
+}
+
+// Applies a sequence of san moves separated by whitespace, e.g. "1. e4 e5 2. Nf3"
+// Move numbers are skipped
+pub fn apply_san_moves(board: &Board, san_moves: &str) -> Result<Board, SanError> {
+    let mut board = board.clone();
+
+    for san in san_moves.split_whitespace() {
+
+        // Move numbers (1. or 1...), which can be written directly before the move (1.e4)
+        // This also skips e.p. suffixes written as a seperate word
+        let san = san.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+        if san.is_empty() || san == "e.p." {
+            continue;
+        }
+
+        board = apply_san(&board, san)?;
+    }
+
+    Ok(board)
+}
+
+// Applies the only legal move matching a filter
+// The filter is given the initial bit, final bit, piece id, and promotion piece id of each legal move
+fn find_move(board: &Board, filter: impl Fn(u8, u8, usize, Option<usize>) -> bool) -> Result<Board, SanError> {
+    let friendly_board = match board.piece_to_move {
+        PieceColor::Black => &board.black_board,
+        PieceColor::White => &board.white_board,
+    };
+
+    let mut matching_boards = legal_moves::legal_move_boards(board).filter(|&((initial_bit, final_bit, promotion_piece_id), _)| {
+        let piece_id = board_representation::read_piece_id(friendly_board, initial_bit);
+        filter(initial_bit, final_bit, piece_id, promotion_piece_id)
+    });
+
+    match (matching_boards.next(), matching_boards.next()) {
+        (Some((_, new_board)), None) => Ok(new_board),
+        (Some(_), Some(_)) => Err(SanError::Ambiguous),
+        (None, _) => Err(SanError::NoMatchingMove),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board_representation::fen::read_fen;
+
+    #[test]
+    fn test_apply_san_moves() {
+        let san_board = apply_san_moves(&Board::new(), "1. e4 e5 2. Nf3 Nc6 3.Bb5 a6 4. Bxc6 dxc6 5. O-O Qd6+?! 6. d4 exd4").unwrap();
+
+        let mut uci_board = Board::new();
+        for uci_move in ["e2e4", "e7e5", "g1f3", "b8c6", "f1b5", "a7a6", "b5c6", "d7c6", "e1g1", "d8d6", "d2d4", "e5d4"] {
+            uci_board = uci_board.apply_uci_move(uci_move).unwrap();
+        }
+
+        assert_eq!(san_board, uci_board);
+    }
+
+    #[test]
+    fn test_apply_san() {
+
+        // En-passant
+        let board = read_fen("4k3/8/8/3pP3/8/8/8/4K3 w - 28 0 1");
+        assert_eq!(apply_san(&board, "exd6 e.p."), Ok(read_fen("4k3/8/3P4/8/8/8/8/4K3 b - - 0 1")));
+        assert_eq!(apply_san(&board, "exd6"), apply_san(&board, "exd6 e.p."));
+
+        // Queenside castling
+        let board = read_fen("r3k3/8/8/8/8/8/8/4K3 b q - 0 1");
+        assert_eq!(apply_san(&board, "O-O-O"), Ok(read_fen("2kr4/8/8/8/8/8/8/4K3 w - - 1 2")));
+        assert_eq!(apply_san(&board, "O-O"), Err(SanError::NoMatchingMove));
+
+        // Knights on g1 and e5 can both move to f3
+        let board = read_fen("4k3/8/8/4N3/8/8/8/4K1N1 w - - 0 1");
+        assert_eq!(apply_san(&board, "Nf3"), Err(SanError::Ambiguous));
+        assert_eq!(apply_san(&board, "Ngf3"), board.apply_uci_move("g1f3").map_err(|_| SanError::NoMatchingMove));
+        assert_eq!(apply_san(&board, "N5f3"), board.apply_uci_move("e5f3").map_err(|_| SanError::NoMatchingMove));
+
+        // Promotion with check
+        let board = read_fen("k7/4P3/8/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!(apply_san(&board, "e8=Q+"), board.apply_uci_move("e7e8q").map_err(|_| SanError::NoMatchingMove));
+        assert_eq!(apply_san(&board, "e8N"), board.apply_uci_move("e7e8n").map_err(|_| SanError::NoMatchingMove));
+        assert_eq!(apply_san(&board, "e8"), Err(SanError::NoMatchingMove));
+
+        assert_eq!(apply_san(&board, "Kz9"), Err(SanError::InvalidNotation));
+        assert_eq!(apply_san(&board, ""), Err(SanError::InvalidNotation));
+    }
+}
@@ -0,0 +1,136 @@
+// Static exchange evaluation (SEE)
+// Works out the material won or lost by a capture, if both teams keep recapturing on the same bit with their least valuable piece
+//
+// Pins and checks are ignored, and promotions during the exchange aren't counted
+// Pieces behind a capturing slider (x-rays) join in, because the attackers are found again after each capture
+
+use crate::board_representation;
+use crate::board_representation::{Board, PieceColor};
+use crate::check_validation;
+use crate::pieces;
+
+// Returns the material gained by the team to move from capturing on final_bit with the piece on initial_bit
+// Negative when the capture loses material, and 0 for an even trade
+// Quiet moves can be checked as well, they score 0 unless the piece can be won on final_bit
+pub fn see(board: &Board, initial_bit: u8, final_bit: u8) -> i16 {
+    let mut board = board.clone();
+    let color = board.piece_to_move;
+
+    let (friendly_board, enemy_board) = team_boards(&mut board, color);
+    let piece_id = board_representation::read_piece_id(friendly_board, initial_bit);
+    let mut captured_piece_id = board_representation::read_piece_id(enemy_board, final_bit);
+
+    // A pawn moving diagonally onto an empty bit is an en-passant, which captures the pawn on the en-passant target bit
+    if piece_id == pieces::PAWN_ID && captured_piece_id == 0 && initial_bit % 8 != final_bit % 8 {
+        if let Some(en_passant_target_bit) = board.en_passant_target_bit {
+            let (_, enemy_board) = team_boards(&mut board, color);
+            board_representation::remove_piece(en_passant_target_bit, enemy_board);
+            captured_piece_id = pieces::PAWN_ID;
+        }
+    }
+
+    // Material gained after each capture in the exchange, from the perspective of the team making that capture
+    let mut gains = vec![board_representation::piece_value(captured_piece_id, color.opposite())];
+    capture(&mut board, color, piece_id, initial_bit, final_bit);
+
+    let mut piece_on_bit = piece_id;
+    let mut capturing_color = color.opposite();
+    while let Some((attacker_bit, attacker_id)) = least_valuable_attacker(&board, final_bit, capturing_color) {
+        capture(&mut board, capturing_color, attacker_id, attacker_bit, final_bit);
+
+        // The king can only capture if the bit isn't defended any more
+        if attacker_id == pieces::KING_ID && check_validation::attackers_to(&board, final_bit, capturing_color.opposite()).len() > 0 {
+            break;
+        }
+
+        gains.push(board_representation::piece_value(piece_on_bit, capturing_color.opposite()) - gains[gains.len() - 1]);
+        piece_on_bit = attacker_id;
+        capturing_color = capturing_color.opposite();
+    }
+
+    // Each team can stop recapturing whenever the next capture would lose material
+    for i in (1..gains.len()).rev() {
+        gains[i - 1] = -(-gains[i - 1]).max(gains[i]);
+    }
+    gains[0]
+}
+
+// Returns the team boards of a color and the other color
+fn team_boards(board: &mut Board, color: PieceColor) -> (&mut [u64; 3], &mut [u64; 3]) {
+    match color {
+        PieceColor::Black => (&mut board.black_board, &mut board.white_board),
+        PieceColor::White => (&mut board.white_board, &mut board.black_board),
+    }
+}
+
+// Moves a piece onto final_bit, removing the piece it captures
+// Only the team boards are updated, which is all attackers_to needs
+fn capture(board: &mut Board, color: PieceColor, piece_id: usize, initial_bit: u8, final_bit: u8) {
+    let (friendly_board, enemy_board) = team_boards(board, color);
+
+    board_representation::remove_piece(initial_bit, friendly_board);
+    board_representation::remove_piece(final_bit, enemy_board);
+    board_representation::insert_piece(final_bit, piece_id, friendly_board);
+}
+
+// Returns the bit and piece id of the least valuable piece of a team attacking a bit
+fn least_valuable_attacker(board: &Board, bit: u8, attacking_color: PieceColor) -> Option<(u8, usize)> {
+    let attacking_board = match attacking_color {
+        PieceColor::Black => &board.black_board,
+        PieceColor::White => &board.white_board,
+    };
+
+    let attackers = check_validation::attackers_to(board, bit, attacking_color);
+    (0..attackers.len())
+        .map(|i| {
+            let attacker_bit = attackers.internal_array[i];
+            (attacker_bit, board_representation::read_piece_id(attacking_board, attacker_bit))
+        })
+        .min_by_key(|&(_, attacker_id)| {
+
+            // The king has no material value, but it always captures last
+            if attacker_id == pieces::KING_ID {
+                i16::MAX
+            } else {
+                board_representation::piece_value(attacker_id, attacking_color)
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board_representation::fen::read_fen;
+    use crate::uci::square_to_bit;
+
+    fn see_uci(fen: &str, uci: &str) -> i16 {
+        let board = read_fen(fen);
+        see(&board, square_to_bit(&uci[0..2]).unwrap(), square_to_bit(&uci[2..4]).unwrap())
+    }
+
+    #[test]
+    fn test_see() {
+
+        // Undefended pawn, and a pawn defended by a pawn
+        assert_eq!(see_uci("4k3/8/8/4p3/8/8/4R3/4K3 w - - 0 1", "e2e5"), 1);
+        assert_eq!(see_uci("4k3/8/3p4/4p3/8/8/4R3/4K3 w - - 0 1", "e2e5"), 1 - 5);
+
+        // Rook takes a knight defended by a pawn, the pawn recaptures, then the second rook takes the pawn
+        assert_eq!(see_uci("4k3/8/3p4/4n3/8/8/4R3/4RK2 w - - 0 1", "e2e5"), 3 - 5 + 1);
+
+        // Pawn takes a knight defended by a pawn
+        assert_eq!(see_uci("4k3/8/3p4/4n3/3P4/8/8/4K3 w - - 0 1", "d4e5"), 3 - 1);
+
+        // The queen behind the rook recaptures (x-ray), so black shouldn't take back with the rook
+        assert_eq!(see_uci("4k3/4r3/8/4p3/8/8/4R3/4Q1K1 w - - 0 1", "e2e5"), 1);
+
+        // The king can't recapture a defended piece
+        assert_eq!(see_uci("8/8/8/4k3/3p4/8/3R4/3RK3 w - - 0 1", "d2d4"), 1);
+
+        // En-passant
+        assert_eq!(see_uci("4k3/8/8/3pP3/8/8/8/4K3 w - 28 0 1", "e5d6"), 1);
+
+        // Not a capture
+        assert_eq!(see_uci("4k3/8/8/8/8/8/4R3/4K3 w - - 0 1", "e2e5"), 0);
+    }
+}
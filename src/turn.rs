@@ -2,7 +2,9 @@ use crate::board_representation;
 use crate::board_representation::{Board, PieceColor};
 use crate::pieces;
 use crate::check_validation;
+use crate::castling;
 use crate::check_validation::MAX_CHECKING_PIECES;
+use crate::pesto;
 use crate::fixed_vecor::*;
 
 // For the thing don't iterate over every thing, use the bits on thing
@@ -14,6 +16,27 @@ use crate::fixed_vecor::*;
 pub enum TurnError {
     Check,
     NotCapture,
+    KingCapture, // Only reachable from positions where the team not to move is in check, kings can't be captured
+}
+
+// The bits a piece moves between, and what else happens as part of the move
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct TurnMove {
+    pub initial_bit: u8,
+    pub final_bit: u8,
+    pub ep_bits: (Option<u8>, Option<u8>), // From get_ep_bits_for_turn
+    pub promotion_piece_id: Option<usize>, // Pawns moving onto the last row are promoted to this, or a queen if it is None
+}
+
+impl TurnMove {
+    pub fn new(initial_bit: u8, final_bit: u8, ep_bits: (Option<u8>, Option<u8>), promotion_piece_id: Option<usize>) -> Self {
+        TurnMove {
+            initial_bit,
+            final_bit,
+            ep_bits,
+            promotion_piece_id,
+        }
+    }
 }
 
 // Takes a turn by moving piece at initial_bit to the final_bit
@@ -22,28 +45,20 @@ pub enum TurnError {
 pub fn take_turn(
     initial_board: &Board,
     piece_id: usize,
-    initial_bit: u8,
-    final_bit: u8,
+    turn_move: TurnMove,
     only_use_captures: bool,
-    ep_bits_for_turn: (Option<u8>, Option<u8>),
     potential_checking_pieces: FixedVector<u8, MAX_CHECKING_PIECES>,
-) -> Result<(Board, i8), TurnError> {
+) -> Result<(Board, i16), TurnError> {
     let mut new_board = initial_board.clone();
 
+    let TurnMove { initial_bit, final_bit, ep_bits: ep_bits_for_turn, promotion_piece_id } = turn_move;
+
     let (en_passant_target_bit, en_passant_capture_bit) = ep_bits_for_turn;
     
-    let (friendly_board, enemy_board, next_piece_to_move) = match new_board.piece_to_move {
-        PieceColor::Black => (
-            &mut new_board.black_board,
-            &mut new_board.white_board,
-            PieceColor::White
-        ),
-
-        PieceColor::White => (
-            &mut new_board.white_board,
-            &mut new_board.black_board,
-            PieceColor::Black
-        ),
+    let next_piece_to_move = new_board.piece_to_move.opposite();
+    let (friendly_board, enemy_board) = match new_board.piece_to_move {
+        PieceColor::Black => (&mut new_board.black_board, &mut new_board.white_board),
+        PieceColor::White => (&mut new_board.white_board, &mut new_board.black_board),
     };
 
     // Get the captured piece id
@@ -52,13 +67,17 @@ pub fn take_turn(
     let capture_piece_id = if let Some(en_passant_capture_bit) = en_passant_capture_bit {
         let id = board_representation::read_piece_id(&enemy_board, en_passant_capture_bit);
         board_representation::remove_piece(en_passant_capture_bit, enemy_board);
-        new_board.en_passant_target_bit = None;
 
         id
     } else {
         board_representation::read_piece_id(&enemy_board, final_bit)
     };
 
+    // Capturing the king would leave the enemy king bit pointing at an empty bit
+    if capture_piece_id == pieces::KING_ID {
+        return Err(TurnError::KingCapture);
+    }
+
     // Get capture piece value
     let capture_piece_value = if capture_piece_id == 0 {
         if only_use_captures {
@@ -67,32 +86,86 @@ pub fn take_turn(
         
         0
     } else {
-        pieces::BLACK_PIECE_INFORMATION[capture_piece_id].piece_value
+        board_representation::piece_value(capture_piece_id, next_piece_to_move)
+    };
+
+    // Get the id of the piece after it has moved, which only changes when a pawn is promoted
+    let final_piece_id = if piece_id == pieces::PAWN_ID && is_promotion_bit(final_bit, initial_board.piece_to_move) {
+        promotion_piece_id.unwrap_or(pieces::QUEEN_ID)
+    } else {
+        piece_id
     };
+    let promotion_value = board_representation::piece_value(final_piece_id, initial_board.piece_to_move) - board_representation::piece_value(piece_id, initial_board.piece_to_move);
 
     // Subtract material value of capture from enemy teams total material
+    // Add material gained by promotion to friendly teams total material
     match new_board.piece_to_move {
-        PieceColor::Black => new_board.white_material -= capture_piece_value,
-        PieceColor::White => new_board.black_material -= capture_piece_value,
+        PieceColor::Black => {
+            new_board.white_material -= capture_piece_value;
+            new_board.black_material += promotion_value;
+        },
+        PieceColor::White => {
+            new_board.black_material -= capture_piece_value;
+            new_board.white_material += promotion_value;
+        },
+    }
+
+    // Update the piece square table scores from the bits which change
+    // The castling rook is handled below once the castling bits are known
+    let moving_color = new_board.piece_to_move;
+    let (friendly_table_score, enemy_table_score) = match moving_color {
+        PieceColor::Black => (&mut new_board.black_table_score, &mut new_board.white_table_score),
+        PieceColor::White => (&mut new_board.white_table_score, &mut new_board.black_table_score),
+    };
+    let capture_bit = en_passant_capture_bit.unwrap_or(final_bit);
+    *enemy_table_score -= pesto::piece_table_score(capture_piece_id, capture_bit, next_piece_to_move);
+    *friendly_table_score -= pesto::piece_table_score(piece_id, initial_bit, moving_color);
+
+    // When castling in Chess960 the final bit can be the rooks bit, so the bit the king ends up on is found seperately
+    let castling_bits = if piece_id == pieces::KING_ID {
+        castling::get_castling_move(initial_board, initial_bit, final_bit)
+    } else {
+        None
+    };
+
+    let piece_final_bit = castling_bits.as_ref().map_or(final_bit, |castling_bits| castling_bits.king_final_bit);
+
+    *friendly_table_score += pesto::piece_table_score(final_piece_id, piece_final_bit, moving_color);
+    if let Some(castling_bits) = &castling_bits {
+        *friendly_table_score -= pesto::piece_table_score(pieces::ROOK_ID, castling_bits.rook_initial_bit, moving_color);
+        *friendly_table_score += pesto::piece_table_score(pieces::ROOK_ID, castling_bits.rook_final_bit, moving_color);
     }
 
     // Move friendly piece to it's new position
     // Remove enemy piece from the position the piece moves to
     board_representation::remove_piece(initial_bit, friendly_board);
-    board_representation::insert_piece(final_bit, piece_id, friendly_board);
+
+    // Move the rook as well if the king is castling
+    if let Some(castling_bits) = &castling_bits {
+        board_representation::remove_piece(castling_bits.rook_initial_bit, friendly_board);
+        board_representation::insert_piece(castling_bits.rook_final_bit, pieces::ROOK_ID, friendly_board);
+    }
+
+    board_representation::insert_piece(piece_final_bit, final_piece_id, friendly_board);
     board_representation::remove_piece(final_bit, enemy_board);
 
+    castling::update_castling_availability(&mut new_board.castling_availability, initial_board, initial_bit, final_bit);
+
     //crate::bitboard_manipulation::debugging::print_bytes(friendly_board[1]);
     //crate::bitboard_manipulation::debugging::print_bytes(new_board.white_board[1]);
 
-    // If the king is moved the potential checking pieces needs to be updated
-    // Otherwise recalculation can be avoided
-    let potential_checking_pieces = if piece_id == pieces::KING_ID {
+    if piece_id == pieces::KING_ID {
         match initial_board.piece_to_move {
-            PieceColor::Black => new_board.black_king_bit = final_bit,
-            PieceColor::White => new_board.white_king_bit = final_bit,
+            PieceColor::Black => new_board.black_king_bit = piece_final_bit,
+            PieceColor::White => new_board.white_king_bit = piece_final_bit,
         }
+    }
 
+    // If the king is moved the potential checking pieces needs to be updated
+    // An en-passant removes two pawns from the same row at once, which can uncover a check along the kings row,
+    // so the potential checking pieces are recalculated then as well instead of relying on the old ones
+    // Otherwise recalculation can be avoided
+    let potential_checking_pieces = if piece_id == pieces::KING_ID || en_passant_capture_bit.is_some() {
         check_validation::get_potential_checking_pieces(&new_board, initial_board.piece_to_move)
     } else {
         potential_checking_pieces
@@ -108,8 +181,12 @@ pub fn take_turn(
         new_board.fullmove_number += 1;
     }
 
-    // set / reset en-passant target bit
-    new_board.en_passant_target_bit = en_passant_target_bit;
+    // The en-passant target bit is only set by a pawn double move, every other move clears it
+    new_board.en_passant_target_bit = if piece_id == pieces::PAWN_ID {
+        en_passant_target_bit
+    } else {
+        None
+    };
 
     if capture_piece_value == 0 {
         if piece_id == pieces::PAWN_ID {
@@ -123,9 +200,23 @@ pub fn take_turn(
 
     new_board.piece_to_move = next_piece_to_move;
 
+    debug_assert_eq!(new_board.white_material, board_representation::recount_material(&new_board, PieceColor::White));
+    debug_assert_eq!(new_board.black_material, board_representation::recount_material(&new_board, PieceColor::Black));
+    debug_assert_eq!(new_board.white_table_score, pesto::recount_table_score(&new_board, PieceColor::White));
+    debug_assert_eq!(new_board.black_table_score, pesto::recount_table_score(&new_board, PieceColor::Black));
+
     Ok((new_board, capture_piece_value))
 }
 
+// Returns true if a pawn of the given color moving to bit would be promoted
+// White pawns are promoted on row 0, and black pawns on row 7
+pub fn is_promotion_bit(bit: u8, piece_color: PieceColor) -> bool {
+    match piece_color {
+        PieceColor::Black => bit >= 56,
+        PieceColor::White => bit < 8,
+    }
+}
+
 // For converting en_passant outputs from move generator to those needed by the turn function
 pub fn get_ep_bits_for_turn(
     en_passant_target_bit: Option<u8>,
@@ -164,11 +255,36 @@ pub fn get_ep_bits_for_turn(
     (en_passant_target_bit, en_passant_capture_bit)
 }
 
+// Returns true if the team to move captures a piece by moving to final_bit
+// ep_bits are the bits from get_ep_bits_for_turn, an en-passant captures a pawn which isn't on the final bit
+pub fn is_capture(board: &Board, final_bit: u8, ep_bits: (Option<u8>, Option<u8>)) -> bool {
+    let enemy_board = match board.piece_to_move {
+        PieceColor::Black => &board.white_board,
+        PieceColor::White => &board.black_board,
+    };
+
+    ep_bits.1.is_some() || board_representation::read_piece_id(enemy_board, final_bit) != 0
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::board_representation::fen::read_fen;
 
+    #[test]
+    fn test_king_capture() {
+
+        // Black is in check with white to move, which can't happen in a game but can be set up with a fen
+        let board = read_fen("4k3/8/8/8/8/8/4R3/4K3 w - - 0 1");
+        let potential_checking_pieces = check_validation::get_potential_checking_pieces(&board, board.piece_to_move);
+        assert_eq!(take_turn(&board, pieces::ROOK_ID, TurnMove::new(51, 3, (None, None), None), false, potential_checking_pieces), Err(TurnError::KingCapture));
+
+        // No legal move targets the enemy king, and the other rook moves are unaffected
+        let legal_moves = crate::legal_moves::legal_moves(&board);
+        assert!(legal_moves.iter().all(|&(_, final_bit, _)| final_bit != board.black_king_bit));
+        assert!(legal_moves.contains(&(51, 11, None)));
+    }
+
     #[test]
     fn test_take_turn() {
 
@@ -177,19 +293,108 @@ mod tests {
         let potential_checking_pieces = check_validation::get_potential_checking_pieces(&board, board.piece_to_move);
         let expected_board = read_fen("r1bqkb1r/pppp1ppp/2n2n2/4N3/4P3/2N5/PPPP1PPP/R1BQKB1R b KQkq - 0 1");
 
-        assert_eq!(take_turn(&board, 2, 42, 27, false, (None, None), potential_checking_pieces), Ok((expected_board, 1)));
+        assert_eq!(take_turn(&board, 2, TurnMove::new(42, 27, (None, None), None), false, potential_checking_pieces), Ok((expected_board, 1)));
 
         // Test white attempting to put it's own king in check (error)
         let board =  read_fen("r1bqkb1r/p1pp1pp1/1p3n1p/4n3/6b1/2N5/PPPP1PPP/R1BQK2R w KQkq - 0 1");
         let potential_checking_pieces = check_validation::get_potential_checking_pieces(&board, board.piece_to_move);
 
-        assert_eq!(take_turn(&board, 6, 59, 51, false, (None, None), potential_checking_pieces), Err(TurnError::Check)); 
+        assert_eq!(take_turn(&board, 6, TurnMove::new(59, 51, (None, None), None), false, potential_checking_pieces), Err(TurnError::Check)); 
 
         // Test black doing an en-passant
         let board =  read_fen("rn1qkbnr/p1ppp1pp/bp6/8/5pP1/2N5/PPPPPP1P/R1BQKBNR b KQkq 33 0 1");
         let potential_checking_pieces = check_validation::get_potential_checking_pieces(&board, board.piece_to_move);
         let expected_board = read_fen("rn1qkbnr/p1ppp1pp/bp6/8/8/2N3p1/PPPPPP1P/R1BQKBNR w KQkq - 0 2");
 
-        assert_eq!(take_turn(&board, 1, 34, 41, false, (None, Some(33)), potential_checking_pieces), Ok((expected_board, 1)));
+        assert_eq!(take_turn(&board, 1, TurnMove::new(34, 41, (None, Some(33)), None), false, potential_checking_pieces), Ok((expected_board, 1)));
+
+        // Test white castling kingside
+        let board = read_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        let potential_checking_pieces = check_validation::get_potential_checking_pieces(&board, board.piece_to_move);
+        let expected_board = read_fen("r3k2r/8/8/8/8/8/8/R4RK1 b kq - 1 1");
+
+        assert_eq!(take_turn(&board, 6, TurnMove::new(59, 57, (None, None), None), false, potential_checking_pieces), Ok((expected_board, 0)));
+
+        // Test white castling kingside in Chess960, with the king moving onto the rooks bit
+        let board = read_fen("1k6/8/8/8/8/8/8/RK5R w AH - 0 1");
+        let potential_checking_pieces = check_validation::get_potential_checking_pieces(&board, board.piece_to_move);
+        let expected_board = read_fen("1k6/8/8/8/8/8/8/R4RK1 b - - 1 1");
+
+        assert_eq!(take_turn(&board, 6, TurnMove::new(62, 56, (None, None), None), false, potential_checking_pieces), Ok((expected_board, 0)));
+
+        // Test black capturing and promoting to a knight
+        let board = read_fen("4k3/8/8/8/8/8/6p1/4K2R b K - 0 1");
+        let potential_checking_pieces = check_validation::get_potential_checking_pieces(&board, board.piece_to_move);
+        let expected_board = read_fen("4k3/8/8/8/8/8/8/4K2n w - - 0 2");
+
+        assert_eq!(take_turn(&board, 1, TurnMove::new(49, 56, (None, None), Some(2)), false, potential_checking_pieces), Ok((expected_board, 5)));
+    }
+
+    #[test]
+    fn test_capturing_promotion() {
+
+        // White pawn on b7 takes the rook on a8 and promotes to a queen
+        let board = read_fen("r3k3/1P6/8/8/8/8/8/4K3 w - - 0 1");
+        let potential_checking_pieces = check_validation::get_potential_checking_pieces(&board, board.piece_to_move);
+        let (new_board, capture_value) = take_turn(&board, pieces::PAWN_ID, TurnMove::new(14, 7, (None, None), Some(pieces::QUEEN_ID)), false, potential_checking_pieces).unwrap();
+
+        // Only the captured piece is returned, the promotion gain is added to the material of the team promoting
+        let piece_value = board_representation::piece_value;
+        assert_eq!(capture_value, piece_value(pieces::ROOK_ID, PieceColor::Black));
+        assert_eq!(new_board.white_material, board.white_material + piece_value(pieces::QUEEN_ID, PieceColor::White) - piece_value(pieces::PAWN_ID, PieceColor::White));
+        assert_eq!(new_board.black_material, board.black_material - piece_value(pieces::ROOK_ID, PieceColor::Black));
+        assert_eq!(
+            new_board.material_balance() - board.material_balance(),
+            piece_value(pieces::ROOK_ID, PieceColor::Black) + piece_value(pieces::QUEEN_ID, PieceColor::White) - piece_value(pieces::PAWN_ID, PieceColor::White)
+        );
+
+        for color in PieceColor::all() {
+            let material = match color {
+                PieceColor::Black => new_board.black_material,
+                PieceColor::White => new_board.white_material,
+            };
+            assert_eq!(material, board_representation::recount_material(&new_board, color));
+        }
+        assert_eq!(new_board, read_fen("Q3k3/8/8/8/8/8/8/4K3 b - - 0 1"));
+
+        // Black taking en-passant, the captured pawn isn't on the final bit
+        let board = read_fen("4k3/8/8/8/5pP1/8/8/4K3 b - 33 0 1");
+        let potential_checking_pieces = check_validation::get_potential_checking_pieces(&board, board.piece_to_move);
+        let (new_board, capture_value) = take_turn(&board, pieces::PAWN_ID, TurnMove::new(34, 41, (None, Some(33)), None), false, potential_checking_pieces).unwrap();
+
+        assert_eq!(capture_value, piece_value(pieces::PAWN_ID, PieceColor::White));
+        assert_eq!((new_board.white_material, new_board.black_material), (board.white_material - piece_value(pieces::PAWN_ID, PieceColor::White), board.black_material));
+        assert_eq!(new_board.white_material, board_representation::recount_material(&new_board, PieceColor::White));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_is_capture() {
+
+        // Knight on c3 taking the pawn on e4, or moving to the empty b5
+        let board = read_fen("4k3/8/8/8/4p3/2N5/8/4K3 w - - 0 1");
+        assert!(is_capture(&board, 35, (None, None)));
+        assert!(!is_capture(&board, 30, (None, None)));
+
+        // En-passant onto the empty d6
+        let board = read_fen("4k3/8/8/3pP3/8/8/8/4K3 w - 28 0 1");
+        assert!(is_capture(&board, 20, (None, Some(28))));
+        assert!(!is_capture(&board, 19, (None, None)));
+    }
+
+    #[test]
+    fn test_en_passant_target_cleared() {
+
+        // White double moves next to a black pawn, then both teams make a quiet move
+        let board = read_fen("4k3/8/8/8/5p2/8/4P3/4K3 w - - 0 1").apply_uci_move("e2e4").unwrap();
+        assert_eq!(board.en_passant_target_bit, Some(35));
+
+        let board = board.apply_uci_move("e8d8").unwrap();
+        assert_eq!(board.en_passant_target_bit, None);
+
+        // The black pawn can't capture en-passant any more
+        let board = board.apply_uci_move("e1d1").unwrap();
+        assert_eq!(board.en_passant_target_bit, None);
+        assert_eq!(board.apply_uci_move("f4e3"), Err(crate::uci::MoveError::IllegalMove));
+        assert!(board.apply_uci_move("f4f3").is_ok());
+    }
+}
@@ -0,0 +1,186 @@
+// Module for converting to and from uci (coordinate) move notation, and applying uci moves to a board
+// E.g. e2e4, e7e8q
+
+use crate::board_representation::{Board, PerspectiveBoards};
+use crate::board_representation;
+use crate::bitboard_manipulation;
+use crate::move_generation;
+use crate::check_validation;
+use crate::pieces;
+use crate::turn::{self, TurnMove};
+use crate::legal_moves::IllegalReason;
+
+#[derive(Debug, PartialEq)]
+pub enum MoveError {
+    InvalidNotation,    // The move string couldn't be parsed
+    NoPiece,            // There is no piece belonging to the team to move on the initial bit
+    IllegalMove,        // The piece can't move to the final bit
+    InvalidPromotion,   // A promotion piece was given for a move that isn't a promotion, or the other way round
+    Check,              // The move would leave the king in check
+}
+
+impl From<IllegalReason> for MoveError {
+    fn from(illegal_reason: IllegalReason) -> Self {
+        match illegal_reason {
+            IllegalReason::NoPieceThere | IllegalReason::NotYourPiece => MoveError::NoPiece,
+            IllegalReason::NotAPseudoLegalTarget | IllegalReason::CastlingThroughCheck => MoveError::IllegalMove,
+            IllegalReason::LeavesKingInCheck => MoveError::Check,
+            IllegalReason::BadPromotion => MoveError::InvalidPromotion,
+        }
+    }
+}
+
+// Converts a square (e.g. e4) to a bit
+pub fn square_to_bit(square: &str) -> Option<u8> {
+    bitboard_manipulation::square_bit(square)
+}
+
+// Converts a bit to a square (e.g. e4)
+pub fn bit_to_square(bit: u8) -> String {
+    bitboard_manipulation::square_name(bit).to_string()
+}
+
+// Formats a move as a uci move, the opposite of parse_uci_move
+pub fn format_uci_move(initial_bit: u8, final_bit: u8, promotion_piece_id: Option<usize>) -> String {
+    let promotion = promotion_piece_id.map_or(String::new(), |piece_id| pieces::BLACK_PIECE_TYPES[piece_id].to_string());
+    format!("{}{}{}", bit_to_square(initial_bit), bit_to_square(final_bit), promotion)
+}
+
+// Parses a uci move into the initial bit, final bit, and promotion piece id
+pub fn parse_uci_move(uci: &str) -> Result<(u8, u8, Option<usize>), MoveError> {
+    if !uci.is_ascii() || uci.len() < 4 || uci.len() > 5 {
+        return Err(MoveError::InvalidNotation);
+    }
+
+    let initial_bit = square_to_bit(&uci[0..2]).ok_or(MoveError::InvalidNotation)?;
+    let final_bit = square_to_bit(&uci[2..4]).ok_or(MoveError::InvalidNotation)?;
+
+    // Promotion pieces are written in lowercase, the same as black pieces in fen
+    let promotion_piece_id = match uci.chars().nth(4) {
+        Some(c) => match pieces::BLACK_PIECE_TYPES.iter().position(|&p| p == c) {
+            Some(piece_id) if piece_id != pieces::PAWN_ID && piece_id != pieces::KING_ID && piece_id != 0 => Some(piece_id),
+            _ => return Err(MoveError::InvalidNotation),
+        },
+        None => None,
+    };
+
+    Ok((initial_bit, final_bit, promotion_piece_id))
+}
+
+impl Board {
+
+    // Applies a uci move to the board, returning the new board
+    // Unlike take_turn the move is validated first
+    pub fn apply_uci_move(&self, uci: &str) -> Result<Board, MoveError> {
+        let (initial_bit, final_bit, promotion_piece_id) = parse_uci_move(uci)?;
+
+        let perspective_boards = PerspectiveBoards::gen(self, self.piece_to_move);
+        let piece_id = board_representation::read_piece_id(perspective_boards.friendly_board, initial_bit);
+
+        if piece_id == 0 {
+            return Err(MoveError::NoPiece);
+        }
+
+        let pinned_bitboard = check_validation::pinned_pieces(self, self.piece_to_move);
+        let (
+            move_bitboard,
+            en_passant_target_bit,
+            en_passant_cap_bits
+        ) = move_generation::generate_turn_moves(self, initial_bit, piece_id, &perspective_boards, pinned_bitboard);
+
+        if !bitboard_manipulation::bit_on(move_bitboard, final_bit) {
+            return Err(MoveError::IllegalMove);
+        }
+
+        // A promotion piece has to be given if, and only if, a pawn is moving onto the last row
+        let is_promotion = piece_id == pieces::PAWN_ID && turn::is_promotion_bit(final_bit, self.piece_to_move);
+        if is_promotion != promotion_piece_id.is_some() {
+            return Err(MoveError::InvalidPromotion);
+        }
+
+        let ep_bits = turn::get_ep_bits_for_turn(en_passant_target_bit, en_passant_cap_bits, final_bit);
+        let potential_checking_pieces = check_validation::get_potential_checking_pieces(self, self.piece_to_move);
+
+        match turn::take_turn(self, piece_id, TurnMove::new(initial_bit, final_bit, ep_bits, promotion_piece_id), false, potential_checking_pieces) {
+            Ok((new_board, _)) => Ok(new_board),
+            Err(_) => Err(MoveError::Check),
+        }
+    }
+
+    // Applies a list of uci moves to the starting position, the same as "position startpos moves ..." in the uci protocol
+    // Returns the index of the first move which couldn't be applied, along with the error
+    pub fn from_moves(uci_moves: &[&str]) -> Result<Board, (usize, MoveError)> {
+        uci_moves.iter().enumerate().try_fold(Board::new(), |board, (i, uci)| {
+            board.apply_uci_move(uci).map_err(|error| (i, error))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board_representation::fen::read_fen;
+
+    #[test]
+    fn test_square_conversion() {
+        assert_eq!(square_to_bit("e1"), Some(59));
+        assert_eq!(square_to_bit("h8"), Some(0));
+        assert_eq!(square_to_bit("i8"), None);
+        assert_eq!(bit_to_square(3), "e8");
+        assert_eq!(bit_to_square(63), "a1");
+    }
+
+    #[test]
+    fn test_format_uci_move() {
+        assert_eq!(format_uci_move(51, 35, None), "e2e4");
+        assert_eq!(format_uci_move(15, 7, Some(pieces::KNIGHT_ID)), "a7a8n");
+        assert_eq!(parse_uci_move(&format_uci_move(10, 2, Some(pieces::QUEEN_ID))), Ok((10, 2, Some(pieces::QUEEN_ID))));
+    }
+
+    #[test]
+    fn test_apply_uci_move() {
+
+        // Test an opening from the starting position
+        let board = Board::new()
+            .apply_uci_move("e2e4").unwrap()
+            .apply_uci_move("e7e5").unwrap()
+            .apply_uci_move("g1f3").unwrap();
+        let expected = read_fen("rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+
+        assert_eq!(board, expected);
+
+        // Test promotion
+        let board = read_fen("8/P6k/8/8/8/8/8/K7 w - - 0 1");
+        let expected = read_fen("Q7/7k/8/8/8/8/8/K7 b - - 0 1");
+
+        assert_eq!(board.apply_uci_move("a7a8q"), Ok(expected));
+        assert_eq!(board.apply_uci_move("a7a8"), Err(MoveError::InvalidPromotion));
+
+        // Test invalid moves
+        let board = Board::new();
+        assert_eq!(board.apply_uci_move("e2e5"), Err(MoveError::IllegalMove));
+        assert_eq!(board.apply_uci_move("e7e5"), Err(MoveError::NoPiece));
+        assert_eq!(board.apply_uci_move("e2e4k"), Err(MoveError::InvalidNotation));
+
+        let board = read_fen("4k3/8/8/8/8/8/4r3/4K3 w - - 0 1");
+        assert_eq!(board.apply_uci_move("e1f2"), Err(MoveError::Check));
+    }
+
+    #[test]
+    fn test_from_moves() {
+
+        // Ten moves of the Ruy Lopez, including castling for both teams
+        let board = Board::from_moves(&[
+            "e2e4", "e7e5", "g1f3", "b8c6", "f1b5", "a7a6", "b5a4", "g8f6", "e1g1", "f8e7",
+            "f1e1", "b7b5", "a4b3", "d7d6", "c2c3", "e8g8", "h2h3", "c6b8", "d2d4", "b8d7",
+        ]);
+        assert_eq!(board, Ok(read_fen("r1bq1rk1/2pnbppp/p2p1n2/1p2p3/3PP3/1BP2N1P/PP3PP1/RNBQR1K1 w - - 1 11")));
+
+        // En-passant and a promotion capturing the rook on h8
+        let board = Board::from_moves(&["e2e4", "d7d5", "e4e5", "f7f5", "e5f6", "b8c6", "f6g7", "c6d4", "g7h8q"]);
+        assert_eq!(board, Ok(read_fen("r1bqkbnQ/ppp1p2p/8/3p4/3n4/8/PPPP1PPP/RNBQKBNR b KQq - 0 5")));
+
+        assert_eq!(Board::from_moves(&[]), Ok(Board::new()));
+        assert_eq!(Board::from_moves(&["e2e4", "e7e5", "e4e5"]), Err((2, MoveError::IllegalMove)));
+    }
+}
@@ -0,0 +1,47 @@
+// Bindings for running the engine in the browser, enabled with the wasm feature
+// Positions are passed in and out as fen strings, and moves as uci moves (e.g. e2e4, e7e8q)
+
+use std::time::Duration;
+
+use wasm_bindgen::prelude::*;
+
+use crate::board_representation::{Board, fen};
+use crate::bot;
+use crate::legal_moves;
+use crate::uci;
+
+// Searches the position for up to ms milliseconds, returning the best move
+// Throws if the fen is invalid, or there is no move to make (checkmate or stalemate)
+#[wasm_bindgen]
+pub fn best_move_from_fen(fen: &str, ms: u32) -> Result<String, JsError> {
+    let board = read_fen(fen)?;
+    let (initial_bit, final_bit, promotion_piece_id) = bot::gen_best_move(&board, Duration::from_millis(ms as u64))
+        .map_err(|_| JsError::new("no legal moves"))?;
+
+    Ok(uci::format_uci_move(initial_bit, final_bit, promotion_piece_id))
+}
+
+// Returns every legal move in the position
+#[wasm_bindgen]
+pub fn legal_moves_from_fen(fen: &str) -> Result<Vec<String>, JsError> {
+    let board = read_fen(fen)?;
+
+    Ok(legal_moves::legal_moves(&board).into_iter()
+        .map(|(initial_bit, final_bit, promotion_piece_id)| uci::format_uci_move(initial_bit, final_bit, promotion_piece_id))
+        .collect())
+}
+
+// Plays a uci move, returning the fen of the new position
+// Throws if the fen is invalid or the move is illegal
+#[wasm_bindgen]
+pub fn apply_move(fen: &str, uci: &str) -> Result<String, JsError> {
+    let board = read_fen(fen)?.apply_uci_move(uci)
+        .map_err(|move_error| JsError::new(&format!("{:?}", move_error)))?;
+
+    Ok(fen::write_fen(&board))
+}
+
+// Same as fen::try_read_fen, with the error converted so it can be thrown in JS
+fn read_fen(fen: &str) -> Result<Board, JsError> {
+    fen::try_read_fen(fen).map_err(|fen_error| JsError::new(&format!("{:?}", fen_error)))
+}
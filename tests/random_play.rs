@@ -0,0 +1,107 @@
+// Property tests which play random legal games from the starting position,
+// checking that the board stays consistent after every move
+
+use proptest::prelude::*;
+
+use chess3::board_representation::{self, Board, PieceColor, PerspectiveBoards};
+use chess3::check_validation;
+use chess3::game_state::GameState;
+use chess3::legal_moves;
+use chess3::move_generation;
+use chess3::pesto;
+use chess3::pieces;
+use chess3::uci;
+
+const MAX_PLIES: usize = 80;
+
+// Returns true if any enemy piece can move onto the king, by generating the moves of every enemy piece
+// Doesn't use the potential checking pieces or attack maps, so it checks them independently
+fn brute_force_in_check(board: &Board, king_color: PieceColor) -> bool {
+    let king_bit = match king_color {
+        PieceColor::Black => board.black_king_bit,
+        PieceColor::White => board.white_king_bit,
+    };
+
+    let enemy_color = king_color.opposite();
+    let perspective_boards = PerspectiveBoards::gen(board, enemy_color);
+
+    (0..64).any(|bit| {
+        let piece_id = board_representation::read_piece_id(perspective_boards.friendly_board, bit);
+        piece_id != 0 && move_generation::generate_moves(board, bit, piece_id, enemy_color, &perspective_boards).0 & (1 << king_bit) != 0
+    })
+}
+
+// Checks everything stored on the board which can be worked out from the pieces
+fn check_board(board: &Board) {
+    for color in PieceColor::all() {
+        let (material, table_score, king_bit) = match color {
+            PieceColor::Black => (board.black_material, board.black_table_score, board.black_king_bit),
+            PieceColor::White => (board.white_material, board.white_table_score, board.white_king_bit),
+        };
+
+        assert_eq!(material, board_representation::recount_material(board, color), "{}", board);
+        assert_eq!(table_score, pesto::recount_table_score(board, color), "{}", board);
+        assert_eq!(check_validation::get_king_bit(board, color), Some(king_bit), "{}", board);
+
+        let potential_checking_pieces = check_validation::get_potential_checking_pieces(board, color);
+        assert_eq!(
+            check_validation::is_king_in_check(board, color, &potential_checking_pieces),
+            brute_force_in_check(board, color),
+            "{}", board
+        );
+    }
+
+    // Only the team to move can be in check, and the teams never share a bit
+    assert!(!brute_force_in_check(board, board.piece_to_move.opposite()), "{}", board);
+
+    assert_eq!(board.white_occupancy() & board.black_occupancy(), 0, "{}", board);
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(48))]
+
+    // Each choice picks a legal move by index (wrapping around), so any sequence of choices is a legal game
+    #[test]
+    fn test_random_play(choices in prop::collection::vec(any::<usize>(), 1..MAX_PLIES)) {
+        let mut game_state = GameState::new(Board::new());
+
+        for choice in choices {
+            let board = game_state.board.clone();
+            check_board(&board);
+
+            let legal_move_boards: Vec<_> = legal_moves::legal_move_boards(&board).collect();
+            if legal_move_boards.is_empty() {
+                break;
+            }
+
+            // Every generated move is accepted by apply_uci_move, and gives the same board as the move generator
+            // Kings are never captured, so no move targets the enemy king
+            let enemy_king_bit = match board.piece_to_move {
+                PieceColor::Black => board.white_king_bit,
+                PieceColor::White => board.black_king_bit,
+            };
+            for ((initial_bit, final_bit, promotion_piece_id), new_board) in &legal_move_boards {
+                prop_assert_ne!(*final_bit, enemy_king_bit);
+                let uci_move = uci::format_uci_move(*initial_bit, *final_bit, *promotion_piece_id);
+                prop_assert_eq!(board.apply_uci_move(&uci_move), Ok(new_board.clone()), "{} {}", board, uci_move);
+            }
+
+            let ((initial_bit, final_bit, promotion_piece_id), new_board) = &legal_move_boards[choice % legal_move_boards.len()];
+
+            // Undoing a move gives back the exact board
+            prop_assert_eq!(game_state.push_move(*initial_bit, *final_bit, *promotion_piece_id), Ok(()));
+            prop_assert_eq!(&game_state.board, new_board);
+            prop_assert_eq!(game_state.pop_move(), Some((*initial_bit, *final_bit, *promotion_piece_id)));
+            prop_assert_eq!(&game_state.board, &board);
+
+            game_state.push_move(*initial_bit, *final_bit, *promotion_piece_id).unwrap();
+
+            // Pawns reaching the first or last row are always promoted
+            for bit in (0..8).chain(56..64) {
+                let board = &game_state.board;
+                prop_assert_ne!(board_representation::read_piece_id(&board.white_board, bit), pieces::PAWN_ID);
+                prop_assert_ne!(board_representation::read_piece_id(&board.black_board, bit), pieces::PAWN_ID);
+            }
+        }
+    }
+}